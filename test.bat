@@ -1,2 +1,2 @@
-cd bge
+cd zenith
 cargo test 
\ No newline at end of file
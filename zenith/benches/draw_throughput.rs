@@ -0,0 +1,103 @@
+// Draw throughput of the batched render queue, run with `cargo bench`.
+// Uses the headless SDL2 backend (dummy video driver + software renderer)
+// so it works without a display.
+
+use std::time::{Duration, Instant};
+use zenith::*;
+
+const FRAMES: u32 = 60;
+
+fn main() {
+    let mut instance = Instance2D::new_headless();
+
+    let pixels: Vec<u8> = (0..16 * 16).flat_map(|i| [i as u8, 255 - i as u8, 128, 255]).collect();
+    let texture = instance
+        .engine_settings
+        .create_texture(16, 16, &pixels)
+        .expect("could not create benchmark texture");
+
+    for count in [1_000, 10_000, 50_000] {
+        let elapsed = bench(&mut instance, count, |i| {
+            DrawCall::Rect(VisualRect::new(
+                Vec2::new((i * 7 % 600) as i32, (i * 13 % 400) as i32),
+                Vec2::new(4, 4),
+                palette(i),
+            ))
+        });
+        report("rects", count, elapsed);
+
+        let elapsed = bench(&mut instance, count, |i| {
+            DrawCall::Sprite(
+                VisualSprite::new(
+                    Vec2::new((i * 7 % 600) as i32, (i * 13 % 400) as i32),
+                    Vec2::new(8, 8),
+                    texture,
+                )
+                .with_tint(palette(i)),
+            )
+        });
+        report("sprites", count, elapsed);
+
+        let elapsed = bench_particles(&mut instance, count);
+        report("particles", count, elapsed);
+    }
+}
+
+// Update + draw of one emitter holding `count` live particles
+fn bench_particles(instance: &mut Instance2D, count: usize) -> Duration {
+    let mut emitter = ParticleEmitter::new()
+        .with_position(Vec2::new(300, 200))
+        .with_spawn_rate(0.0)
+        .with_lifetime(100.0, 100.0)
+        .with_velocity((-50.0, -50.0), (50.0, 50.0))
+        .with_gravity((0.0, 30.0))
+        .with_colors(Color::white(), Color { r: 255, g: 0, b: 0 })
+        .with_max_particles(count);
+    emitter.burst(count as u32);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        emitter.update(1.0 / 60.0);
+        emitter.draw(&mut instance.engine_settings, &Vec2::new(0, 0));
+        instance.engine_settings.update_display();
+    }
+    start.elapsed()
+}
+
+enum DrawCall {
+    Rect(VisualRect),
+    Sprite(VisualSprite),
+}
+
+fn bench(instance: &mut Instance2D, count: usize, make: impl Fn(usize) -> DrawCall) -> Duration {
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        for i in 0..count {
+            match make(i) {
+                DrawCall::Rect(rect) => instance.engine_settings.draw_rect(rect),
+                DrawCall::Sprite(sprite) => instance.engine_settings.draw_sprite(sprite),
+            }
+        }
+        instance.engine_settings.update_display();
+    }
+    start.elapsed()
+}
+
+// A handful of colors so batching has something to group
+fn palette(i: usize) -> Color {
+    match i % 4 {
+        0 => Color { r: 255, g: 0, b: 0 },
+        1 => Color { r: 0, g: 255, b: 0 },
+        2 => Color { r: 0, g: 0, b: 255 },
+        _ => Color::white(),
+    }
+}
+
+fn report(kind: &str, count: usize, elapsed: Duration) {
+    let per_frame = elapsed / FRAMES;
+    let per_second = (count as f64 * FRAMES as f64) / elapsed.as_secs_f64();
+    println!(
+        "{:>7} {:>6} per frame: {:>10.2?} per frame, {:>12.0} draws/sec",
+        kind, count, per_frame, per_second
+    );
+}
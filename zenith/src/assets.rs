@@ -0,0 +1,391 @@
+use crate::{load_tiled_map_from, AssetPack, EngineSettings2D, FrameBuffer, Sound, TextureId, TiledMap};
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Anything the asset store can load from a path, files should be read through the pack
+// so loading works the same from disk and from packed assets
+pub trait Asset: Any + Sized {
+    fn load(path: &Path, pack: &AssetPack, engine_settings: &mut EngineSettings2D) -> Result<Self, String>;
+
+    // Frees whatever the asset holds outside of itself, like GPU textures
+    fn unload(self, _engine_settings: &mut EngineSettings2D) {}
+
+    // Puts a reloaded copy in place of this one
+    fn replace(&mut self, new: Self, engine_settings: &mut EngineSettings2D) {
+        std::mem::replace(self, new).unload(engine_settings);
+    }
+}
+
+// A PNG uploaded as a texture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Texture {
+    pub id: TextureId,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Asset for Texture {
+    fn load(path: &Path, pack: &AssetPack, engine_settings: &mut EngineSettings2D) -> Result<Self, String> {
+        let image = FrameBuffer::decode_png(pack.read(path)?.as_slice())?;
+        Ok(Texture {
+            id: engine_settings.create_texture(image.width, image.height, &image.pixels)?,
+            width: image.width,
+            height: image.height,
+        })
+    }
+
+    fn unload(self, engine_settings: &mut EngineSettings2D) {
+        engine_settings.destroy_texture(self.id);
+    }
+
+    // The new image moves under the old id, so ids copied out of the asset (tilesets,
+    // sprites) keep working and show the new image
+    fn replace(&mut self, new: Self, engine_settings: &mut EngineSettings2D) {
+        if engine_settings.swap_textures(self.id, new.id) {
+            engine_settings.destroy_texture(new.id);
+            *self = Texture { id: self.id, ..new };
+        } else {
+            std::mem::replace(self, new).unload(engine_settings);
+        }
+    }
+}
+
+// A PNG kept in memory
+impl Asset for FrameBuffer {
+    fn load(path: &Path, pack: &AssetPack, _engine_settings: &mut EngineSettings2D) -> Result<Self, String> {
+        FrameBuffer::decode_png(pack.read(path)?.as_slice())
+    }
+}
+
+impl Asset for Sound {
+    fn load(path: &Path, pack: &AssetPack, _engine_settings: &mut EngineSettings2D) -> Result<Self, String> {
+        Sound::from_bytes(&path.to_string_lossy(), pack.read(path)?)
+    }
+}
+
+// Plain text like config or data files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextFile {
+    pub text: String,
+}
+
+impl Asset for TextFile {
+    fn load(path: &Path, pack: &AssetPack, _engine_settings: &mut EngineSettings2D) -> Result<Self, String> {
+        Ok(TextFile {
+            text: pack.read_to_string(path)?,
+        })
+    }
+}
+
+// Tileset textures are not created here, Instance2D::load_tileset_textures loads them as
+// Texture assets
+impl Asset for TiledMap {
+    fn load(path: &Path, pack: &AssetPack, _engine_settings: &mut EngineSettings2D) -> Result<Self, String> {
+        load_tiled_map_from(pack, &path.to_string_lossy())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct AssetKey {
+    type_id: TypeId,
+    path: PathBuf,
+}
+
+// Typed reference to a loaded asset. The store keeps one reference of its own, an
+// asset counts as unused once every handle to it has been dropped.
+pub struct Handle<T> {
+    key: Arc<AssetKey>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn path(&self) -> &Path {
+        &self.key.path
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            key: self.key.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({})", self.key.path.display())
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state)
+    }
+}
+
+type LoadFn = fn(&Path, &AssetPack, &mut EngineSettings2D) -> Result<Box<dyn Any>, String>;
+
+struct Entry {
+    value: Box<dyn Any>,
+    load: LoadFn,
+    unload: fn(Box<dyn Any>, &mut EngineSettings2D),
+    replace: fn(&mut Box<dyn Any>, Box<dyn Any>, &mut EngineSettings2D),
+}
+
+fn load_erased<T: Asset>(
+    path: &Path,
+    pack: &AssetPack,
+    engine_settings: &mut EngineSettings2D,
+) -> Result<Box<dyn Any>, String> {
+    Ok(Box::new(T::load(path, pack, engine_settings)?))
+}
+
+fn unload_erased<T: Asset>(value: Box<dyn Any>, engine_settings: &mut EngineSettings2D) {
+    if let Ok(value) = value.downcast::<T>() {
+        value.unload(engine_settings);
+    }
+}
+
+fn replace_erased<T: Asset>(value: &mut Box<dyn Any>, new: Box<dyn Any>, engine_settings: &mut EngineSettings2D) {
+    if let (Some(value), Ok(new)) = (value.downcast_mut::<T>(), new.downcast::<T>()) {
+        value.replace(*new, engine_settings);
+    }
+}
+
+pub struct Assets {
+    entries: HashMap<Arc<AssetKey>, Entry>,
+    pack: AssetPack,
+    reloaded: Vec<PathBuf>, // Since the last update()
+    #[cfg(feature = "dev")]
+    watcher: Option<crate::hot_reload::FileWatcher>,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Assets {
+            entries: HashMap::new(),
+            pack: AssetPack::default(),
+            reloaded: Vec::new(),
+            #[cfg(feature = "dev")]
+            watcher: match crate::hot_reload::FileWatcher::new() {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    eprintln!("Error: Could not watch assets for changes - {}", e);
+                    None
+                }
+            },
+        }
+    }
+
+    // Where assets get read from, the working directory unless changed. Assets that are
+    // already loaded stay as they are.
+    pub fn set_pack(&mut self, pack: AssetPack) {
+        self.pack = pack;
+    }
+
+    pub fn get_pack(&self) -> &AssetPack {
+        &self.pack
+    }
+
+    // Raw bytes of a file in the pack, for things that aren't assets like music
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        self.pack.read(path)
+    }
+
+    // The same file reached through different relative paths still counts as one asset
+    fn key_path(&self, path: &str) -> PathBuf {
+        match self.pack.disk_path(path) {
+            Some(path) => path.canonicalize().unwrap_or(path),
+            None => PathBuf::from(path.trim_start_matches("./")),
+        }
+    }
+
+    fn key<T: Asset>(&self, path: &str) -> AssetKey {
+        AssetKey {
+            type_id: TypeId::of::<T>(),
+            path: self.key_path(path),
+        }
+    }
+
+    fn handle<T>(key: &Arc<AssetKey>) -> Handle<T> {
+        Handle {
+            key: key.clone(),
+            marker: PhantomData,
+        }
+    }
+
+    // Loads the asset, or hands out another handle if it is already loaded
+    pub fn load<T: Asset>(&mut self, path: &str, engine_settings: &mut EngineSettings2D) -> Result<Handle<T>, String> {
+        let key = self.key::<T>(path);
+        if let Some((key, _)) = self.entries.get_key_value(&key) {
+            return Ok(Self::handle(key));
+        }
+
+        let value = T::load(Path::new(path), &self.pack, engine_settings).map_err(|e| format!("Could not load {} - {}", path, e))?;
+        Ok(self.store(key, value))
+    }
+
+    // Adds an asset made in code under a made up path, replacing (and unloading) one already there
+    pub fn insert<T: Asset>(&mut self, path: &str, value: T, engine_settings: &mut EngineSettings2D) -> Handle<T> {
+        let key = self.key::<T>(path);
+        if let Some(old) = self.entries.remove(&key) {
+            (old.unload)(old.value, engine_settings);
+        }
+        self.store(key, value)
+    }
+
+    fn store<T: Asset>(&mut self, key: AssetKey, value: T) -> Handle<T> {
+        #[cfg(feature = "dev")]
+        if let (Some(watcher), true) = (&mut self.watcher, self.pack.is_disk()) {
+            watcher.watch(&key.path);
+        }
+
+        let key = Arc::new(key);
+        let handle = Self::handle(&key);
+        self.entries.insert(
+            key,
+            Entry {
+                value: Box::new(value),
+                load: load_erased::<T>,
+                unload: unload_erased::<T>,
+                replace: replace_erased::<T>,
+            },
+        );
+        handle
+    }
+
+    pub fn get<T: Asset>(&self, handle: &Handle<T>) -> Option<&T> {
+        self.entries.get(&handle.key)?.value.downcast_ref()
+    }
+
+    pub fn get_mut<T: Asset>(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.entries.get_mut(&handle.key)?.value.downcast_mut()
+    }
+
+    // Handle without loading, None if the asset isn't loaded
+    pub fn find<T: Asset>(&self, path: &str) -> Option<Handle<T>> {
+        let key = self.key::<T>(path);
+        self.entries.get_key_value(&key).map(|(key, _)| Self::handle(key))
+    }
+
+    pub fn is_loaded<T: Asset>(&self, path: &str) -> bool {
+        self.entries.contains_key(&self.key::<T>(path))
+    }
+
+    // How many handles to this asset are alive, not counting the store's own
+    pub fn ref_count<T>(&self, handle: &Handle<T>) -> usize {
+        match self.entries.get_key_value(&handle.key) {
+            Some((key, _)) => Arc::strong_count(key) - 1,
+            None => 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Drops every asset no handle points to anymore, returns how many went
+    pub fn unload_unused(&mut self, engine_settings: &mut EngineSettings2D) -> usize {
+        let unused: Vec<Arc<AssetKey>> = self
+            .entries
+            .keys()
+            .filter(|key| Arc::strong_count(key) == 1)
+            .cloned()
+            .collect();
+        for key in &unused {
+            if let Some(entry) = self.entries.remove(key) {
+                (entry.unload)(entry.value, engine_settings);
+            }
+        }
+        unused.len()
+    }
+
+    // Loads the file again for every asset type it was loaded as, existing handles see the
+    // new data. Every type is loaded before any is swapped in, so if one fails they all
+    // keep the old data.
+    pub fn reload(&mut self, path: &str, engine_settings: &mut EngineSettings2D) -> Result<usize, String> {
+        let path = self.key_path(path);
+        let mut loaded: Vec<(Arc<AssetKey>, Box<dyn Any>)> = Vec::new();
+        for (key, entry) in self.entries.iter().filter(|(key, _)| key.path == path) {
+            match (entry.load)(&path, &self.pack, engine_settings) {
+                Ok(value) => loaded.push((key.clone(), value)),
+                Err(e) => {
+                    for (key, value) in loaded {
+                        (self.entries[&key].unload)(value, engine_settings);
+                    }
+                    return Err(format!("Could not reload {} - {}", key.path.display(), e));
+                }
+            }
+        }
+        let reloaded = loaded.len();
+        for (key, value) in loaded {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                (entry.replace)(&mut entry.value, value, engine_settings);
+            }
+        }
+        if reloaded > 0 && !self.reloaded.contains(&path) {
+            self.reloaded.push(path);
+        }
+        Ok(reloaded)
+    }
+
+    // Paths reloaded since the start of this frame, for scripts that cache things built from assets
+    pub fn reloaded(&self) -> &[PathBuf] {
+        &self.reloaded
+    }
+
+    pub fn was_reloaded<T>(&self, handle: &Handle<T>) -> bool {
+        self.reloaded.contains(&handle.key.path)
+    }
+
+    // Called by the event loop at the start of every frame, with the dev feature this is
+    // where changed files get reloaded
+    #[cfg_attr(not(feature = "dev"), allow(unused_variables))]
+    pub fn update(&mut self, engine_settings: &mut EngineSettings2D) {
+        self.reloaded.clear();
+
+        #[cfg(feature = "dev")]
+        {
+            let Some(watcher) = &mut self.watcher else {
+                return;
+            };
+            for path in watcher.changed() {
+                if let Err(e) = self.reload(&path.to_string_lossy(), engine_settings) {
+                    eprintln!("Error: {}", e);
+                }
+            }
+        }
+    }
+
+    // Unloads everything, handles still around just stop resolving
+    pub fn clear(&mut self, engine_settings: &mut EngineSettings2D) {
+        for (_, entry) in self.entries.drain() {
+            (entry.unload)(entry.value, engine_settings);
+        }
+    }
+}
@@ -0,0 +1,830 @@
+// Software mixer on top of the SDL2 audio device, so no SDL_mixer is needed.
+// Everything is mixed as stereo f32 at SAMPLE_RATE, sounds are converted on load
+// and music is decoded and converted bit by bit while it plays.
+use crate::render::RenderingEnvironment;
+use crate::{EntityId, Vec2};
+
+use sdl2::audio::{AudioCallback, AudioSpecDesired};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+const CHANNEL_COUNT: usize = 32;
+
+// Decoded sound effect, cheap to clone
+#[derive(Clone, Debug)]
+pub struct Sound {
+    samples: Arc<Vec<f32>>, // Interleaved stereo at SAMPLE_RATE
+}
+
+impl Sound {
+    // WAV or OGG by file extension
+    pub fn load(path: &str) -> Result<Sound, String> {
+        Self::decode(Decoder::open(Path::new(path))?)
+    }
+
+    // A WAV or OGG file already in memory, `name` only needs the right extension
+    pub fn from_bytes(name: &str, bytes: Vec<u8>) -> Result<Sound, String> {
+        Self::decode(Decoder::new(Box::new(Cursor::new(bytes)), Path::new(name))?)
+    }
+
+    fn decode(decoder: Decoder) -> Result<Sound, String> {
+        let mut decoder = decoder;
+        let mut resampler = Resampler::new(decoder.sample_rate);
+        let mut samples = Vec::new();
+        while let Some(block) = decoder.next_block()? {
+            resampler.process(&block, &mut samples);
+        }
+        Ok(Sound {
+            samples: Arc::new(samples),
+        })
+    }
+
+    // Samples are interleaved with the given channel count, 1 or 2 channels
+    pub fn from_samples(channels: u16, sample_rate: u32, samples: &[f32]) -> Sound {
+        let stereo = to_stereo(channels, samples.iter().copied());
+        let mut resampled = Vec::new();
+        Resampler::new(sample_rate).process(&stereo, &mut resampled);
+        Sound {
+            samples: Arc::new(resampled),
+        }
+    }
+
+    // Seconds
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / SAMPLE_RATE as f32
+    }
+
+    fn frames(&self) -> usize {
+        self.samples.len() / 2
+    }
+}
+
+fn to_stereo(channels: u16, samples: impl Iterator<Item = f32>) -> Vec<f32> {
+    match channels {
+        1 => samples.flat_map(|s| [s, s]).collect(),
+        2 => samples.collect(),
+        // Anything above stereo just keeps the first two channels
+        n => {
+            let samples: Vec<f32> = samples.collect();
+            samples.chunks_exact(n as usize).flat_map(|frame| [frame[0], frame[1]]).collect()
+        }
+    }
+}
+
+// Linear resampler that keeps its position between blocks so streamed audio doesn't click
+struct Resampler {
+    step: f64,
+    position: f64,
+    previous: [f32; 2],
+}
+
+impl Resampler {
+    fn new(source_rate: u32) -> Self {
+        Resampler {
+            step: source_rate as f64 / SAMPLE_RATE as f64,
+            position: 0.0,
+            previous: [0.0, 0.0],
+        }
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let frames = input.len() / 2;
+        if frames == 0 {
+            return;
+        }
+        // Frame -1 is the last frame of the previous block
+        let frame = |i: isize| -> [f32; 2] {
+            if i < 0 {
+                self.previous
+            } else {
+                [input[i as usize * 2], input[i as usize * 2 + 1]]
+            }
+        };
+        while self.position < (frames - 1) as f64 {
+            let i = self.position.floor() as isize;
+            let t = (self.position - i as f64) as f32;
+            let (a, b) = (frame(i), frame(i + 1));
+            output.push(a[0] + (b[0] - a[0]) * t);
+            output.push(a[1] + (b[1] - a[1]) * t);
+            self.position += self.step;
+        }
+        self.position -= frames as f64;
+        self.previous = frame(frames as isize - 1);
+    }
+}
+
+// Decoders read from files or from packed bytes, and music decodes on the audio thread
+trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+type SoundReader = BufReader<Box<dyn ReadSeek>>;
+
+enum DecoderSource {
+    Wav(hound::WavReader<SoundReader>),
+    Ogg(Box<lewton::inside_ogg::OggStreamReader<SoundReader>>),
+}
+
+// Hands out stereo blocks at the file's own sample rate
+struct Decoder {
+    source: DecoderSource,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl Decoder {
+    fn open(path: &Path) -> Result<Decoder, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::new(Box::new(file), path)
+    }
+
+    // The format comes from the extension of `path`
+    fn new(reader: Box<dyn ReadSeek>, path: &Path) -> Result<Decoder, String> {
+        let reader = BufReader::new(reader);
+        let error = |e: String| format!("{}: {}", path.display(), e);
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("wav") => {
+                let wav = hound::WavReader::new(reader).map_err(|e| error(e.to_string()))?;
+                let spec = wav.spec();
+                Ok(Decoder {
+                    source: DecoderSource::Wav(wav),
+                    channels: spec.channels,
+                    sample_rate: spec.sample_rate,
+                })
+            }
+            Some("ogg") => {
+                let ogg = lewton::inside_ogg::OggStreamReader::new(reader).map_err(|e| error(e.to_string()))?;
+                Ok(Decoder {
+                    channels: ogg.ident_hdr.audio_channels as u16,
+                    sample_rate: ogg.ident_hdr.audio_sample_rate,
+                    source: DecoderSource::Ogg(Box::new(ogg)),
+                })
+            }
+            _ => Err(error("Unknown sound format, expected .wav or .ogg".to_string())),
+        }
+    }
+
+    fn next_block(&mut self) -> Result<Option<Vec<f32>>, String> {
+        const BLOCK_FRAMES: usize = 4096;
+        match &mut self.source {
+            DecoderSource::Wav(wav) => {
+                let spec = wav.spec();
+                let wanted = BLOCK_FRAMES * spec.channels as usize;
+                let block: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+                    hound::SampleFormat::Float => wav.samples::<f32>().take(wanted).collect(),
+                    hound::SampleFormat::Int => {
+                        let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                        wav.samples::<i32>().take(wanted).map(|s| s.map(|s| s as f32 / scale)).collect()
+                    }
+                };
+                let block = block.map_err(|e| e.to_string())?;
+                if block.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(to_stereo(self.channels, block.into_iter())))
+            }
+            DecoderSource::Ogg(ogg) => {
+                let packet = ogg.read_dec_packet_itl().map_err(|e| e.to_string())?;
+                Ok(packet.map(|packet| {
+                    to_stereo(self.channels, packet.into_iter().map(|s| s as f32 / 32768.0))
+                }))
+            }
+        }
+    }
+}
+
+// Reopened from the start when music loops
+enum MusicSource {
+    File(PathBuf),
+    Memory(PathBuf, Arc<Vec<u8>>),
+}
+
+impl MusicSource {
+    fn open(&self) -> Result<Decoder, String> {
+        match self {
+            MusicSource::File(path) => Decoder::open(path),
+            MusicSource::Memory(name, bytes) => Decoder::new(Box::new(Cursor::new(ArcBytes(bytes.clone()))), name),
+        }
+    }
+}
+
+// Cursor wants something that is AsRef<[u8]>, this saves copying the file on every loop
+struct ArcBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for ArcBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Music streamed from disk or memory while it plays
+struct Music {
+    source: MusicSource,
+    decoder: Decoder,
+    resampler: Resampler,
+    buffer: Vec<f32>,
+    position: usize,
+    looping: bool,
+    volume: Volume,
+}
+
+impl Music {
+    fn next_frame(&mut self) -> Option<[f32; 2]> {
+        let mut reopened = false;
+        while self.position >= self.buffer.len() / 2 {
+            self.buffer.clear();
+            self.position = 0;
+            match self.decoder.next_block() {
+                Ok(Some(block)) => self.resampler.process(&block, &mut self.buffer),
+                // Only once per frame, an empty file would spin forever otherwise
+                Ok(None) if self.looping && !reopened => match self.source.open() {
+                    Ok(decoder) => {
+                        self.decoder = decoder;
+                        reopened = true;
+                    }
+                    Err(e) => {
+                        eprintln!("Error: Could not loop music - {}", e);
+                        return None;
+                    }
+                },
+                Ok(None) => return None,
+                Err(e) => {
+                    eprintln!("Error: Could not decode music - {}", e);
+                    return None;
+                }
+            }
+        }
+        let frame = [self.buffer[self.position * 2], self.buffer[self.position * 2 + 1]];
+        self.position += 1;
+        Some(frame)
+    }
+}
+
+// Volume with an optional linear fade towards a target
+#[derive(Clone, Debug)]
+struct Volume {
+    current: f32,
+    target: f32,
+    step: f32,            // Change per frame while fading
+    stop_after_fade: bool,
+}
+
+impl Volume {
+    fn new(volume: f32) -> Self {
+        Volume {
+            current: volume,
+            target: volume,
+            step: 0.0,
+            stop_after_fade: false,
+        }
+    }
+
+    fn fade_to(&mut self, target: f32, seconds: f32, stop_after_fade: bool) {
+        self.target = target;
+        self.stop_after_fade = stop_after_fade;
+        let frames = (seconds * SAMPLE_RATE as f32).max(1.0);
+        self.step = (target - self.current).abs() / frames;
+        if seconds <= 0.0 {
+            self.current = target;
+        }
+    }
+
+    // Returns the volume for this frame, None once a fade out has finished
+    fn advance(&mut self) -> Option<f32> {
+        if self.current < self.target {
+            self.current = (self.current + self.step).min(self.target);
+        } else if self.current > self.target {
+            self.current = (self.current - self.step).max(self.target);
+        } else if self.stop_after_fade {
+            return None;
+        }
+        Some(self.current)
+    }
+}
+
+// Handle to something playing on the mixer, goes stale once the sound stops
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelId {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlaySettings {
+    pub volume: f32,
+    pub pan: f32, // -1.0 left, 0.0 center, 1.0 right
+    pub looping: bool,
+    pub fade_in: f32, // Seconds
+}
+
+impl Default for PlaySettings {
+    fn default() -> Self {
+        PlaySettings {
+            volume: 1.0,
+            pan: 0.0,
+            looping: false,
+            fade_in: 0.0,
+        }
+    }
+}
+
+struct Channel {
+    sound: Sound,
+    position: usize,
+    volume: Volume,
+    pan: f32,
+    gain: f32, // Set from the distance for positional sounds
+    looping: bool,
+    paused: bool,
+}
+
+pub struct Mixer {
+    channels: Vec<Option<Channel>>,
+    generations: Vec<u32>,
+    music: Option<Music>,
+    master_volume: f32,
+    music_volume: f32,
+    muted: bool,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Mixer {
+            channels: (0..CHANNEL_COUNT).map(|_| None).collect(),
+            generations: vec![0; CHANNEL_COUNT],
+            music: None,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            muted: false,
+        }
+    }
+
+    fn channel_mut(&mut self, id: ChannelId) -> Option<&mut Channel> {
+        if self.generations.get(id.index) != Some(&id.generation) {
+            return None;
+        }
+        self.channels[id.index].as_mut()
+    }
+
+    fn stop_channel(&mut self, index: usize) {
+        self.channels[index] = None;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+    }
+
+    // Mixes interleaved stereo into `out`
+    pub fn mix(&mut self, out: &mut [f32]) {
+        out.iter_mut().for_each(|sample| *sample = 0.0);
+
+        for index in 0..self.channels.len() {
+            let mut finished = false;
+            if let Some(channel) = &mut self.channels[index] {
+                if channel.paused {
+                    continue;
+                }
+                let left_gain = (1.0 - channel.pan).min(1.0) * channel.gain;
+                let right_gain = (1.0 + channel.pan).min(1.0) * channel.gain;
+                let frames = channel.sound.frames();
+
+                for frame in out.chunks_exact_mut(2) {
+                    if channel.position >= frames {
+                        if channel.looping && frames > 0 {
+                            channel.position = 0;
+                        } else {
+                            finished = true;
+                            break;
+                        }
+                    }
+                    let Some(volume) = channel.volume.advance() else {
+                        finished = true;
+                        break;
+                    };
+                    let samples = &channel.sound.samples;
+                    frame[0] += samples[channel.position * 2] * volume * left_gain;
+                    frame[1] += samples[channel.position * 2 + 1] * volume * right_gain;
+                    channel.position += 1;
+                }
+            }
+            if finished {
+                self.stop_channel(index);
+            }
+        }
+
+        if let Some(music) = &mut self.music {
+            let mut finished = false;
+            for frame in out.chunks_exact_mut(2) {
+                let (Some(volume), Some(samples)) = (music.volume.advance(), music.next_frame()) else {
+                    finished = true;
+                    break;
+                };
+                frame[0] += samples[0] * volume * self.music_volume;
+                frame[1] += samples[1] * volume * self.music_volume;
+            }
+            if finished {
+                self.music = None;
+            }
+        }
+
+        // Still mixed while muted so everything stays in sync when unmuting
+        let master = if self.muted { 0.0 } else { self.master_volume };
+        for sample in out.iter_mut() {
+            *sample = (*sample * master).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+impl AudioCallback for Mixer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.mix(out);
+    }
+}
+
+enum Device {
+    Null(Box<Mixer>),
+    Sdl2(sdl2::audio::AudioDevice<Mixer>),
+}
+
+// How loudness drops between min_distance and max_distance, silent past max_distance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    None,
+    Linear,
+    Inverse { rolloff: f32 },     // Like real sound, higher rolloff drops faster
+    Exponential { rolloff: f32 }, // (distance / min_distance) ^ -rolloff
+}
+
+impl Falloff {
+    fn gain(&self, distance: f32, min_distance: f32, max_distance: f32) -> f32 {
+        if distance >= max_distance && *self != Falloff::None {
+            return 0.0;
+        }
+        if distance <= min_distance {
+            return 1.0;
+        }
+        let gain = match *self {
+            Falloff::None => 1.0,
+            Falloff::Linear => 1.0 - (distance - min_distance) / (max_distance - min_distance),
+            Falloff::Inverse { rolloff } => min_distance / (min_distance + rolloff * (distance - min_distance)),
+            Falloff::Exponential { rolloff } => (distance / min_distance.max(0.001)).powf(-rolloff),
+        };
+        gain.clamp(0.0, 1.0)
+    }
+}
+
+// Who hears positional sounds
+#[derive(Clone, Debug, PartialEq)]
+pub enum Listener {
+    Camera,           // Center of the screen
+    Entity(EntityId), // The "location" tag of the entity, the camera again once it's gone
+}
+
+#[derive(Clone, Debug)]
+pub struct Attenuation {
+    pub min_distance: f32, // Full volume closer than this, in pixels
+    pub max_distance: f32,
+    pub falloff: Falloff,
+    pub pan_distance: f32, // Horizontal distance at which the sound is panned all the way
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self::new(50.0, 600.0)
+    }
+}
+
+impl Attenuation {
+    pub fn new(min_distance: f32, max_distance: f32) -> Self {
+        Attenuation {
+            min_distance,
+            max_distance: max_distance.max(min_distance),
+            falloff: Falloff::Linear,
+            pan_distance: 300.0,
+        }
+    }
+
+    pub fn with_falloff(self, falloff: Falloff) -> Self {
+        let mut x = self;
+        x.falloff = falloff;
+        x
+    }
+
+    pub fn with_pan_distance(self, pan_distance: f32) -> Self {
+        let mut x = self;
+        x.pan_distance = pan_distance;
+        x
+    }
+
+    // (gain, pan) for a sound at `source` heard from `listener`
+    pub fn evaluate(&self, listener: (f32, f32), source: (f32, f32)) -> (f32, f32) {
+        let dx = source.0 - listener.0;
+        let dy = source.1 - listener.1;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let gain = self.falloff.gain(distance, self.min_distance, self.max_distance);
+        let pan = if self.pan_distance > 0.0 {
+            (dx / self.pan_distance).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        (gain, pan)
+    }
+}
+
+// A sound following an entity around
+struct PositionalSound {
+    channel: ChannelId,
+    entity: EntityId,
+    attenuation: Attenuation,
+}
+
+pub struct Audio {
+    device: Device,
+    listener: Listener,
+    positional: Vec<PositionalSound>,
+}
+
+impl Audio {
+    // Plays nothing, for tests and machines without sound hardware. The mixer
+    // still runs when mix() is called so results can be checked.
+    pub fn new_null() -> Self {
+        Audio {
+            device: Device::Null(Box::default()),
+            listener: Listener::Camera,
+            positional: Vec::new(),
+        }
+    }
+
+    pub fn new(env: &RenderingEnvironment) -> Result<Self, String> {
+        match env {
+            RenderingEnvironment::Sdl2(sdl2_env) => {
+                let subsystem = sdl2_env.sdl_context.audio()?;
+                let spec = AudioSpecDesired {
+                    freq: Some(SAMPLE_RATE as i32),
+                    channels: Some(2),
+                    samples: Some(1024),
+                };
+                let device = subsystem.open_playback(None, &spec, |_| Mixer::new())?;
+                device.resume();
+                Ok(Audio {
+                    device: Device::Sdl2(device),
+                    listener: Listener::Camera,
+                    positional: Vec::new(),
+                })
+            }
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.device, Device::Null(_))
+    }
+
+    fn with_mixer<R>(&mut self, f: impl FnOnce(&mut Mixer) -> R) -> R {
+        match &mut self.device {
+            Device::Null(mixer) => f(mixer),
+            Device::Sdl2(device) => f(&mut device.lock()),
+        }
+    }
+
+    // Pulls samples out of the mixer by hand, only does anything on the null device
+    pub fn mix(&mut self, out: &mut [f32]) {
+        if let Device::Null(mixer) = &mut self.device {
+            mixer.mix(out);
+        }
+    }
+
+    pub fn play(&mut self, sound: &Sound) -> Option<ChannelId> {
+        self.play_with(sound, PlaySettings::default())
+    }
+
+    // None when every channel is busy
+    pub fn play_with(&mut self, sound: &Sound, settings: PlaySettings) -> Option<ChannelId> {
+        self.with_mixer(|mixer| {
+            let index = mixer.channels.iter().position(|channel| channel.is_none())?;
+            let mut volume = Volume::new(if settings.fade_in > 0.0 { 0.0 } else { settings.volume });
+            volume.fade_to(settings.volume, settings.fade_in, false);
+            mixer.channels[index] = Some(Channel {
+                sound: sound.clone(),
+                position: 0,
+                volume,
+                pan: settings.pan.clamp(-1.0, 1.0),
+                looping: settings.looping,
+                gain: 1.0,
+                paused: false,
+            });
+            Some(ChannelId {
+                index,
+                generation: mixer.generations[index],
+            })
+        })
+    }
+
+    // Plays a sound that gets quieter and pans with the entity's distance and direction
+    // from the listener, updated every frame by the event loop. Starts silent until the
+    // first update so far away sounds don't blip. Stops when the entity is despawned.
+    pub fn play_at(
+        &mut self,
+        sound: &Sound,
+        entity: EntityId,
+        settings: PlaySettings,
+        attenuation: Attenuation,
+    ) -> Option<ChannelId> {
+        let channel = self.play_with(sound, settings)?;
+        self.with_mixer(|mixer| {
+            if let Some(channel) = mixer.channel_mut(channel) {
+                channel.gain = 0.0;
+            }
+        });
+        self.positional.push(PositionalSound {
+            channel,
+            entity,
+            attenuation,
+        });
+        Some(channel)
+    }
+
+    pub fn set_listener(&mut self, listener: Listener) {
+        self.listener = listener;
+    }
+
+    pub fn get_listener(&self) -> &Listener {
+        &self.listener
+    }
+
+    // Recomputes gain and pan of positional sounds, `locate` gives an entity's position.
+    // Sounds whose entity is gone are stopped, ones on an entity without a location keep
+    // their last gain and pan.
+    pub fn update_positional(
+        &mut self,
+        listener: (f32, f32),
+        alive: impl Fn(EntityId) -> bool,
+        locate: impl Fn(EntityId) -> Option<Vec2>,
+    ) {
+        if self.positional.is_empty() {
+            return;
+        }
+        let mut positional = std::mem::take(&mut self.positional);
+        self.with_mixer(|mixer| {
+            positional.retain(|sound| {
+                if mixer.channel_mut(sound.channel).is_some() && !alive(sound.entity) {
+                    mixer.stop_channel(sound.channel.index);
+                }
+                let Some(channel) = mixer.channel_mut(sound.channel) else {
+                    return false;
+                };
+                if let Some(location) = locate(sound.entity) {
+                    let (gain, pan) = sound
+                        .attenuation
+                        .evaluate(listener, (location.x as f32, location.y as f32));
+                    channel.gain = gain;
+                    channel.pan = pan;
+                }
+                true
+            });
+        });
+        self.positional = positional;
+    }
+
+    pub fn is_playing(&mut self, channel: ChannelId) -> bool {
+        self.with_mixer(|mixer| mixer.channel_mut(channel).is_some())
+    }
+
+    pub fn stop(&mut self, channel: ChannelId) {
+        self.with_mixer(|mixer| {
+            if mixer.channel_mut(channel).is_some() {
+                mixer.stop_channel(channel.index);
+            }
+        })
+    }
+
+    pub fn stop_all(&mut self) {
+        self.with_mixer(|mixer| {
+            for index in 0..mixer.channels.len() {
+                if mixer.channels[index].is_some() {
+                    mixer.stop_channel(index);
+                }
+            }
+        })
+    }
+
+    pub fn set_paused(&mut self, channel: ChannelId, paused: bool) {
+        self.with_mixer(|mixer| {
+            if let Some(channel) = mixer.channel_mut(channel) {
+                channel.paused = paused;
+            }
+        })
+    }
+
+    pub fn set_volume(&mut self, channel: ChannelId, volume: f32) {
+        self.with_mixer(|mixer| {
+            if let Some(channel) = mixer.channel_mut(channel) {
+                channel.volume = Volume::new(volume);
+            }
+        })
+    }
+
+    pub fn set_pan(&mut self, channel: ChannelId, pan: f32) {
+        self.with_mixer(|mixer| {
+            if let Some(channel) = mixer.channel_mut(channel) {
+                channel.pan = pan.clamp(-1.0, 1.0);
+            }
+        })
+    }
+
+    // Fades to silence and then stops the channel
+    pub fn fade_out(&mut self, channel: ChannelId, seconds: f32) {
+        self.with_mixer(|mixer| {
+            if let Some(channel) = mixer.channel_mut(channel) {
+                channel.volume.fade_to(0.0, seconds, true);
+            }
+        })
+    }
+
+    // Replaces whatever music is playing
+    pub fn play_music(&mut self, path: &str, looping: bool, fade_in: f32) -> Result<(), String> {
+        self.start_music(MusicSource::File(PathBuf::from(path)), looping, fade_in)
+    }
+
+    // Same as play_music for a file already in memory, like one read out of an asset pack.
+    // `name` only needs the right extension.
+    pub fn play_music_bytes(&mut self, name: &str, bytes: Vec<u8>, looping: bool, fade_in: f32) -> Result<(), String> {
+        self.start_music(MusicSource::Memory(PathBuf::from(name), Arc::new(bytes)), looping, fade_in)
+    }
+
+    fn start_music(&mut self, source: MusicSource, looping: bool, fade_in: f32) -> Result<(), String> {
+        let decoder = source.open()?;
+        let mut volume = Volume::new(if fade_in > 0.0 { 0.0 } else { 1.0 });
+        volume.fade_to(1.0, fade_in, false);
+        let music = Music {
+            source,
+            resampler: Resampler::new(decoder.sample_rate),
+            decoder,
+            buffer: Vec::new(),
+            position: 0,
+            looping,
+            volume,
+        };
+        self.with_mixer(|mixer| mixer.music = Some(music));
+        Ok(())
+    }
+
+    pub fn stop_music(&mut self, fade_out: f32) {
+        self.with_mixer(|mixer| {
+            if fade_out <= 0.0 {
+                mixer.music = None;
+            } else if let Some(music) = &mut mixer.music {
+                music.volume.fade_to(0.0, fade_out, true);
+            }
+        })
+    }
+
+    pub fn is_music_playing(&mut self) -> bool {
+        self.with_mixer(|mixer| mixer.music.is_some())
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.with_mixer(|mixer| mixer.music_volume = volume.max(0.0))
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.with_mixer(|mixer| mixer.master_volume = volume.max(0.0))
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.with_mixer(|mixer| mixer.muted = muted)
+    }
+
+    pub fn is_muted(&mut self) -> bool {
+        self.with_mixer(|mixer| mixer.muted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_generations_wrap_around() {
+        let mut audio = Audio::new_null();
+        let sound = Sound::from_samples(1, SAMPLE_RATE, &[0.5; 4]);
+        let Device::Null(mixer) = &mut audio.device else {
+            unreachable!()
+        };
+        mixer.generations[0] = u32::MAX;
+        let old = audio.play(&sound).unwrap();
+        audio.stop(old);
+        let new = audio.play(&sound).unwrap();
+        assert_eq!(new.generation, 0);
+        assert!(!audio.is_playing(old));
+        assert!(audio.is_playing(new));
+    }
+}
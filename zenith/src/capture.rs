@@ -0,0 +1,186 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+// Bytes of RGBA data for the size, None if that doesn't fit in memory
+pub(crate) fn rgba_len(width: u32, height: u32) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)?.checked_mul(4)
+}
+
+pub(crate) fn rgba_len_text(width: u32, height: u32) -> String {
+    match rgba_len(width, height) {
+        Some(len) => len.to_string(),
+        None => "more than fits in memory".to_string(),
+    }
+}
+
+// A captured frame, RGBA with 4 bytes per pixel row by row
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![0; rgba_len(width, height).expect("Frame is too big to fit in memory")],
+        }
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, String> {
+        if rgba_len(width, height) != Some(pixels.len()) {
+            return Err(format!(
+                "Frame data is {} bytes but {}x{} RGBA needs {}",
+                pixels.len(),
+                width,
+                height,
+                rgba_len_text(width, height)
+            ));
+        }
+        Ok(FrameBuffer { width, height, pixels })
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        Some([self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]])
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        self.pixels[i..i + 4].copy_from_slice(&pixel);
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::decode_png(BufReader::new(file)).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn decode_png(reader: impl std::io::Read) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(reader);
+        // Palettes, low bit depths and 16 bit all get turned into 8 bit channels
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+        data.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => data,
+            png::ColorType::Rgb => data.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => data.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => data.iter().flat_map(|p| [*p, *p, *p, 255]).collect(),
+            png::ColorType::Indexed => return Err("Indexed PNG was not expanded".to_string()),
+        };
+        FrameBuffer::from_pixels(info.width, info.height, pixels)
+    }
+}
+
+// Dumps every Nth presented frame into a directory as frame_000000.png, frame_000001.png...
+pub struct FrameRecorder {
+    directory: PathBuf,
+    every_nth: u32,
+    frame: u32,
+    saved: u32,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: impl AsRef<Path>, every_nth: u32) -> Result<Self, String> {
+        let directory = directory.as_ref().to_path_buf();
+        std::fs::create_dir_all(&directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+        Ok(FrameRecorder {
+            directory,
+            every_nth: every_nth.max(1),
+            frame: 0,
+            saved: 0,
+        })
+    }
+
+    pub fn wants_frame(&self) -> bool {
+        self.frame.is_multiple_of(self.every_nth)
+    }
+
+    pub fn record(&mut self, frame: Option<&FrameBuffer>) {
+        if let (true, Some(frame)) = (self.wants_frame(), frame) {
+            let path = self.directory.join(format!("frame_{:06}.png", self.saved));
+            match frame.save_png(&path) {
+                Ok(()) => self.saved += 1,
+                Err(e) => eprintln!("Error: Could not record frame - {}", e),
+            }
+        }
+        self.frame += 1;
+    }
+
+    pub fn frames_saved(&self) -> u32 {
+        self.saved
+    }
+}
+
+// What should happen to frames as they are presented
+#[derive(Default)]
+pub struct CaptureSettings {
+    pub keep_last_frame: bool,
+    pub last_frame: Option<FrameBuffer>,
+    pub pending_screenshots: Vec<PathBuf>,
+    pub recorder: Option<FrameRecorder>,
+}
+
+impl CaptureSettings {
+    pub fn new() -> Self {
+        CaptureSettings::default()
+    }
+
+    pub fn wants_frame(&self) -> bool {
+        self.keep_last_frame
+            || !self.pending_screenshots.is_empty()
+            || self.recorder.as_ref().is_some_and(|r| r.wants_frame())
+    }
+
+    pub fn frame_presented(&mut self, frame: Option<FrameBuffer>) {
+        for path in self.pending_screenshots.drain(..) {
+            match &frame {
+                Some(frame) => {
+                    if let Err(e) = frame.save_png(&path) {
+                        eprintln!("Error: Could not save screenshot - {}", e)
+                    }
+                }
+                None => eprintln!("Error: Could not save screenshot {} - frame was not captured", path.display()),
+            }
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(frame.as_ref());
+        }
+
+        if self.keep_last_frame {
+            self.last_frame = frame;
+        }
+    }
+}
@@ -0,0 +1,39 @@
+// Box overlaps for the Collision event. An entity with a "collider" tag (Vec2 width and
+// height) is a box with its top left at its "location".
+use crate::entities::EntityStore;
+use crate::{Entity, EntityId, TagValue};
+
+fn bounds(entity: &Entity) -> Option<(i32, i32, i32, i32)> {
+    let (TagValue::Vec2(location), TagValue::Vec2(size)) = (entity.tags.get("location")?, entity.tags.get("collider")?)
+    else {
+        return None;
+    };
+    Some((location.x, location.y, location.x + size.x, location.y + size.y))
+}
+
+// Pairs in the order the entities were added. Sorted by left edge so only boxes that
+// overlap on x get compared.
+pub(crate) fn overlapping(entities: &EntityStore) -> Vec<(EntityId, EntityId)> {
+    let mut boxes: Vec<(usize, (i32, i32, i32, i32))> = entities
+        .as_slice()
+        .iter()
+        .enumerate()
+        .filter_map(|(position, entity)| bounds(entity).map(|bounds| (position, bounds)))
+        .collect();
+    boxes.sort_by_key(|(position, bounds)| (bounds.0, *position));
+
+    let ids = entities.ids();
+    let mut pairs = Vec::new();
+    for (index, (first, a)) in boxes.iter().enumerate() {
+        for (second, b) in &boxes[index + 1..] {
+            if b.0 >= a.2 {
+                break;
+            }
+            if a.1 < b.3 && b.1 < a.3 {
+                pairs.push((*first.min(second), *first.max(second)));
+            }
+        }
+    }
+    pairs.sort();
+    pairs.into_iter().map(|(a, b)| (ids[a], ids[b])).collect()
+}
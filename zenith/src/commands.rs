@@ -0,0 +1,152 @@
+// Changes to the entity list queued up while it's being looped over. Scripts queue them
+// on the environment, entity functions on the entity they were called with. Everything
+// queued during a frame is applied in order right after the entity update functions run,
+// so an entity spawned this frame gets updated for the first time next frame.
+use crate::events::QueuedEvent;
+use crate::{EntityId, Entity, Environment, TagValue};
+
+#[derive(Clone)]
+enum Target {
+    Id(EntityId),
+    Name(String),
+    This, // The entity whose function queued it
+}
+
+#[derive(Clone)]
+enum Command {
+    Spawn(Entity),
+    SpawnPrefab(String, Vec<(String, TagValue)>),
+    Despawn(Target),
+    SetTag(Target, String, TagValue),
+    RemoveTag(Target, String),
+    Event(Box<dyn QueuedEvent>),
+}
+
+#[derive(Clone, Default)]
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, entity: Entity) {
+        self.queue.push(Command::Spawn(entity));
+    }
+
+    pub fn spawn_prefab(&mut self, name: &str, overrides: &[(&str, TagValue)]) {
+        let overrides = overrides
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        self.queue.push(Command::SpawnPrefab(name.to_string(), overrides));
+    }
+
+    // Despawning also removes the entity's children, like Environment::despawn
+    pub fn despawn(&mut self, id: EntityId) {
+        self.queue.push(Command::Despawn(Target::Id(id)));
+    }
+
+    pub fn despawn_named(&mut self, name: &str) {
+        self.queue.push(Command::Despawn(Target::Name(name.to_string())));
+    }
+
+    // Only means something when queued from an entity function
+    pub fn despawn_self(&mut self) {
+        self.queue.push(Command::Despawn(Target::This));
+    }
+
+    pub fn set_tag(&mut self, id: EntityId, tag_name: &str, tag_value: TagValue) {
+        self.queue.push(Command::SetTag(Target::Id(id), tag_name.to_string(), tag_value));
+    }
+
+    pub fn set_tag_named(&mut self, name: &str, tag_name: &str, tag_value: TagValue) {
+        self.queue
+            .push(Command::SetTag(Target::Name(name.to_string()), tag_name.to_string(), tag_value));
+    }
+
+    pub fn remove_tag(&mut self, id: EntityId, tag_name: &str) {
+        self.queue.push(Command::RemoveTag(Target::Id(id), tag_name.to_string()));
+    }
+
+    pub fn remove_tag_named(&mut self, name: &str, tag_name: &str) {
+        self.queue
+            .push(Command::RemoveTag(Target::Name(name.to_string()), tag_name.to_string()));
+    }
+
+    // Lets entity functions send events, they go out when the commands are applied
+    pub fn send_event<T: Clone + Send + Sync + 'static>(&mut self, event: T) {
+        self.queue.push(Command::Event(Box::new(event)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    // Moves what an entity queued onto the end of this queue, `this` is the entity's id
+    pub(crate) fn take_from(&mut self, other: &mut Commands, this: EntityId) {
+        let this = |target: Target| match target {
+            Target::This => Target::Id(this),
+            target => target,
+        };
+        for command in other.queue.drain(..) {
+            self.queue.push(match command {
+                Command::Despawn(target) => Command::Despawn(this(target)),
+                Command::SetTag(target, name, value) => Command::SetTag(this(target), name, value),
+                Command::RemoveTag(target, name) => Command::RemoveTag(this(target), name),
+                command => command,
+            });
+        }
+    }
+
+    // Commands aimed at entities that are gone by the time they run are skipped
+    pub(crate) fn apply(self, environment: &mut Environment) {
+        let find = |environment: &Environment, target: &Target| match target {
+            Target::Id(id) => Some(*id),
+            Target::Name(name) => environment.get_entity_id(name),
+            Target::This => None,
+        };
+        for command in self.queue {
+            match command {
+                Command::Spawn(entity) => {
+                    environment.add_entity(entity);
+                }
+                Command::SpawnPrefab(name, overrides) => {
+                    let overrides: Vec<(&str, TagValue)> = overrides
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.clone()))
+                        .collect();
+                    if let Err(e) = environment.spawn_prefab(&name, &overrides) {
+                        eprintln!("Error: Could not spawn prefab {} - {}", name, e);
+                    }
+                }
+                Command::Despawn(target) => {
+                    if let Some(id) = find(environment, &target) {
+                        environment.despawn(id);
+                    }
+                }
+                Command::SetTag(target, name, value) => {
+                    if let Some(entity) = find(environment, &target).and_then(|id| environment.get_mut_entity_by_id(id)) {
+                        entity.set_tag(&name, value);
+                    }
+                }
+                Command::RemoveTag(target, name) => {
+                    if let Some(entity) = find(environment, &target).and_then(|id| environment.get_mut_entity_by_id(id)) {
+                        entity.tags.remove(&name);
+                    }
+                }
+                Command::Event(event) => event.send(&mut environment.events),
+            }
+        }
+    }
+}
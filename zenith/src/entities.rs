@@ -0,0 +1,225 @@
+// Where the environment keeps its entities. Every entity gets an EntityId when it is
+// added, made of a slot and the generation of that slot. Removing an entity bumps the
+// generation, so old ids stop finding anything instead of finding whatever gets the
+// slot next. Entities stay in the order they were added, which is the order they run in.
+use crate::Entity;
+
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl EntityId {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Slot {
+    generation: u32,
+    position: Option<usize>, // Where the entity is in `entities`, None once it's removed
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct EntityStore {
+    entities: Vec<Entity>,
+    ids: Vec<EntityId>, // Id of the entity at the same position
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    names: HashMap<String, Vec<EntityId>>, // Entities with each name, in position order
+    indexed: Vec<String>, // Name each position is filed under in `names`
+    // Positions handed out mutably, their names may have changed since. Everything is
+    // looked at again on the next sync
+    dirty: Vec<usize>,
+    all_dirty: bool,
+}
+
+impl EntityStore {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn insert(&mut self, entity: Entity) -> EntityId {
+        self.sync();
+        let mut entity = entity;
+        entity.started = false;
+        let position = self.entities.len();
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.position = Some(position);
+                EntityId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    position: Some(position),
+                });
+                EntityId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        // Goes on the end, so it's last with its name too
+        let name = entity.get_name();
+        self.names.entry(name.clone()).or_default().push(id);
+        self.indexed.push(name);
+        self.entities.push(entity);
+        self.ids.push(id);
+        id
+    }
+
+    pub(crate) fn position(&self, id: EntityId) -> Option<usize> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.position
+    }
+
+    pub(crate) fn contains(&self, id: EntityId) -> bool {
+        self.position(id).is_some()
+    }
+
+    pub(crate) fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.position(id).map(|position| &self.entities[position])
+    }
+
+    pub(crate) fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.sync();
+        let position = self.position(id)?;
+        self.dirty.push(position);
+        Some(&mut self.entities[position])
+    }
+
+    // Names are tags so they can change whenever an entity is handed out mutably. Those
+    // positions are remembered and filed under their new name here, before anything else
+    // changes the store.
+    pub(crate) fn sync(&mut self) {
+        let positions: Vec<usize> = if self.all_dirty {
+            (0..self.entities.len()).collect()
+        } else {
+            std::mem::take(&mut self.dirty)
+        };
+        self.dirty.clear();
+        self.all_dirty = false;
+        for position in positions {
+            let name = self.entities[position].get_name();
+            if name == self.indexed[position] {
+                continue;
+            }
+            let id = self.ids[position];
+            let old = std::mem::replace(&mut self.indexed[position], name.clone());
+            if let Some(ids) = self.names.get_mut(&old) {
+                ids.retain(|other| *other != id);
+                if ids.is_empty() {
+                    self.names.remove(&old);
+                }
+            }
+            let slots = &self.slots;
+            let ids = self.names.entry(name).or_default();
+            let at = ids.partition_point(|other| slots[other.index as usize].position < Some(position));
+            ids.insert(at, id);
+        }
+    }
+
+    // The first entity filed under the name that still has it, unless something renamed
+    // since the last sync comes before it
+    fn position_of(&self, name: &str) -> Option<usize> {
+        let filed = self.names.get(name).and_then(|ids| {
+            ids.iter()
+                .filter_map(|id| self.position(*id))
+                .find(|position| self.entities[*position].get_name() == name)
+        });
+        let renamed = if self.all_dirty {
+            self.entities.iter().position(|entity| entity.get_name() == name)
+        } else {
+            self.dirty
+                .iter()
+                .copied()
+                .filter(|position| self.entities[*position].get_name() == name)
+                .min()
+        };
+        match (filed, renamed) {
+            (Some(filed), Some(renamed)) => Some(filed.min(renamed)),
+            (filed, renamed) => filed.or(renamed),
+        }
+    }
+
+    pub(crate) fn id_of(&self, name: &str) -> Option<EntityId> {
+        self.position_of(name).map(|position| self.ids[position])
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<&Entity> {
+        self.position_of(name).map(|position| &self.entities[position])
+    }
+
+    pub(crate) fn find_mut(&mut self, name: &str) -> Option<&mut Entity> {
+        self.sync();
+        let position = self.position_of(name)?;
+        self.dirty.push(position);
+        Some(&mut self.entities[position])
+    }
+
+    pub(crate) fn ids(&self) -> &[EntityId] {
+        &self.ids
+    }
+
+    pub(crate) fn as_slice(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [Entity] {
+        self.sync();
+        self.all_dirty = true;
+        &mut self.entities
+    }
+
+    // Keeps the entities `keep` returns true for, returns the ones that were removed
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(EntityId, &Entity) -> bool) -> Vec<(EntityId, Entity)> {
+        self.sync();
+        let entities = std::mem::take(&mut self.entities);
+        let ids = std::mem::take(&mut self.ids);
+        let indexed = std::mem::take(&mut self.indexed);
+        let mut removed = Vec::new();
+        for ((entity, id), name) in entities.into_iter().zip(ids).zip(indexed) {
+            let slot = &mut self.slots[id.index as usize];
+            if keep(id, &entity) {
+                slot.position = Some(self.entities.len());
+                self.entities.push(entity);
+                self.ids.push(id);
+                self.indexed.push(name);
+            } else {
+                slot.position = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(id.index);
+                if let Some(ids) = self.names.get_mut(&name) {
+                    ids.retain(|other| *other != id);
+                    if ids.is_empty() {
+                        self.names.remove(&name);
+                    }
+                }
+                removed.push((id, entity));
+            }
+        }
+        // Kept entities stay in the same order, so the lists still are
+        removed
+    }
+
+    pub(crate) fn clear(&mut self) -> Vec<(EntityId, Entity)> {
+        self.retain(|_, _| false)
+    }
+}
@@ -1,71 +1,219 @@
 use crate::render::{draw_rect, update_keystrokes, VisualRect};
 use crate::*;
-use crate::Entity;
 use crate::Instance2D;
 use std::time::{Duration, Instant};
 
 pub fn eventloop(instance: Instance2D) {
     let mut instance = instance;
 
-    start_entities(&mut instance); // Run the start functions on all of the entities
-    start_scripts(&mut instance);
+    startup(&mut instance);
 
     // Frame rate keeper stuff
     let framerate_goal = instance.screen.framerate_cap;
     let frame_duration = Duration::from_secs(1) / framerate_goal as u32;
     let mut last_frame_time = Instant::now();
 
-    draw_rect(
-        VisualRect::new(Vec2::new(0, 0), Vec2::new(100, 100), Color::white()),
-        &mut instance.engine_settings.engine_env,
-    );
-
     loop {
         let frame_start_time = Instant::now();
         if !instance.engine_settings.is_running {
             break;
         }
 
-        update_scripts(&mut instance);
-        update_keystrokes(&mut instance);
-        update_entities(&mut instance);
+        frame(&mut instance);
 
         maintain_framerate(frame_duration, &mut last_frame_time, frame_start_time);
     }
+
+    shutdown(&mut instance);
+}
+
+// A slow frame doesn't get to run more fixed updates than this to catch up
+const MAX_FIXED_STEPS: u32 = 8;
+
+// Everything before the first frame, split out so the headless runner can drive frames itself
+pub fn startup(instance: &mut Instance2D) {
+    let screen = instance.screen.clone();
+    instance.engine_settings.apply_screen(&screen);
+
+    instance.environment.start_entities(); // Run the start functions on all of the entities
+    run_scripts(instance, ScriptStage::Start);
+    instance.environment.apply_commands();
+
+    draw_rect(
+        VisualRect::new(Vec2::new(0, 0), Vec2::new(100, 100), Color::white()),
+        &mut instance.engine_settings.engine_env,
+    );
+}
+
+pub fn frame(instance: &mut Instance2D) {
+    instance.engine_settings.update_delta_time();
+    instance.environment.mut_events().update();
+    update_assets(instance);
+    let paused = instance.environment.is_paused();
+    update_tilemaps(instance, paused); // Still drawn while paused, just not animated
+    update_particles(instance, paused);
+    if !paused {
+        fixed_update(instance);
+    }
+    run_scripts(instance, ScriptStage::Update);
+    if !paused {
+        let delta_time = instance.engine_settings.delta_time;
+        instance.environment.run_systems(delta_time);
+    }
+    update_keystrokes(instance);
+    send_key_events(instance);
+    timers::update_timers(instance); // These skip themselves while paused
+    timers::update_sequences(instance);
+    if !paused {
+        instance.environment.update_entities();
+    }
+    instance.environment.apply_commands();
+    tween::update_tweens(instance); // Before the transforms so tweened local tags move children
+    hierarchy::propagate_transforms(&mut instance.environment.entities);
+    instance.environment.send_collisions();
+    run_scripts(instance, ScriptStage::LateUpdate);
+    instance.environment.apply_commands();
+    update_audio(instance);
 }
 
-fn update_scripts(instance: &mut Instance2D) {
-    for script in instance.environment.update_scripts.clone() {
-        script.1(instance)
+// After the last frame
+pub fn shutdown(instance: &mut Instance2D) {
+    run_scripts(instance, ScriptStage::Shutdown);
+    instance.environment.apply_commands();
+}
+
+// Fixed update scripts see delta_time as the fixed time step
+fn fixed_update(instance: &mut Instance2D) {
+    let settings = &mut instance.engine_settings;
+    let timestep = settings.fixed_timestep;
+    if timestep <= 0.0 {
+        return;
+    }
+    settings.fixed_time += settings.delta_time;
+
+    let delta_time = settings.delta_time;
+    let mut steps = 0;
+    while instance.engine_settings.fixed_time >= timestep {
+        if steps == MAX_FIXED_STEPS {
+            instance.engine_settings.fixed_time = 0.0;
+            break;
+        }
+        instance.engine_settings.delta_time = timestep;
+        run_scripts(instance, ScriptStage::FixedUpdate);
+        instance.engine_settings.fixed_time -= timestep;
+        steps += 1;
     }
+    instance.engine_settings.delta_time = delta_time;
 }
 
-fn start_scripts(instance: &mut Instance2D) {
-    for script in instance.environment.start_scripts.clone() {
-        script.1(instance)
+fn update_assets(instance: &mut Instance2D) {
+    instance.assets.update(&mut instance.engine_settings);
+    if instance.assets.reloaded().is_empty() {
+        return;
     }
+
+    let mut tilemaps = std::mem::take(&mut instance.environment.tilemaps);
+    for tilemap in tilemaps.iter_mut() {
+        let Some(source) = &tilemap.source else {
+            continue;
+        };
+        let changed = instance.assets.was_reloaded(source)
+            || tilemap.texture_handles.iter().any(|handle| instance.assets.was_reloaded(handle));
+        if changed {
+            if let Err(e) = instance.refresh_tilemap(tilemap) {
+                eprintln!("Error: Could not reload tilemap - {}", e);
+            }
+        }
+    }
+    instance.environment.tilemaps = tilemaps;
 }
 
-fn update_entities(instance: &mut Instance2D) {
-    let mut new_entities: Vec<Entity> = Vec::new();
-    for entity in instance.environment.entities.iter_mut() {
-        if let Some(update_function) = &mut entity.update_function {
-            update_function(entity);
-            new_entities.push(entity.clone());
+fn update_tilemaps(instance: &mut Instance2D, paused: bool) {
+    let delta_time = instance.engine_settings.delta_time;
+    let camera = instance.engine_settings.camera.clone();
+    let view_size = instance.engine_settings.view_size();
+    for tilemap in instance.environment.tilemaps.iter_mut() {
+        if !paused {
+            tilemap.update(delta_time);
         }
+        tilemap.draw(&mut instance.engine_settings, &camera, view_size);
     }
-    instance.environment.entities = new_entities;
 }
 
-fn start_entities(instance: &mut Instance2D) {
-    let mut new_entities: Vec<Entity> = Vec::new();
-    for entity in instance.environment.entities.iter_mut() {
-        if let Some(start_function) = &mut entity.start_function {
-            start_function(entity);
-            new_entities.push(entity.clone());
+// Positional sounds follow their entities, heard from the camera center or the listener entity
+fn update_audio(instance: &mut Instance2D) {
+    let entities = &instance.environment.entities;
+    let locate = |id: EntityId| {
+        entities
+            .get(id)
+            .and_then(|entity| entity.get_tag("location"))
+            .and_then(|tag| tag.extract_vec2())
+    };
+
+    let camera = &instance.engine_settings.camera;
+    let view_size = instance.engine_settings.view_size();
+    let screen_center = (
+        camera.x as f32 + view_size.0 as f32 / 2.0,
+        camera.y as f32 + view_size.1 as f32 / 2.0,
+    );
+    let listener = match instance.audio.get_listener() {
+        Listener::Camera => screen_center,
+        Listener::Entity(id) => match locate(*id) {
+            Some(location) => (location.x as f32, location.y as f32),
+            None => screen_center,
+        },
+    };
+    instance.audio.update_positional(listener, |id| entities.contains(id), locate);
+}
+
+fn update_particles(instance: &mut Instance2D, paused: bool) {
+    let delta_time = instance.engine_settings.delta_time;
+    let camera = instance.engine_settings.camera.clone();
+    for emitter in instance.environment.emitters.iter_mut() {
+        if let Some(id) = emitter.attached_to {
+            match instance.environment.entities.get(id) {
+                Some(entity) => {
+                    if let Some(location) = entity.get_tag("location").and_then(|tag| tag.extract_vec2()) {
+                        emitter.position = Vec2::new(location.x + emitter.offset.x, location.y + emitter.offset.y);
+                    }
+                }
+                None => {
+                    emitter.attached_to = None;
+                    emitter.emitting = false;
+                }
+            }
+        }
+        if !paused {
+            emitter.update(delta_time);
         }
+        emitter.draw(&mut instance.engine_settings, &camera);
+    }
+}
+
+// Compares the keys with last frame's, so scripted input sends them too
+fn send_key_events(instance: &mut Instance2D) {
+    let pressed: Vec<String> = instance
+        .engine_settings
+        .keys
+        .all_pressed_str()
+        .iter()
+        .map(|key| key.to_string())
+        .collect();
+    let last_frame = std::mem::take(&mut instance.engine_settings.pressed_last_frame);
+    let events = instance.environment.mut_events();
+    for key in pressed.iter().filter(|key| !last_frame.contains(key)) {
+        events.send(KeyPressed { key: key.clone() });
+    }
+    for key in last_frame.iter().filter(|key| !pressed.contains(key)) {
+        events.send(KeyReleased { key: key.clone() });
+    }
+    instance.engine_settings.pressed_last_frame = pressed;
+}
+
+fn run_scripts(instance: &mut Instance2D, stage: ScriptStage) {
+    for script in instance.environment.scripts.enabled(stage) {
+        script(instance)
     }
-    instance.environment.entities = new_entities;
 }
 
 fn maintain_framerate(
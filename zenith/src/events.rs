@@ -0,0 +1,211 @@
+// Typed events. Anything that is Clone + Send + Sync can be sent, each type gets its own
+// channel. Events are kept for the frame they were sent in and the frame after, then they
+// are dropped. The engine sends its own events (keys, window, spawning, collisions) on the
+// same bus, keys and collisions after the Update scripts have run.
+//
+// read is how events should be read, each place in the code that calls it gets every event
+// once no matter which stage it runs in:
+//     for collision in instance.environment.mut_events().read::<Collision>() { .. }
+// read_new does the same for a named reader, for when several calls should share one cursor.
+use crate::EntityId;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+// Engine events
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyPressed {
+    pub key: String, // Same names as Keys::all_pressed_str
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyReleased {
+    pub key: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowResized {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntitySpawned {
+    pub id: EntityId,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityDespawned {
+    pub id: EntityId,
+    pub name: String,
+}
+
+// Sent every frame two colliders overlap, `a` is the one added first
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collision {
+    pub a: EntityId,
+    pub b: EntityId,
+}
+
+trait Channel: Send + Sync {
+    fn update(&mut self);
+    fn clone_channel(&self) -> Box<dyn Channel>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct EventChannel<T> {
+    previous: Vec<(u64, T)>, // Sent last frame
+    current: Vec<(u64, T)>,
+    next: u64,
+    readers: HashMap<String, u64>, // First event each named reader hasn't seen
+}
+
+impl<T: Clone + Send + Sync + 'static> Channel for EventChannel<T> {
+    fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    fn clone_channel(&self) -> Box<dyn Channel> {
+        Box::new(EventChannel {
+            previous: self.previous.clone(),
+            current: self.current.clone(),
+            next: self.next,
+            readers: self.readers.clone(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl<T> EventChannel<T> {
+    fn all(&self) -> impl Iterator<Item = &(u64, T)> {
+        self.previous.iter().chain(&self.current)
+    }
+}
+
+#[derive(Default)]
+pub struct Events {
+    channels: HashMap<TypeId, Box<dyn Channel>>,
+}
+
+impl Clone for Events {
+    fn clone(&self) -> Self {
+        Events {
+            channels: self
+                .channels
+                .iter()
+                .map(|(type_id, channel)| (*type_id, channel.clone_channel()))
+                .collect(),
+        }
+    }
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn channel<T: Clone + Send + Sync + 'static>(&self) -> Option<&EventChannel<T>> {
+        self.channels
+            .get(&TypeId::of::<T>())
+            .and_then(|channel| channel.as_any().downcast_ref())
+    }
+
+    fn mut_channel<T: Clone + Send + Sync + 'static>(&mut self) -> &mut EventChannel<T> {
+        self.channels
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Box::new(EventChannel::<T> {
+                    previous: Vec::new(),
+                    current: Vec::new(),
+                    next: 0,
+                    readers: HashMap::new(),
+                })
+            })
+            .as_any_mut()
+            .downcast_mut()
+            .expect("Event channel stored under the wrong type")
+    }
+
+    pub fn send<T: Clone + Send + Sync + 'static>(&mut self, event: T) {
+        let channel = self.mut_channel::<T>();
+        channel.current.push((channel.next, event));
+        channel.next += 1;
+    }
+
+    // Events this call hasn't returned yet. Every call site is its own reader, so the same
+    // read in a script sees each event once whichever frame it was sent in.
+    #[track_caller]
+    pub fn read<T: Clone + Send + Sync + 'static>(&mut self) -> Vec<&T> {
+        let caller = std::panic::Location::caller();
+        self.read_new(&format!("{}:{}:{}", caller.file(), caller.line(), caller.column()))
+    }
+
+    // Everything still kept, last frame's and this frame's, oldest first. Nothing is marked
+    // as read, so calling this every frame sees most events twice.
+    pub fn peek<T: Clone + Send + Sync + 'static>(&self) -> Vec<&T> {
+        match self.channel::<T>() {
+            Some(channel) => channel.all().map(|(_, event)| event).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Events `reader` hasn't been given yet. Readers are just names, usually the name of
+    // the script reading, so scripts don't have to keep track of anything themselves. A
+    // reader that doesn't read for two frames misses what was dropped in between.
+    pub fn read_new<T: Clone + Send + Sync + 'static>(&mut self, reader: &str) -> Vec<&T> {
+        let channel = self.mut_channel::<T>();
+        let first = channel.readers.get(reader).copied().unwrap_or(0);
+        channel.readers.insert(reader.to_string(), channel.next);
+        channel.all().filter(|(id, _)| *id >= first).map(|(_, event)| event).collect()
+    }
+
+    pub fn is_empty<T: Clone + Send + Sync + 'static>(&self) -> bool {
+        self.channel::<T>().is_none_or(|channel| channel.all().next().is_none())
+    }
+
+    pub fn clear<T: Clone + Send + Sync + 'static>(&mut self) {
+        if let Some(channel) = self.channels.get_mut(&TypeId::of::<T>()) {
+            channel.update();
+            channel.update();
+        }
+    }
+
+    // Drops last frame's events, the event loop calls this at the start of every frame
+    pub fn update(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.update();
+        }
+    }
+}
+
+// Events queued by entity functions through their commands
+pub(crate) trait QueuedEvent: Send + Sync {
+    fn send(self: Box<Self>, events: &mut Events);
+    fn clone_event(&self) -> Box<dyn QueuedEvent>;
+}
+
+impl<T: Clone + Send + Sync + 'static> QueuedEvent for T {
+    fn send(self: Box<Self>, events: &mut Events) {
+        events.send(*self);
+    }
+
+    fn clone_event(&self) -> Box<dyn QueuedEvent> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn QueuedEvent> {
+    fn clone(&self) -> Self {
+        (**self).clone_event()
+    }
+}
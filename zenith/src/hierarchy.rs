@@ -0,0 +1,199 @@
+// Parent/child links between entities. A child holds the EntityId of its parent and keeps
+// its transform relative to the parent in "local_location", "local_rotation" and
+// "local_scale". Every frame the world "location", "rotation" and "scale" tags of
+// children are worked out from their parent's, parents first.
+use crate::entities::EntityStore;
+use crate::{Entity, EntityId, TagValue, Vec2};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub position: (f32, f32),
+    pub rotation: f32, // Degrees, clockwise since y points down
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            position: (0.0, 0.0),
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+fn float_tag(entity: &Entity, name: &str) -> Option<f32> {
+    match entity.tags.get(name)? {
+        TagValue::Float(value) => Some(*value),
+        TagValue::Double(value) => Some(*value as f32),
+        TagValue::Int(value) => Some(*value as f32),
+        _ => None,
+    }
+}
+
+fn vec2_tag(entity: &Entity, name: &str) -> Option<(f32, f32)> {
+    match entity.tags.get(name)? {
+        TagValue::Vec2(value) => Some((value.x as f32, value.y as f32)),
+        _ => None,
+    }
+}
+
+impl Transform {
+    // From the "location", "rotation" and "scale" tags
+    pub fn world(entity: &Entity) -> Self {
+        Transform {
+            position: vec2_tag(entity, "location").unwrap_or((0.0, 0.0)),
+            rotation: float_tag(entity, "rotation").unwrap_or(0.0),
+            scale: float_tag(entity, "scale").unwrap_or(1.0),
+        }
+    }
+
+    // From the "local_*" tags, None if the entity has no local location yet
+    pub fn local(entity: &Entity) -> Option<Self> {
+        Some(Transform {
+            position: vec2_tag(entity, "local_location")?,
+            rotation: float_tag(entity, "local_rotation").unwrap_or(0.0),
+            scale: float_tag(entity, "local_scale").unwrap_or(1.0),
+        })
+    }
+
+    // `local` placed inside of self
+    pub fn combine(&self, local: &Transform) -> Transform {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let x = local.position.0 * self.scale;
+        let y = local.position.1 * self.scale;
+        Transform {
+            position: (self.position.0 + x * cos - y * sin, self.position.1 + x * sin + y * cos),
+            rotation: self.rotation + local.rotation,
+            scale: self.scale * local.scale,
+        }
+    }
+
+    // What `world` is relative to self, the opposite of combine
+    pub fn relative(&self, world: &Transform) -> Transform {
+        let (sin, cos) = (-self.rotation).to_radians().sin_cos();
+        let x = world.position.0 - self.position.0;
+        let y = world.position.1 - self.position.1;
+        let scale = if self.scale == 0.0 { 1.0 } else { self.scale };
+        Transform {
+            position: ((x * cos - y * sin) / scale, (x * sin + y * cos) / scale),
+            rotation: world.rotation - self.rotation,
+            scale: world.scale / scale,
+        }
+    }
+
+    pub fn write_world(&self, entity: &mut Entity) {
+        let position = Vec2::new(self.position.0.round() as i32, self.position.1.round() as i32);
+        entity.set_tag("location", TagValue::Vec2(position));
+        entity.set_tag("rotation", TagValue::Float(self.rotation));
+        entity.set_tag("scale", TagValue::Float(self.scale));
+    }
+
+    pub fn write_local(&self, entity: &mut Entity) {
+        let position = Vec2::new(self.position.0.round() as i32, self.position.1.round() as i32);
+        entity.set_tag("local_location", TagValue::Vec2(position));
+        entity.set_tag("local_rotation", TagValue::Float(self.rotation));
+        entity.set_tag("local_scale", TagValue::Float(self.scale));
+    }
+}
+
+// Children of every entity, by the child's position in the store. Children whose parent
+// is gone count as roots.
+fn children_by_position(entities: &EntityStore) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let mut children = vec![Vec::new(); entities.as_slice().len()];
+    let mut roots = Vec::new();
+    for (position, entity) in entities.as_slice().iter().enumerate() {
+        match entity.parent.and_then(|parent| entities.position(parent)) {
+            Some(parent) if parent != position => children[parent].push(position),
+            _ => roots.push(position),
+        }
+    }
+    (children, roots)
+}
+
+pub(crate) fn children(entities: &EntityStore, id: EntityId) -> Vec<EntityId> {
+    let ids = entities.ids();
+    entities
+        .as_slice()
+        .iter()
+        .enumerate()
+        .filter(|(_, entity)| entity.parent == Some(id))
+        .map(|(position, _)| ids[position])
+        .filter(|child| *child != id)
+        .collect()
+}
+
+// Every entity under `id`, children before grandchildren
+pub(crate) fn descendants(entities: &EntityStore, id: EntityId) -> Vec<EntityId> {
+    let Some(start) = entities.position(id) else {
+        return Vec::new();
+    };
+    let (children, _) = children_by_position(entities);
+    let mut seen = vec![false; children.len()];
+    seen[start] = true;
+    let mut found = vec![start];
+    let mut next = 0;
+    while next < found.len() {
+        for child in &children[found[next]] {
+            // A broken file could make a loop, don't go around it forever
+            if !seen[*child] {
+                seen[*child] = true;
+                found.push(*child);
+            }
+        }
+        next += 1;
+    }
+    let ids = entities.ids();
+    found[1..].iter().map(|position| ids[*position]).collect()
+}
+
+// Parent first, then its parent and so on
+pub(crate) fn ancestors(entities: &EntityStore, id: EntityId) -> Vec<EntityId> {
+    let mut found: Vec<EntityId> = Vec::new();
+    let mut current = entities.get(id).and_then(|entity| entity.parent);
+    while let Some(parent) = current {
+        if parent == id || found.contains(&parent) || !entities.contains(parent) {
+            break;
+        }
+        current = entities.get(parent).and_then(|entity| entity.parent);
+        found.push(parent);
+    }
+    found
+}
+
+// Works out world transforms of all children from their parents. Children without local
+// tags yet (just attached, or spawned from a prefab) keep where they are and get local
+// tags made for them.
+pub(crate) fn propagate_transforms(entities: &mut EntityStore) {
+    // Walking down from the roots does parents before their children, anything in a loop
+    // never gets reached
+    let (children, roots) = children_by_position(entities);
+    let mut order = Vec::new();
+    for root in roots {
+        let mut stack = vec![root];
+        while let Some(parent) = stack.pop() {
+            for child in children[parent].iter().rev() {
+                order.push((parent, *child));
+                stack.push(*child);
+            }
+        }
+    }
+
+    let all = entities.as_mut_slice();
+    for (parent, child) in order {
+        let parent_world = Transform::world(&all[parent]);
+        let child = &mut all[child];
+        let local = match Transform::local(child) {
+            Some(local) => local,
+            None => {
+                let local = parent_world.relative(&Transform::world(child));
+                local.write_local(child);
+                local
+            }
+        };
+        parent_world.combine(&local).write_world(child);
+    }
+    // Only transforms changed, but filing the names again now saves lookups from
+    // scanning until the store is next changed
+    entities.sync();
+}
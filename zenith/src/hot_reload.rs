@@ -0,0 +1,62 @@
+// Only built with the dev feature, tells the asset store which loaded files changed on disk
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    directories: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Result<Self, String> {
+        let (sender, events) = channel();
+        let watcher = notify::recommended_watcher(sender).map_err(|e| e.to_string())?;
+        Ok(FileWatcher {
+            watcher,
+            events,
+            directories: HashSet::new(),
+        })
+    }
+
+    // Watches the folder instead of the file, editors often save by writing a new
+    // file and renaming it over the old one which would end a watch on the file itself
+    pub fn watch(&mut self, file: &Path) {
+        let Some(directory) = file.parent() else {
+            return;
+        };
+        if self.directories.contains(directory) {
+            return;
+        }
+        match self.watcher.watch(directory, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                self.directories.insert(directory.to_path_buf());
+            }
+            Err(e) => eprintln!("Error: Could not watch {} - {}", directory.display(), e),
+        }
+    }
+
+    // Files written or replaced since the last call, each only once no matter how many
+    // events the save caused
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths {
+                let path = path.canonicalize().unwrap_or(path);
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+}
@@ -1,14 +1,77 @@
+pub use assets::{Asset, Assets, Handle, TextFile, Texture};
+pub use entities::EntityId;
+pub use commands::Commands;
+pub use events::{Collision, EntityDespawned, EntitySpawned, Events, KeyPressed, KeyReleased, WindowResized};
+pub use audio::{Attenuation, Audio, ChannelId, Falloff, Listener, Mixer, PlaySettings, Sound};
+use capture::CaptureSettings;
+use entities::EntityStore;
 use render::{Keys, RenderingEnvironment};
+use schedule::Schedule;
+use systems::Systems;
+use timers::Timers;
+use tween::Tweens;
+use scene::EntityFnNames;
+pub use capture::{FrameBuffer, FrameRecorder};
+pub use pack::AssetPack;
+pub use hierarchy::Transform;
+pub use particles::{ParticleEmitter, ParticleShape};
+pub use tiled::{load_tiled_map, load_tiled_map_from, TiledMap};
+pub use tilemap::{AnimationFrame, TileLayer, Tilemap, Tileset};
+pub use testing::{assert_snapshot, check_snapshot, compare_frames, snapshot_dir, HeadlessRunner, SnapshotDiff};
+pub use prefab::{Prefab, PrefabChild, Prefabs};
+pub use save::{Migration, Saves};
+pub use scene::{Scene, SceneEntity, SceneFormat, ScriptRegistry};
+pub use schedule::{ScriptOrder, ScriptStage};
+pub use systems::{System, SystemContext};
+pub use tags::TagError;
+pub use timers::{Sequence, SequenceId, Timer, TimerId};
+pub use tween::{Easing, Tween, TweenId};
+pub use render::{
+    BlendMode, Mouse, PostEffect, RenderQueue, ScalingMode, TextureId, VisualRect, VisualSprite,
+};
+// Used by embed_assets!
+pub use include_dir;
+// Save migrations edit the save as JSON
+pub use serde_json;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use std::collections::{BTreeMap, HashSet};
 use std::{collections::HashMap, f32::INFINITY};
 
+mod assets;
+mod audio;
+mod capture;
+mod collision;
+mod commands;
+mod entities;
 mod eventloop;
+mod events;
+mod hierarchy;
+#[cfg(feature = "dev")]
+mod hot_reload;
+mod pack;
+mod particles;
+mod prefab;
 mod render;
+mod save;
+mod scene;
+mod schedule;
 mod sdl2_renderer;
+mod systems;
+mod tags;
+mod testing;
+mod tiled;
+mod tilemap;
+mod timers;
+mod tween;
 
 pub struct Instance2D {
     pub screen: Screen,
     pub engine_settings: EngineSettings2D,
     pub environment: Environment,
+    pub audio: Audio,
+    pub assets: Assets,
+    pub saves: Saves, // Save version and migrations used by save_game/load_game
 }
 
 #[derive(Clone)]
@@ -16,6 +79,8 @@ pub struct Screen {
     caption: String,
     framerate_cap: u32,
     window_size: (u32, u32),
+    logical_size: Option<(u32, u32)>,
+    scaling: ScalingMode,
 }
 
 pub struct EngineSettings2D {
@@ -23,49 +88,634 @@ pub struct EngineSettings2D {
     engine_env: RenderingEnvironment,
     pub is_running: bool,
     pub keys: Keys,
+    pub mouse: Mouse,
+    pub post_effects: Vec<PostEffect>,
+    pub camera: Vec2,                   // World position of the top left of the screen
+    pub delta_time: f32,                // Seconds the last frame took
+    pub fixed_delta_time: Option<f32>,  // Use this instead of measuring, for deterministic runs
+    pub fixed_timestep: f32,            // Seconds between fixed update scripts, 0 turns them off
+    fixed_time: f32,                    // Time that hasn't been used up by fixed updates yet
+    pressed_last_frame: Vec<String>,    // For KeyPressed/KeyReleased events
+    last_frame_time: Option<Instant>,
+    white_texture: Option<TextureId>,
+    capture: CaptureSettings,
 }
 
 #[derive(Clone)]
 pub struct Environment {
-    entities: Vec<Entity>, // Default Entity tag type is set to i32
-    update_scripts: Vec<(String, fn(&mut Instance2D))>,
-    start_scripts: Vec<(String, fn(&mut Instance2D))>,
+    entities: EntityStore, // Default Entity tag type is set to i32
+    scripts: Schedule,
+    systems: Systems, // Run every frame after the update scripts
+    tilemaps: Vec<Tilemap>,
+    emitters: Vec<ParticleEmitter>,
+    registry: ScriptRegistry, // Names scenes use to refer to scripts and entity functions
+    resources: HashMap<String, TagValue>, // Game wide values that don't belong to an entity
+    prefabs: Prefabs,
+    commands: Commands, // Applied once a frame, after the entity update functions
+    events: Events,
+    timers: Timers,
+    tweens: Tweens,
+    paused: bool, // Stops entities, systems, fixed update, timers, sequences and tweens
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
-            entities: Vec::new(),
-            update_scripts: get_builtin_update_functions(),
-            start_scripts: Vec::new(),
+            entities: EntityStore::new(),
+            scripts: builtin_schedule(),
+            systems: Systems::new(),
+            tilemaps: Vec::new(),
+            emitters: Vec::new(),
+            registry: builtin_registry(),
+            resources: HashMap::new(),
+            prefabs: Prefabs::new(),
+            commands: Commands::new(),
+            events: Events::new(),
+            timers: Timers::new(),
+            tweens: Tweens::new(),
+            paused: false,
         }
     }
 
     pub fn new_skeleton() -> Self {
         Environment {
-            entities: Vec::new(),
-            update_scripts: Vec::new(),
-            start_scripts: Vec::new(),
+            entities: EntityStore::new(),
+            scripts: Schedule::new(),
+            systems: Systems::new(),
+            tilemaps: Vec::new(),
+            emitters: Vec::new(),
+            registry: builtin_registry(),
+            resources: HashMap::new(),
+            prefabs: Prefabs::new(),
+            commands: Commands::new(),
+            events: Events::new(),
+            timers: Timers::new(),
+            tweens: Tweens::new(),
+            paused: false,
         }
     }
 
+    // Also registers the script under its name so scenes can use it. Adding a name that's
+    // already in the stage replaces it.
+    pub fn add_script(&mut self, stage: ScriptStage, name: &str, script: fn(&mut Instance2D), order: ScriptOrder) {
+        self.registry.register_script(name, script);
+        self.scripts.add(stage, name, script, order);
+    }
+
     pub fn add_update_script(&mut self, name: &str, script: fn(&mut Instance2D)) {
-        self.update_scripts.push((name.to_string(), script))
+        self.add_script(ScriptStage::Update, name, script, ScriptOrder::new());
+    }
+
+    pub fn add_start_script(&mut self, name: &str, script: fn(&mut Instance2D)) {
+        self.add_script(ScriptStage::Start, name, script, ScriptOrder::new());
+    }
+
+    pub fn add_late_update_script(&mut self, name: &str, script: fn(&mut Instance2D)) {
+        self.add_script(ScriptStage::LateUpdate, name, script, ScriptOrder::new());
+    }
+
+    pub fn add_fixed_update_script(&mut self, name: &str, script: fn(&mut Instance2D)) {
+        self.add_script(ScriptStage::FixedUpdate, name, script, ScriptOrder::new());
+    }
+
+    pub fn add_shutdown_script(&mut self, name: &str, script: fn(&mut Instance2D)) {
+        self.add_script(ScriptStage::Shutdown, name, script, ScriptOrder::new());
+    }
+
+    pub fn set_script_order(&mut self, stage: ScriptStage, name: &str, order: ScriptOrder) -> Result<(), String> {
+        if self.scripts.set_order(stage, name, order) {
+            Ok(())
+        } else {
+            Err(format!("No {:?} script named {}", stage, name))
+        }
+    }
+
+    // Disabled scripts stay where they are in the order but get skipped. These go by name
+    // in every stage and return false if there was no script with the name.
+    pub fn enable_script(&mut self, name: &str) -> bool {
+        self.scripts.set_enabled(name, true)
+    }
+
+    pub fn disable_script(&mut self, name: &str) -> bool {
+        self.scripts.set_enabled(name, false)
+    }
+
+    pub fn is_script_enabled(&self, name: &str) -> bool {
+        self.scripts.is_enabled(name)
+    }
+
+    // The script stays registered, so scenes can still add it back
+    pub fn remove_script(&mut self, name: &str) -> bool {
+        self.scripts.remove(name)
+    }
+
+    // Adding a name again replaces the system
+    pub fn add_system(&mut self, system: System) {
+        self.systems.add(system);
+    }
+
+    pub fn remove_system(&mut self, name: &str) -> bool {
+        self.systems.remove(name)
+    }
+
+    pub fn list_systems(&self) -> Vec<&str> {
+        self.systems.names()
+    }
+
+    // Systems in the same batch run at the same time
+    pub fn list_system_batches(&self) -> Vec<Vec<&str>> {
+        self.systems.batches()
+    }
+
+    // Off runs every system on this thread, results are the same either way
+    pub fn set_parallel_systems(&mut self, parallel: bool) {
+        self.systems.parallel = parallel;
+    }
+
+    pub fn run_systems(&mut self, delta_time: f32) {
+        self.systems.run(&mut self.entities, &mut self.resources, delta_time);
+    }
+
+    // Timers and sequences go by game time, see set_paused
+    pub fn add_timer(&mut self, timer: Timer) -> TimerId {
+        self.timers.add_timer(timer)
+    }
+
+    pub fn cancel_timer(&mut self, id: TimerId) -> bool {
+        self.timers.cancel_timer(id)
+    }
+
+    // Seconds until it next fires, None once it's done or cancelled
+    pub fn get_timer_remaining(&self, id: TimerId) -> Option<f32> {
+        self.timers.timer_remaining(id)
+    }
+
+    // Starts running next frame
+    pub fn start_sequence(&mut self, sequence: Sequence) -> SequenceId {
+        self.timers.start_sequence(sequence)
+    }
+
+    pub fn stop_sequence(&mut self, id: SequenceId) -> bool {
+        self.timers.stop_sequence(id)
+    }
+
+    pub fn is_sequence_running(&self, id: SequenceId) -> bool {
+        self.timers.is_sequence_running(id)
+    }
+
+    // Starts next frame. Adding a tween for a tag that's already being tweened doesn't stop
+    // the other one, cancel it first.
+    pub fn add_tween(&mut self, tween: Tween) -> TweenId {
+        self.tweens.add(tween)
+    }
+
+    pub fn cancel_tween(&mut self, id: TweenId) -> bool {
+        self.tweens.cancel(id)
+    }
+
+    // Until the whole chain is done
+    pub fn is_tween_running(&self, id: TweenId) -> bool {
+        self.tweens.is_running(id)
+    }
+
+    // While paused the event loop skips the entity functions, systems, fixed update
+    // scripts, timers, sequences and tweens. Update and late update scripts keep running so a
+    // pause menu still works, and so do timers and sequences made with_runs_while_paused.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Names in the order they run
+    pub fn list_scripts(&self, stage: ScriptStage) -> Vec<&str> {
+        self.scripts.in_stage(stage).map(|script| script.name.as_str()).collect()
+    }
+
+    // For scripts that only get added by scenes
+    pub fn register_script(&mut self, name: &str, script: fn(&mut Instance2D)) {
+        self.registry.register_script(name, script);
+    }
+
+    // Entity update/start functions have to be registered to be saved in or loaded from a scene
+    pub fn register_entity_fn(&mut self, name: &str, function: fn(&mut Entity)) {
+        self.registry.register_entity_fn(name, function);
+    }
+
+    pub fn register_prefab(&mut self, name: &str, prefab: Prefab) {
+        self.prefabs.register(name, prefab);
+    }
+
+    pub fn get_prefabs(&self) -> &Prefabs {
+        &self.prefabs
+    }
+
+    pub fn mut_prefabs(&mut self) -> &mut Prefabs {
+        &mut self.prefabs
+    }
+
+    // Entities for a prefab and its children without adding them
+    pub fn instantiate_prefab(
+        &self,
+        name: &str,
+        overrides: &[(&str, TagValue)],
+    ) -> Result<Vec<(Entity, Option<usize>)>, String> {
+        self.prefabs.instantiate(name, overrides, &self.registry)
+    }
+
+    // Adds the prefab and its children to the environment, children are parented to the
+    // entities they were spawned with. Returns the id of the new entity.
+    pub fn spawn_prefab(&mut self, name: &str, overrides: &[(&str, TagValue)]) -> Result<EntityId, String> {
+        let entities = self.instantiate_prefab(name, overrides)?;
+        let ids = self.add_entities(entities);
+        ids.first().copied().ok_or_else(|| format!("Prefab {} made no entities", name))
+    }
+
+    // Makes `child` move with `parent`, or detaches it with None. The child stays where
+    // it is in the world either way.
+    pub fn set_parent_by_id(&mut self, child: EntityId, parent: Option<EntityId>) -> Result<(), String> {
+        if let Some(parent) = parent {
+            if !self.entities.contains(parent) {
+                return Err(format!("No entity with id {:?}", parent));
+            }
+            if parent == child || hierarchy::ancestors(&self.entities, parent).contains(&child) {
+                return Err(format!("{:?} can't be a parent of {:?}, it is under it", parent, child));
+            }
+        }
+        let Some(entity) = self.entities.get_mut(child) else {
+            return Err(format!("No entity with id {:?}", child));
+        };
+        for tag in ["local_location", "local_rotation", "local_scale"] {
+            entity.tags.remove(tag);
+        }
+        entity.parent = parent;
+        Ok(())
+    }
+
+    pub fn get_parent_by_id(&self, id: EntityId) -> Option<EntityId> {
+        self.entities.get(id)?.parent.filter(|parent| self.entities.contains(*parent))
+    }
+
+    pub fn get_children_by_id(&self, id: EntityId) -> Vec<EntityId> {
+        hierarchy::children(&self.entities, id)
+    }
+
+    // Children before grandchildren
+    pub fn get_descendants_by_id(&self, id: EntityId) -> Vec<EntityId> {
+        hierarchy::descendants(&self.entities, id)
+    }
+
+    // Parent first, root last
+    pub fn get_ancestors_by_id(&self, id: EntityId) -> Vec<EntityId> {
+        hierarchy::ancestors(&self.entities, id)
+    }
+
+    // The versions that take names use the first entity with the name, go by id when
+    // several entities share one (two of the same prefab, say)
+    pub fn set_parent(&mut self, child: &str, parent: Option<&str>) -> Result<(), String> {
+        let Some(child_id) = self.entities.id_of(child) else {
+            return Err(format!("No entity named {}", child));
+        };
+        let parent_id = match parent {
+            Some(parent) => match self.entities.id_of(parent) {
+                Some(id) => Some(id),
+                None => return Err(format!("No entity named {}", parent)),
+            },
+            None => None,
+        };
+        self.set_parent_by_id(child_id, parent_id)
+    }
+
+    pub fn get_parent(&self, name: &str) -> Option<String> {
+        let parent = self.get_parent_by_id(self.entities.id_of(name)?)?;
+        self.entities.get(parent).map(|entity| entity.get_name())
+    }
+
+    // Names of the direct children
+    pub fn get_children(&self, name: &str) -> Vec<String> {
+        match self.entities.id_of(name) {
+            Some(id) => self.names_of(hierarchy::children(&self.entities, id)),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_descendants(&self, name: &str) -> Vec<String> {
+        match self.entities.id_of(name) {
+            Some(id) => self.names_of(hierarchy::descendants(&self.entities, id)),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_ancestors(&self, name: &str) -> Vec<String> {
+        match self.entities.id_of(name) {
+            Some(id) => self.names_of(hierarchy::ancestors(&self.entities, id)),
+            None => Vec::new(),
+        }
+    }
+
+    fn names_of(&self, ids: Vec<EntityId>) -> Vec<String> {
+        ids.into_iter()
+            .filter_map(|id| self.entities.get(id))
+            .map(|entity| entity.get_name())
+            .collect()
+    }
+
+    // Removes the entity and everything under it, returns how many entities went
+    pub fn remove_entity(&mut self, name: &str) -> usize {
+        match self.entities.id_of(name) {
+            Some(id) => self.despawn(id),
+            None => 0,
+        }
+    }
+
+    // Same as remove_entity, ids of removed entities stop finding anything. Other entities
+    // with the same name as the removed one are left alone.
+    pub fn despawn(&mut self, id: EntityId) -> usize {
+        if !self.entities.contains(id) {
+            return 0;
+        }
+        let mut doomed: HashSet<EntityId> = hierarchy::descendants(&self.entities, id).into_iter().collect();
+        doomed.insert(id);
+        let removed = self.entities.retain(|other, _| !doomed.contains(&other));
+        let count = removed.len();
+        self.destroyed(removed);
+        count
+    }
+
+    // Runs `hook` of the entity and moves whatever it queued onto the environment's commands
+    fn run_hook(&mut self, id: EntityId, hook: fn(&Entity) -> Option<scene::EntityFn>) {
+        if let Some(function) = self.entities.get(id).and_then(hook) {
+            self.run_entity_fn(id, function);
+        }
+    }
+
+    pub(crate) fn run_entity_fn(&mut self, id: EntityId, function: impl FnOnce(&mut Entity)) {
+        if let Some(entity) = self.entities.get_mut(id) {
+            function(entity);
+            self.commands.take_from(entity.commands(), id);
+        }
+    }
+
+    fn destroyed(&mut self, removed: Vec<(EntityId, Entity)>) {
+        for (id, mut entity) in removed {
+            self.events.send(EntityDespawned {
+                id,
+                name: entity.get_name(),
+            });
+            if let Some(destroy_function) = entity.destroy_function {
+                destroy_function(&mut entity);
+                self.commands.take_from(entity.commands(), id);
+            }
+        }
+    }
+
+    // Each entity can come with the position of its parent in the list. Spawn functions
+    // run once all of them are in, so a parent can find its children.
+    fn add_entities(&mut self, entities: Vec<(Entity, Option<usize>)>) -> Vec<EntityId> {
+        let mut parents = Vec::with_capacity(entities.len());
+        let mut ids = Vec::with_capacity(entities.len());
+        for (entity, parent) in entities {
+            parents.push(parent);
+            ids.push(self.entities.insert(entity));
+        }
+        for (id, parent) in ids.iter().zip(parents) {
+            let parent = parent.and_then(|parent| ids.get(parent)).copied();
+            if let (Some(entity), Some(parent)) = (self.entities.get_mut(*id), parent) {
+                entity.parent = Some(parent);
+            }
+        }
+        for id in &ids {
+            self.spawned(*id);
+        }
+        ids
+    }
+
+    fn spawned(&mut self, id: EntityId) {
+        if let Some(entity) = self.entities.get(id) {
+            self.events.send(EntitySpawned {
+                id,
+                name: entity.get_name(),
+            });
+        }
+        self.run_hook(id, |entity| entity.spawn_function);
+    }
+
+    // Start functions of entities that haven't started yet, the event loop runs this before
+    // the start scripts and again every frame before the update functions
+    pub fn start_entities(&mut self) {
+        for id in self.entities.ids().to_vec() {
+            let Some(entity) = self.entities.get_mut(id) else {
+                continue;
+            };
+            if entity.started {
+                continue;
+            }
+            entity.started = true;
+            self.run_hook(id, |entity| entity.start_function);
+        }
+    }
+
+    // Starts new entities, then runs every update function followed by every late update
+    // function. Entities are never removed for not having one.
+    pub fn update_entities(&mut self) {
+        self.start_entities();
+        for id in self.entities.ids().to_vec() {
+            self.run_hook(id, |entity| entity.update_function);
+        }
+        for id in self.entities.ids().to_vec() {
+            self.run_hook(id, |entity| entity.late_update_function);
+        }
+    }
+
+    // Resources are saved along with the entities by save games
+    pub fn set_resource(&mut self, name: &str, value: TagValue) {
+        self.resources.insert(name.to_string(), value);
+    }
+
+    pub fn get_resource(&self, name: &str) -> Option<&TagValue> {
+        self.resources.get(name)
+    }
+
+    pub fn get_mut_resource(&mut self, name: &str) -> Option<&mut TagValue> {
+        self.resources.get_mut(name)
+    }
+
+    pub fn modify_resource(&mut self, name: &str, modify: impl FnOnce(&mut TagValue)) -> Result<(), TagError> {
+        match self.resources.get_mut(name) {
+            Some(value) => {
+                modify(value);
+                Ok(())
+            }
+            None => Err(TagError::Missing(name.to_string())),
+        }
+    }
+
+    pub fn remove_resource(&mut self, name: &str) -> Option<TagValue> {
+        self.resources.remove(name)
+    }
+
+    pub fn list_resources(&self) -> &HashMap<String, TagValue> {
+        &self.resources
+    }
+
+    pub fn clear_resources(&mut self) {
+        self.resources.clear();
+    }
+
+    // For scripts to spawn and despawn entities, see Entity::commands for entity functions
+    pub fn commands(&mut self) -> &mut Commands {
+        &mut self.commands
+    }
+
+    // Runs everything queued so far, the event loop does this every frame
+    pub fn apply_commands(&mut self) {
+        let commands = std::mem::take(&mut self.commands);
+        commands.apply(self);
+    }
+
+    // Sending and reading events, see Commands::send_event for entity functions
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
+    pub fn mut_events(&mut self) -> &mut Events {
+        &mut self.events
+    }
+
+    // Collision events for every pair of overlapping colliders, the event loop does this
+    // every frame once the entities have moved
+    pub fn send_collisions(&mut self) {
+        for (a, b) in collision::overlapping(&self.entities) {
+            self.events.send(Collision { a, b });
+        }
+    }
+
+    pub fn get_registry(&self) -> &ScriptRegistry {
+        &self.registry
+    }
+
+    // Entities and non built-in scripts as a scene, fails if an entity uses a function
+    // that isn't registered
+    pub fn to_scene(&self) -> Result<Scene, String> {
+        let mut scene = Scene {
+            entities: scene::entities_to_scene(&self.entities, &self.registry)?,
+            ..Scene::default()
+        };
+        for stage in ScriptStage::ALL {
+            *scene.mut_scripts(stage) = self
+                .scripts
+                .in_stage(stage)
+                .map(|script| script.name.clone())
+                .filter(|name| !self.registry.is_builtin(name))
+                .collect();
+        }
+        Ok(scene)
+    }
+
+    // Replaces the entities and scripts with the ones in the scene, built-in scripts are
+    // kept. Scripts that were already in the stage keep their order settings, new ones run
+    // in the order the scene lists them. Nothing changes if a name in the scene isn't registered.
+    pub fn load_scene(&mut self, scene: &Scene) -> Result<(), String> {
+        let mut scripts = Vec::new();
+        for stage in ScriptStage::ALL {
+            for name in scene.get_scripts(stage) {
+                let Some(script) = self.registry.get_script(name) else {
+                    return Err(format!("No script registered as {}", name));
+                };
+                let order = self.scripts.get_order(stage, name).cloned().unwrap_or_default();
+                scripts.push((stage, name.clone(), script, order));
+            }
+        }
+        let entities = scene::entities_from_scene(&scene.entities, &self.registry)?;
+
+        let registry = &self.registry;
+        self.scripts.retain(|script| registry.is_builtin(&script.name));
+        self.scripts.add_all(scripts);
+        let removed = self.entities.clear();
+        self.destroyed(removed);
+        self.add_entities(entities);
+        Ok(())
+    }
+
+    // Runs the entity's spawn function straight away, its start function runs next frame
+    pub fn add_entity(&mut self, entity: Entity) -> EntityId {
+        let id = self.entities.insert(entity);
+        self.spawned(id);
+        id
+    }
+
+    // For games that look entities up by name, fails instead of adding a second entity
+    // with a name that is already taken
+    pub fn add_unique_entity(&mut self, entity: Entity) -> Result<EntityId, String> {
+        let name = entity.get_name();
+        if self.entities.id_of(&name).is_some() {
+            return Err(format!("There is already an entity named {}", name));
+        }
+        Ok(self.add_entity(entity))
+    }
+
+    // None once the entity has been removed, even if something else took its slot
+    pub fn get_entity_by_id(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(id)
+    }
+
+    pub fn get_mut_entity_by_id(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities.get_mut(id)
+    }
+
+    pub fn contains_entity(&self, id: EntityId) -> bool {
+        self.entities.contains(id)
+    }
+
+    // Id of the first entity added with the name
+    pub fn get_entity_id(&self, name: &str) -> Option<EntityId> {
+        self.entities.id_of(name)
+    }
+
+    // Same order as list_entities
+    pub fn list_entity_ids(&self) -> &[EntityId] {
+        self.entities.ids()
+    }
+
+    // Tilemaps get animated and drawn every frame, relative to the camera
+    pub fn add_tilemap(&mut self, tilemap: Tilemap) -> usize {
+        self.tilemaps.push(tilemap);
+        self.tilemaps.len() - 1
+    }
+
+    pub fn list_tilemaps(&self) -> &Vec<Tilemap> {
+        &self.tilemaps
+    }
+
+    pub fn mut_tilemaps(&mut self) -> &mut Vec<Tilemap> {
+        &mut self.tilemaps
     }
 
-    pub fn add_entity(&mut self, entity: Entity) {
-        self.entities.push(entity);
+    // Emitters get updated and drawn every frame
+    pub fn add_emitter(&mut self, emitter: ParticleEmitter) -> usize {
+        self.emitters.push(emitter);
+        self.emitters.len() - 1
     }
 
-    pub fn mut_entities(&mut self) -> &mut Vec<Entity> {
-        &mut self.entities
+    pub fn list_emitters(&self) -> &Vec<ParticleEmitter> {
+        &self.emitters
     }
-    pub fn list_entities(&self) -> &Vec<Entity> {
-        &self.entities
+
+    pub fn mut_emitters(&mut self) -> &mut Vec<ParticleEmitter> {
+        &mut self.emitters
+    }
+
+    pub fn mut_entities(&mut self) -> &mut [Entity] {
+        self.entities.as_mut_slice()
+    }
+    pub fn list_entities(&self) -> &[Entity] {
+        self.entities.as_slice()
     }
 
     pub fn print_all_entities(&self) {
-        for ent in &self.entities {
+        for ent in self.entities.as_slice() {
             if let Some(name) = ent.get_tag("name") {
                 println!("{:?} :", name)
             } else {
@@ -78,50 +728,39 @@ impl Environment {
     }
 
     pub fn print_all_scripts(&self) {
-        println!("Start Scripts -");
-        for script in &self.start_scripts {
-            println!("    {}",script.0)
-        }
-        print!("\n");
-        println!("Update Scripts -");
-        for script in &self.update_scripts {
-            println!("    {}",script.0)
+        for stage in ScriptStage::ALL {
+            println!("{:?} Scripts -", stage);
+            for script in self.scripts.in_stage(stage) {
+                if script.enabled {
+                    println!("    {}", script.name)
+                } else {
+                    println!("    {} (disabled)", script.name)
+                }
+            }
+            print!("\n");
         }
     }
     
-    // Might be slow someone optimise / By someone I mean me :cry:
-    pub fn get_entity(&self, name: &str) -> Option<Entity>{
-        for entity in &self.entities {
-            if entity.get_name() == name { 
-                return Some(entity.clone())
-            }
-        }
-        None
+    // Looks the name up in an index, if there are several entities with the name this is
+    // the first one added
+    pub fn get_entity(&self, name: &str) -> Option<&Entity> {
+        self.entities.find(name)
     }
 
-    pub fn get_mut_entity(&mut self, name: &str) -> Option<&mut Entity>{
-        for entity in &mut self.entities {
-            if entity.get_name() == name { 
-                return Some(entity)
-            }
-        }
-        None
+    pub fn get_mut_entity(&mut self, name: &str) -> Option<&mut Entity> {
+        self.entities.find_mut(name)
     }
 
-
-    // Might be slow ngl
+    // The entity keeps its id
     pub fn overwrite(&mut self, name: &str, entity_to_change: &mut Entity) {
-        for entity in &mut self.entities {
-            if entity.get_name() == name {
-                *entity = entity_to_change.clone(); // Assuming Entity implements Clone
-                return;
-            }
+        match self.entities.find_mut(name) {
+            Some(entity) => *entity = entity_to_change.clone(),
+            None => eprintln!("Cannot replace - No entity found with the name : {}", name),
         }
-        eprintln!("Cannot replace - No entity found with the name : {}", name)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TagValue {
     String(String),
     Int(i32),
@@ -129,6 +768,9 @@ pub enum TagValue {
     Double(f64),
     Color(Color),
     Vec2(Vec2),
+    Bool(bool),
+    List(Vec<TagValue>),
+    Map(BTreeMap<String, TagValue>), // Sorted so saves and scenes come out the same every time
 }
 
 impl TagValue {
@@ -168,21 +810,64 @@ impl TagValue {
             _=>None
         }
     }
+    pub fn extract_bool(&self) -> Option<bool> {
+        match &self {
+            TagValue::Bool(x) => Some(*x),
+            _=>None
+        }
+    }
+    pub fn extract_list(&self) -> Option<Vec<TagValue>> {
+        match &self {
+            TagValue::List(x) => Some(x.clone()),
+            _=>None
+        }
+    }
+    pub fn extract_map(&self) -> Option<BTreeMap<String, TagValue>> {
+        match &self {
+            TagValue::Map(x) => Some(x.clone()),
+            _=>None
+        }
+    }
 
-    pub fn apply(&mut self, formula: fn( )->Self) {
+    // `t` of the way from self to `to`, None unless both are the same kind of number, color
+    // or Vec2. Ints, colors and Vec2s get rounded, colors are kept in 0 to 255.
+    pub fn lerp(&self, to: &TagValue, t: f32) -> Option<TagValue> {
+        let mix = |from: f32, to: f32| from + (to - from) * t;
+        let channel = |from: u8, to: u8| mix(from as f32, to as f32).round().clamp(0.0, 255.0) as u8;
+        match (self, to) {
+            (TagValue::Int(from), TagValue::Int(to)) => Some(TagValue::Int(mix(*from as f32, *to as f32).round() as i32)),
+            (TagValue::Float(from), TagValue::Float(to)) => Some(TagValue::Float(mix(*from, *to))),
+            (TagValue::Double(from), TagValue::Double(to)) => Some(TagValue::Double(from + (to - from) * t as f64)),
+            (TagValue::Color(from), TagValue::Color(to)) => Some(TagValue::Color(Color {
+                r: channel(from.r, to.r),
+                g: channel(from.g, to.g),
+                b: channel(from.b, to.b),
+            })),
+            (TagValue::Vec2(from), TagValue::Vec2(to)) => Some(TagValue::Vec2(Vec2::new(
+                mix(from.x as f32, to.x as f32).round() as i32,
+                mix(from.y as f32, to.y as f32).round() as i32,
+            ))),
+            _ => None,
+        }
+    }
 
+    // Replaces the value with what `formula` makes of it
+    //     tag.apply(|hp| hp.try_sub(&TagValue::Int(10)).unwrap_or(TagValue::Int(0)))
+    pub fn apply(&mut self, formula: impl FnOnce(TagValue) -> TagValue) {
+        let value = std::mem::replace(self, TagValue::Bool(false));
+        *self = formula(value);
     }
 
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Vec2 {
     pub x: i32,
     pub y: i32,
 }
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -208,11 +893,36 @@ impl Vec2 {
     }
 }
 
-#[derive(Clone)]
 pub struct Entity {
     pub update_function: Option<fn(&mut Entity)>,
-    pub start_function: Option<fn(&mut Entity)>,
+    pub start_function: Option<fn(&mut Entity)>, // Runs once, on the first frame the entity is in the environment
+    pub spawn_function: Option<fn(&mut Entity)>, // Runs as soon as the entity is added
+    pub late_update_function: Option<fn(&mut Entity)>, // Runs after every entity has been updated
+    pub destroy_function: Option<fn(&mut Entity)>, // Runs when the entity is removed
     pub tags: HashMap<String, TagValue>,
+    pub(crate) function_names: EntityFnNames, // Set by the with_named_ builders, scenes and prefabs
+    commands: Commands,
+    pub(crate) started: bool,
+    pub(crate) parent: Option<EntityId>, // Set through Environment::set_parent_by_id
+}
+
+// Commands stay with the entity they were queued on, copies (overwrite, prefabs, scenes)
+// start with none so nothing gets applied twice
+impl Clone for Entity {
+    fn clone(&self) -> Self {
+        Entity {
+            update_function: self.update_function,
+            start_function: self.start_function,
+            spawn_function: self.spawn_function,
+            late_update_function: self.late_update_function,
+            destroy_function: self.destroy_function,
+            tags: self.tags.clone(),
+            function_names: self.function_names.clone(),
+            commands: Commands::new(),
+            started: self.started,
+            parent: self.parent,
+        }
+    }
 }
 
 impl Entity {
@@ -220,19 +930,91 @@ impl Entity {
         Entity {
             update_function: None,
             start_function: None,
+            spawn_function: None,
+            late_update_function: None,
+            destroy_function: None,
             tags: HashMap::new(),
+            function_names: EntityFnNames::default(),
+            commands: Commands::new(),
+            started: false,
+            parent: None,
         }
     }
 
+    // None for entities that aren't in an environment's hierarchy
+    pub fn get_parent_id(&self) -> Option<EntityId> {
+        self.parent
+    }
+
+    // Spawning and despawning from inside an update or start function, applied after all
+    // of the entities have been updated
+    pub fn commands(&mut self) -> &mut Commands {
+        &mut self.commands
+    }
+
     pub fn with_update_fn(self, update_fn: fn(&mut Entity)) -> Self {
         let mut x = self;
         x.update_function = Some(update_fn);
+        x.function_names.update = None;
+        x
+    }
+
+    // The name is the one it was registered under, entities need these to be saved in a scene
+    pub fn with_named_update_fn(self, name: &str, update_fn: fn(&mut Entity)) -> Self {
+        let mut x = self.with_update_fn(update_fn);
+        x.function_names.update = Some(name.to_string());
         x
     }
 
     pub fn with_start_fn(self, start_fn: fn(&mut Entity)) -> Self {
         let mut x = self;
         x.start_function = Some(start_fn);
+        x.function_names.start = None;
+        x
+    }
+
+    pub fn with_named_start_fn(self, name: &str, start_fn: fn(&mut Entity)) -> Self {
+        let mut x = self.with_start_fn(start_fn);
+        x.function_names.start = Some(name.to_string());
+        x
+    }
+
+    pub fn with_spawn_fn(self, spawn_fn: fn(&mut Entity)) -> Self {
+        let mut x = self;
+        x.spawn_function = Some(spawn_fn);
+        x.function_names.spawn = None;
+        x
+    }
+
+    pub fn with_named_spawn_fn(self, name: &str, spawn_fn: fn(&mut Entity)) -> Self {
+        let mut x = self.with_spawn_fn(spawn_fn);
+        x.function_names.spawn = Some(name.to_string());
+        x
+    }
+
+    pub fn with_late_update_fn(self, late_update_fn: fn(&mut Entity)) -> Self {
+        let mut x = self;
+        x.late_update_function = Some(late_update_fn);
+        x.function_names.late_update = None;
+        x
+    }
+
+    pub fn with_named_late_update_fn(self, name: &str, late_update_fn: fn(&mut Entity)) -> Self {
+        let mut x = self.with_late_update_fn(late_update_fn);
+        x.function_names.late_update = Some(name.to_string());
+        x
+    }
+
+    pub fn with_destroy_fn(self, destroy_fn: fn(&mut Entity)) -> Self {
+        let mut x = self;
+        x.destroy_function = Some(destroy_fn);
+        x.function_names.destroy = None;
+        x
+    }
+
+    pub fn with_named_destroy_fn(self, name: &str, destroy_fn: fn(&mut Entity)) -> Self {
+        let mut x = self.with_destroy_fn(destroy_fn);
+        x.function_names.destroy = Some(name.to_string());
         x
     }
 
@@ -256,6 +1038,24 @@ impl Entity {
         self.tags.insert(tag_name.to_string(), tag_value);
     }
 
+    // Like get_tag without the clone, for the typed accessors
+    //     let hp = entity.try_get_tag("hp")?.as_int()?;
+    pub fn try_get_tag(&self, tag_name: &str) -> Result<&TagValue, TagError> {
+        self.tags.get(tag_name).ok_or_else(|| TagError::Missing(tag_name.to_string()))
+    }
+
+    // Changes the tag where it is
+    //     entity.modify_tag("location", |location| if let TagValue::Vec2(v) = location { v.x += 1 })
+    pub fn modify_tag(&mut self, tag_name: &str, modify: impl FnOnce(&mut TagValue)) -> Result<(), TagError> {
+        match self.tags.get_mut(tag_name) {
+            Some(value) => {
+                modify(value);
+                Ok(())
+            }
+            None => Err(TagError::Missing(tag_name.to_string())),
+        }
+    }
+
     pub fn get_name(&self) -> String {
         match self.tags.get("name") {
             Some(TagValue::String(a)) => a.to_string(),
@@ -274,27 +1074,59 @@ impl Entity {
 
 pub enum RenderingEngine2D {
     Sdl2,
+    Sdl2Headless,
+}
+
+// Falls back to the null device so a missing sound card doesn't stop the game
+fn open_audio(engine_settings: &EngineSettings2D) -> Audio {
+    match Audio::new(&engine_settings.engine_env) {
+        Ok(audio) => audio,
+        Err(e) => {
+            eprintln!("Error: Could not open audio device, sound is disabled - {}", e);
+            Audio::new_null()
+        }
+    }
 }
 
 impl Instance2D {
     pub fn new() -> Self {
+        let engine_settings = EngineSettings2D::new();
         Instance2D {
             // Default values
             screen: Screen::new(),
-            engine_settings: EngineSettings2D::new(),
+            audio: open_audio(&engine_settings),
+            assets: Assets::new(),
+            saves: Saves::default(),
+            engine_settings,
             environment: Environment::new(),
         }
     }
 
     pub fn new_skeleton() -> Self {
+        let engine_settings = EngineSettings2D::new();
         Instance2D {
             // Default values
             screen: Screen::new(),
-            engine_settings: EngineSettings2D::new(),
+            audio: open_audio(&engine_settings),
+            assets: Assets::new(),
+            saves: Saves::default(),
+            engine_settings,
             environment: Environment::new_skeleton(),
         }
     }
 
+    // Same as new() but nothing shows up on screen, useful for tests and benchmarks
+    pub fn new_headless() -> Self {
+        Instance2D {
+            screen: Screen::new(),
+            engine_settings: EngineSettings2D::new_headless(),
+            environment: Environment::new(),
+            audio: Audio::new_null(),
+            assets: Assets::new(),
+            saves: Saves::default(),
+        }
+    }
+
     pub fn start(self) {
         eventloop::eventloop(self)
     }
@@ -307,7 +1139,149 @@ impl Instance2D {
         &self.engine_settings.keys
     }
 
+    pub fn get_mouse(&self) -> &Mouse {
+        &self.engine_settings.mouse
+    }
+
+    // Written as a PNG once the current frame has been drawn
+    pub fn save_screenshot(&mut self, path: &str) {
+        self.engine_settings.capture.pending_screenshots.push(path.into());
+    }
+
+    // Keeps a copy of every presented frame around for last_frame()
+    pub fn set_keep_last_frame(&mut self, keep: bool) {
+        self.engine_settings.capture.keep_last_frame = keep;
+        if !keep {
+            self.engine_settings.capture.last_frame = None;
+        }
+    }
+
+    pub fn last_frame(&self) -> Option<&FrameBuffer> {
+        self.engine_settings.capture.last_frame.as_ref()
+    }
+
+    // Saves every Nth frame to the directory, for turning into GIFs/videos
+    pub fn record_frames(&mut self, directory: &str, every_nth: u32) -> Result<(), String> {
+        self.engine_settings.capture.recorder = Some(FrameRecorder::new(directory, every_nth)?);
+        Ok(())
+    }
+
+    // Returns how many frames were written
+    pub fn stop_recording(&mut self) -> u32 {
+        match self.engine_settings.capture.recorder.take() {
+            Some(recorder) => recorder.frames_saved(),
+            None => 0,
+        }
+    }
+
     pub fn add_tag_handler() {}
+
+    // Replaces the environment's entities and scripts with a scene file read through the
+    // asset pack, format by extension (.ron, .json or .toml)
+    pub fn load_scene(&mut self, path: &str) -> Result<(), String> {
+        let scene = Scene::load_from(self.assets.get_pack(), path)?;
+        self.environment.load_scene(&scene)
+    }
+
+    pub fn save_scene(&self, path: &str) -> Result<(), String> {
+        self.environment.to_scene()?.save(path)
+    }
+
+    // Registers every prefab in the file, read through the asset pack
+    pub fn load_prefabs(&mut self, path: &str) -> Result<usize, String> {
+        self.environment.prefabs.load_from(self.assets.get_pack(), path)
+    }
+
+    pub fn save_game(&self, path: &str) -> Result<(), String> {
+        self.saves.save(&self.environment, path)
+    }
+
+    // Returns the version the save was written as
+    pub fn load_game(&mut self, path: &str) -> Result<u32, String> {
+        let version = self.saves.load(&mut self.environment, path)?;
+        let mut tilemaps = std::mem::take(self.environment.mut_tilemaps());
+        let mut result = Ok(version);
+        for tilemap in tilemaps.iter_mut() {
+            // The saved tiles are kept, the map file is only watched for changes again
+            if let Some(path) = tilemap.source_path.clone() {
+                match self.load_asset::<TiledMap>(&path) {
+                    Ok(handle) => tilemap.source = Some(handle),
+                    Err(e) => result = Err(e),
+                }
+            }
+            if let Err(e) = self.load_tileset_textures(tilemap) {
+                result = Err(e);
+            }
+        }
+        *self.environment.mut_tilemaps() = tilemaps;
+        result
+    }
+
+    // Shortcut for assets.load() with this instance's engine settings
+    pub fn load_asset<T: Asset>(&mut self, path: &str) -> Result<Handle<T>, String> {
+        self.assets.load(path, &mut self.engine_settings)
+    }
+
+    pub fn get_asset<T: Asset>(&self, handle: &Handle<T>) -> Option<&T> {
+        self.assets.get(handle)
+    }
+
+    pub fn unload_unused_assets(&mut self) -> usize {
+        self.assets.unload_unused(&mut self.engine_settings)
+    }
+
+    // Loads a Tiled map with its tileset textures, adds the tilemap to the environment
+    // and every object in it as an entity. Returns the index of the tilemap.
+    // The map and its images go through the asset store so they get hot reloaded too,
+    // objects are only added once though.
+    pub fn load_tilemap(&mut self, path: &str) -> Result<usize, String> {
+        let handle = self.load_asset::<TiledMap>(path)?;
+        let Some(map) = self.assets.get(&handle) else {
+            return Err(format!("{} was unloaded while loading", path));
+        };
+        let objects = map.objects.clone();
+        let mut tilemap = map.tilemap.clone();
+        tilemap.source = Some(handle);
+        tilemap.source_path = Some(path.to_string());
+        self.load_tileset_textures(&mut tilemap)?;
+
+        for entity in objects {
+            self.environment.add_entity(entity);
+        }
+        Ok(self.environment.add_tilemap(tilemap))
+    }
+
+    // For tilemaps made in code, the images are loaded as Texture assets like load_tilemap does
+    pub fn load_tileset_textures(&mut self, tilemap: &mut Tilemap) -> Result<(), String> {
+        tilemap.texture_handles.clear();
+        for tileset in &mut tilemap.tilesets {
+            let Some(image) = &tileset.image else {
+                continue;
+            };
+            let handle = self.assets.load::<Texture>(image, &mut self.engine_settings)?;
+            tileset.texture = self.assets.get(&handle).map(|texture| texture.id);
+            tilemap.texture_handles.push(handle);
+        }
+        Ok(())
+    }
+
+    // Rebuilds a tilemap from load_tilemap() after its map file or one of its images was
+    // reloaded, keeping where it was placed
+    pub(crate) fn refresh_tilemap(&mut self, tilemap: &mut Tilemap) -> Result<(), String> {
+        let Some(source) = tilemap.source.clone() else {
+            return Ok(());
+        };
+        let Some(map) = self.assets.get(&source) else {
+            return Err(format!("{} is no longer loaded", source.path().display()));
+        };
+        let mut fresh = map.tilemap.clone();
+        fresh.position = tilemap.position.clone();
+        fresh.source = Some(source);
+        fresh.source_path = tilemap.source_path.clone();
+        self.load_tileset_textures(&mut fresh)?;
+        *tilemap = fresh;
+        Ok(())
+    }
 }
 
 impl EngineSettings2D {
@@ -320,11 +1294,129 @@ impl EngineSettings2D {
             use_delta_time: true,
             is_running: true,
             keys: Keys::new(),
+            mouse: Mouse::new(),
+            post_effects: Vec::new(),
+            camera: Vec2::new(0, 0),
+            delta_time: 0.0,
+            fixed_delta_time: None,
+            fixed_timestep: 1.0 / 60.0,
+            fixed_time: 0.0,
+            pressed_last_frame: Vec::new(),
+            last_frame_time: None,
+            white_texture: None,
+            capture: CaptureSettings::new(),
+        }
+    }
+
+    pub fn new_headless() -> Self {
+        EngineSettings2D {
+            engine_env: render::new_2D_window(RenderingEngine2D::Sdl2Headless, Screen::new()),
+            use_delta_time: true,
+            is_running: true,
+            keys: Keys::new(),
+            mouse: Mouse::new(),
+            post_effects: Vec::new(),
+            camera: Vec2::new(0, 0),
+            delta_time: 0.0,
+            fixed_delta_time: None,
+            fixed_timestep: 1.0 / 60.0,
+            fixed_time: 0.0,
+            pressed_last_frame: Vec::new(),
+            last_frame_time: None,
+            white_texture: None,
+            capture: CaptureSettings::new(),
+        }
+    }
+
+    // Called once at the start of every frame
+    pub fn update_delta_time(&mut self) {
+        let now = Instant::now();
+        self.delta_time = match (self.fixed_delta_time, self.last_frame_time) {
+            (Some(fixed), _) => fixed,
+            (None, Some(last)) => now.duration_since(last).as_secs_f32(),
+            (None, None) => 0.0,
+        };
+        self.last_frame_time = Some(now);
+    }
+
+    // Size of the area things are drawn into, the logical resolution if there is one
+    pub fn view_size(&self) -> (u32, u32) {
+        render::view_size(&self.engine_env)
+    }
+
+    pub fn apply_screen(&mut self, screen: &Screen) {
+        if let Err(e) = render::apply_screen(screen, &mut self.engine_env) {
+            eprintln!("Error: Could not apply screen settings - {}", e)
         }
     }
 
     pub fn update_display(&mut self) {
-        render::update_display(&mut self.engine_env)
+        let capture = self.capture.wants_frame();
+        let frame = render::update_display(&mut self.engine_env, &self.post_effects, capture);
+
+        if capture {
+            let frame = match frame {
+                Some(Ok(frame)) => Some(frame),
+                Some(Err(e)) => {
+                    eprintln!("Error: Could not capture frame - {}", e);
+                    None
+                }
+                None => None,
+            };
+            self.capture.frame_presented(frame);
+        }
+    }
+
+    // Queued, actually drawn in batches when the display updates
+    pub fn draw_rect(&mut self, rect: VisualRect) {
+        render::draw_rect(rect, &mut self.engine_env)
+    }
+
+    pub fn draw_sprite(&mut self, sprite: VisualSprite) {
+        render::draw_sprite(sprite, &mut self.engine_env)
+    }
+
+    pub fn create_texture(&mut self, width: u32, height: u32, pixels: &[u8]) -> Result<TextureId, String> {
+        render::create_texture(width, height, pixels, &mut self.engine_env)
+    }
+
+    pub fn destroy_texture(&mut self, texture: TextureId) {
+        render::destroy_texture(texture, &mut self.engine_env)
+    }
+
+    // Each id ends up drawing the other's texture
+    pub(crate) fn swap_textures(&mut self, a: TextureId, b: TextureId) -> bool {
+        render::swap_textures(a, b, &mut self.engine_env)
+    }
+
+    // A 1x1 white texture, tinting it gives solid colored quads that batch together
+    pub fn white_texture(&mut self) -> Result<TextureId, String> {
+        if let Some(texture) = self.white_texture {
+            return Ok(texture);
+        }
+        let texture = self.create_texture(1, 1, &[255, 255, 255, 255])?;
+        self.white_texture = Some(texture);
+        Ok(texture)
+    }
+
+    // Overlay draws skip the render target and post effects, for HUDs and minimaps
+    pub fn draw_overlay_rect(&mut self, rect: VisualRect) {
+        render::draw_overlay_rect(rect, &mut self.engine_env)
+    }
+
+    pub fn draw_overlay_sprite(&mut self, sprite: VisualSprite) {
+        render::draw_overlay_sprite(sprite, &mut self.engine_env)
+    }
+
+    // Render the scene into an offscreen texture of this size which then gets
+    // stretched over the window. None draws straight into the window again.
+    pub fn set_render_target(&mut self, size: Option<(u32, u32)>) -> Result<(), String> {
+        render::set_render_target(size, &mut self.engine_env)
+    }
+
+    // The texture the scene is rendered into, can be drawn as an overlay sprite (minimaps etc)
+    pub fn render_target(&self) -> Option<TextureId> {
+        render::render_target(&self.engine_env)
     }
 }
 
@@ -337,6 +1429,8 @@ impl Screen {
             caption: String::from("Zenith Game Window"),
             framerate_cap: 60,
             window_size: (600, 400),
+            logical_size: None,
+            scaling: ScalingMode::Fit,
         }
     }
 
@@ -363,6 +1457,40 @@ impl Screen {
     pub fn get_window_size(&self) -> &(u32, u32) {
         &self.window_size
     }
+
+    // The resolution the game is drawn at, scaled up to the window. None = same as the window
+    pub fn set_logical_size(&mut self, size: Option<(u32, u32)>) {
+        self.logical_size = size;
+    }
+
+    pub fn set_scaling_mode(&mut self, scaling: ScalingMode) {
+        self.scaling = scaling;
+    }
+
+    pub fn get_logical_size(&self) -> Option<(u32, u32)> {
+        self.logical_size
+    }
+
+    pub fn get_scaling_mode(&self) -> ScalingMode {
+        self.scaling
+    }
+}
+
+fn builtin_registry() -> ScriptRegistry {
+    let mut registry = ScriptRegistry::new();
+    for (name, script) in get_builtin_update_functions() {
+        registry.register_builtin(&name, script);
+    }
+    registry
+}
+
+// Built-ins run before every other update script
+fn builtin_schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+    for (name, script) in get_builtin_update_functions() {
+        schedule.add(ScriptStage::Update, &name, script, ScriptOrder::new().with_priority(i32::MIN));
+    }
+    schedule
 }
 
 fn get_builtin_update_functions() -> Vec<(String, fn(&mut Instance2D))> {
@@ -0,0 +1,231 @@
+// Where the asset store reads files from: a folder on disk, a folder embedded into the
+// executable with embed_assets!, or a single archive file made with write_archive.
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+const ARCHIVE_MAGIC: &[u8; 4] = b"ZPAK";
+const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Clone, Debug)]
+enum PackSource {
+    Disk(PathBuf),
+    Embedded(&'static include_dir::Dir<'static>),
+    Archive(Arc<HashMap<String, Vec<u8>>>),
+}
+
+#[derive(Clone, Debug)]
+pub struct AssetPack {
+    source: PackSource,
+}
+
+impl Default for AssetPack {
+    fn default() -> Self {
+        Self::disk("")
+    }
+}
+
+// Embeds the folder into the executable in release builds, debug builds read it from
+// disk so assets can be edited (and hot reloaded) without rebuilding. Takes the same
+// kind of path as include_dir!, usually starting with $CARGO_MANIFEST_DIR:
+//     instance.assets.set_pack(zenith::embed_assets!("$CARGO_MANIFEST_DIR/assets"));
+#[macro_export]
+macro_rules! embed_assets {
+    // tt instead of literal, include_dir! can't parse a literal that went through a macro
+    ($path:tt) => {{
+        #[cfg(debug_assertions)]
+        let pack = $crate::AssetPack::disk($path.replace("$CARGO_MANIFEST_DIR", env!("CARGO_MANIFEST_DIR")));
+        #[cfg(not(debug_assertions))]
+        let pack = {
+            use $crate::include_dir;
+            static DIR: include_dir::Dir<'static> = include_dir::include_dir!($path);
+            $crate::AssetPack::embedded(&DIR)
+        };
+        pack
+    }};
+}
+
+// Turns "a/./b/../c.png" into "a/c.png" so packed files are found no matter how the
+// path was put together
+fn normalize(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+impl AssetPack {
+    // Paths are relative to `root`, an empty root means the working directory
+    pub fn disk(root: impl AsRef<Path>) -> Self {
+        AssetPack {
+            source: PackSource::Disk(root.as_ref().to_path_buf()),
+        }
+    }
+
+    pub fn embedded(dir: &'static include_dir::Dir<'static>) -> Self {
+        AssetPack {
+            source: PackSource::Embedded(dir),
+        }
+    }
+
+    pub fn open_archive(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_archive_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // For archives put into the executable with include_bytes!
+    pub fn from_archive_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = bytes;
+        let mut read = |count: usize| -> Result<Vec<u8>, String> {
+            if reader.len() < count {
+                return Err("Archive is truncated".to_string());
+            }
+            let (head, rest) = reader.split_at(count);
+            reader = rest;
+            Ok(head.to_vec())
+        };
+        let read_u32 = |bytes: Vec<u8>| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        if read(4)? != ARCHIVE_MAGIC {
+            return Err("Not an asset archive".to_string());
+        }
+        let version = read_u32(read(4)?);
+        if version != ARCHIVE_VERSION {
+            return Err(format!("Unsupported archive version {}", version));
+        }
+
+        let count = read_u32(read(4)?);
+        let mut files = HashMap::new();
+        for _ in 0..count {
+            let path_length = read_u32(read(4)?) as usize;
+            let path = String::from_utf8(read(path_length)?).map_err(|e| e.to_string())?;
+            let data_length = read_u32(read(4)?) as usize;
+            files.insert(path, read(data_length)?);
+        }
+        Ok(AssetPack {
+            source: PackSource::Archive(Arc::new(files)),
+        })
+    }
+
+    // Packs every file under `dir` into one archive, returns how many files went in.
+    // Handy in a build script or a small packaging tool.
+    pub fn write_archive(dir: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<usize, String> {
+        let dir = dir.as_ref();
+        let output = output.as_ref();
+        let mut files = Vec::new();
+        collect_files(dir, dir, &mut files)?;
+        files.sort();
+
+        let mut archive = Vec::new();
+        archive.extend_from_slice(ARCHIVE_MAGIC);
+        archive.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        archive.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        for (name, path) in &files {
+            let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let length = u32::try_from(data.len()).map_err(|_| format!("{} is too big to pack", path.display()))?;
+            archive.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            archive.extend_from_slice(name.as_bytes());
+            archive.extend_from_slice(&length.to_le_bytes());
+            archive.extend_from_slice(&data);
+        }
+
+        let mut file = std::fs::File::create(output).map_err(|e| format!("{}: {}", output.display(), e))?;
+        file.write_all(&archive).map_err(|e| format!("{}: {}", output.display(), e))?;
+        Ok(files.len())
+    }
+
+    pub fn is_disk(&self) -> bool {
+        matches!(self.source, PackSource::Disk(_))
+    }
+
+    // Where the file lives on disk, None for packed files
+    pub fn disk_path(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        match &self.source {
+            PackSource::Disk(root) => Some(root.join(path)),
+            _ => None,
+        }
+    }
+
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        match &self.source {
+            PackSource::Disk(root) => root.join(path).is_file(),
+            PackSource::Embedded(dir) => dir.get_file(normalize(path)).is_some(),
+            PackSource::Archive(files) => files.contains_key(&normalize(path)),
+        }
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> Result<Vec<u8>, String> {
+        let path = path.as_ref();
+        let missing = || format!("{}: Not found in the asset pack", path.display());
+        match &self.source {
+            PackSource::Disk(root) => {
+                let full = root.join(path);
+                std::fs::read(&full).map_err(|e| format!("{}: {}", full.display(), e))
+            }
+            PackSource::Embedded(dir) => dir
+                .get_file(normalize(path))
+                .map(|file| file.contents().to_vec())
+                .ok_or_else(missing),
+            PackSource::Archive(files) => files.get(&normalize(path)).cloned().ok_or_else(missing),
+        }
+    }
+
+    pub fn read_to_string(&self, path: impl AsRef<Path>) -> Result<String, String> {
+        let path = path.as_ref();
+        String::from_utf8(self.read(path)?).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Every file in the pack, for disk packs this walks the root folder
+    pub fn files(&self) -> Vec<String> {
+        let mut names = match &self.source {
+            PackSource::Disk(root) => {
+                let mut files = Vec::new();
+                let root = if root.as_os_str().is_empty() { Path::new(".") } else { root };
+                if let Err(e) = collect_files(root, root, &mut files) {
+                    eprintln!("Error: Could not list assets - {}", e);
+                }
+                files.into_iter().map(|(name, _)| name).collect()
+            }
+            PackSource::Embedded(dir) => {
+                let mut files = Vec::new();
+                collect_embedded(dir, &mut files);
+                files
+            }
+            PackSource::Archive(files) => files.keys().cloned().collect(),
+        };
+        names.sort();
+        names
+    }
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("{}: {}", dir.display(), e))?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push((normalize(relative), path.clone()));
+        }
+    }
+    Ok(())
+}
+
+fn collect_embedded(dir: &include_dir::Dir, files: &mut Vec<String>) {
+    for file in dir.files() {
+        files.push(normalize(file.path()));
+    }
+    for dir in dir.dirs() {
+        collect_embedded(dir, files);
+    }
+}
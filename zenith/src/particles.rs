@@ -0,0 +1,287 @@
+use crate::{BlendMode, Color, EngineSettings2D, EntityId, TextureId, Vec2, VisualSprite};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleShape {
+    Rect,
+    Sprite(TextureId),
+}
+
+#[derive(Clone, Debug)]
+struct Particle {
+    x: f32,
+    y: f32,
+    velocity_x: f32,
+    velocity_y: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+// Spawns particles at its position (or the "location" tag of the entity it is
+// attached to) and updates/draws them every frame. All particles of an emitter
+// are drawn as one batch, rect particles use a plain white texture tinted per particle.
+// Once the entity it is attached to is gone the emitter stops where it was and lets the
+// particles it has left play out.
+#[derive(Clone, Debug)]
+pub struct ParticleEmitter {
+    pub position: Vec2,
+    pub attached_to: Option<EntityId>, // Its "location" tag moves the emitter
+    pub offset: Vec2,                // Added to the entity location
+    pub emitting: bool,
+    pub spawn_rate: f32,       // Particles per second while emitting
+    pub lifetime: (f32, f32),  // Min/max seconds
+    pub velocity_min: (f32, f32), // Pixels per second
+    pub velocity_max: (f32, f32),
+    pub gravity: (f32, f32),   // Pixels per second squared
+    pub drag: f32,             // Fraction of the velocity lost per second
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+    pub shape: ParticleShape,
+    pub blend: BlendMode,
+    pub layer: i32,
+    pub max_particles: usize,
+    particles: Vec<Particle>,
+    pending_burst: u32,
+    spawn_timer: f32,
+    rng: u64,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParticleEmitter {
+    pub fn new() -> Self {
+        ParticleEmitter {
+            position: Vec2::new(0, 0),
+            attached_to: None,
+            offset: Vec2::new(0, 0),
+            emitting: true,
+            spawn_rate: 10.0,
+            lifetime: (1.0, 1.0),
+            velocity_min: (-20.0, -20.0),
+            velocity_max: (20.0, 20.0),
+            gravity: (0.0, 0.0),
+            drag: 0.0,
+            start_color: Color::white(),
+            end_color: Color::white(),
+            start_size: 4.0,
+            end_size: 4.0,
+            shape: ParticleShape::Rect,
+            blend: BlendMode::Blend,
+            layer: 0,
+            max_particles: 10_000,
+            particles: Vec::new(),
+            pending_burst: 0,
+            spawn_timer: 0.0,
+            rng: 0x2545_F491_4F6C_DD1D,
+        }
+    }
+
+    pub fn with_position(self, position: Vec2) -> Self {
+        let mut x = self;
+        x.position = position;
+        x
+    }
+
+    pub fn attached_to(self, entity: EntityId, offset: Vec2) -> Self {
+        let mut x = self;
+        x.attached_to = Some(entity);
+        x.offset = offset;
+        x
+    }
+
+    pub fn with_spawn_rate(self, per_second: f32) -> Self {
+        let mut x = self;
+        x.spawn_rate = per_second;
+        x
+    }
+
+    pub fn with_lifetime(self, min: f32, max: f32) -> Self {
+        let mut x = self;
+        x.lifetime = (min, max);
+        x
+    }
+
+    pub fn with_velocity(self, min: (f32, f32), max: (f32, f32)) -> Self {
+        let mut x = self;
+        x.velocity_min = min;
+        x.velocity_max = max;
+        x
+    }
+
+    pub fn with_gravity(self, gravity: (f32, f32)) -> Self {
+        let mut x = self;
+        x.gravity = gravity;
+        x
+    }
+
+    pub fn with_drag(self, drag: f32) -> Self {
+        let mut x = self;
+        x.drag = drag;
+        x
+    }
+
+    pub fn with_colors(self, start: Color, end: Color) -> Self {
+        let mut x = self;
+        x.start_color = start;
+        x.end_color = end;
+        x
+    }
+
+    pub fn with_sizes(self, start: f32, end: f32) -> Self {
+        let mut x = self;
+        x.start_size = start;
+        x.end_size = end;
+        x
+    }
+
+    pub fn with_sprite(self, texture: TextureId) -> Self {
+        let mut x = self;
+        x.shape = ParticleShape::Sprite(texture);
+        x
+    }
+
+    pub fn with_blend(self, blend: BlendMode) -> Self {
+        let mut x = self;
+        x.blend = blend;
+        x
+    }
+
+    pub fn with_layer(self, layer: i32) -> Self {
+        let mut x = self;
+        x.layer = layer;
+        x
+    }
+
+    pub fn with_max_particles(self, max: usize) -> Self {
+        let mut x = self;
+        x.max_particles = max;
+        x
+    }
+
+    // Same seed gives the same particles every run
+    pub fn with_seed(self, seed: u64) -> Self {
+        let mut x = self;
+        x.rng = seed.max(1);
+        x
+    }
+
+    // Spawns this many particles on the next update regardless of the spawn rate
+    pub fn burst(&mut self, count: u32) {
+        self.pending_burst += count;
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    // xorshift64*, plenty for particles and keeps things deterministic
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        (self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn random_range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.random()
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.max_particles {
+            return;
+        }
+        let particle = Particle {
+            x: self.position.x as f32,
+            y: self.position.y as f32,
+            velocity_x: self.random_range(self.velocity_min.0, self.velocity_max.0),
+            velocity_y: self.random_range(self.velocity_min.1, self.velocity_max.1),
+            age: 0.0,
+            lifetime: self.random_range(self.lifetime.0, self.lifetime.1).max(0.001),
+        };
+        self.particles.push(particle);
+    }
+
+    fn room(&self) -> usize {
+        self.max_particles.saturating_sub(self.particles.len())
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        let burst = (std::mem::take(&mut self.pending_burst) as usize).min(self.room());
+        for _ in 0..burst {
+            self.spawn();
+        }
+
+        if self.emitting && self.spawn_rate > 0.0 {
+            self.spawn_timer += delta_time;
+            let interval = 1.0 / self.spawn_rate;
+            // Worked out in one go, taking the interval off one particle at a time never
+            // gets below it when the interval is too small to change the timer
+            let due = (self.spawn_timer / interval) as usize;
+            self.spawn_timer = if interval > 0.0 { self.spawn_timer % interval } else { 0.0 };
+            for _ in 0..due.min(self.room()) {
+                self.spawn();
+            }
+        }
+
+        let keep = (1.0 - self.drag * delta_time).max(0.0);
+        let gravity = self.gravity;
+        self.particles.retain_mut(|particle| {
+            particle.age += delta_time;
+            particle.velocity_x = (particle.velocity_x + gravity.0 * delta_time) * keep;
+            particle.velocity_y = (particle.velocity_y + gravity.1 * delta_time) * keep;
+            particle.x += particle.velocity_x * delta_time;
+            particle.y += particle.velocity_y * delta_time;
+            particle.age < particle.lifetime
+        });
+    }
+
+    pub fn draw(&self, engine_settings: &mut EngineSettings2D, camera: &Vec2) {
+        if self.particles.is_empty() {
+            return;
+        }
+        let texture = match self.shape {
+            ParticleShape::Sprite(texture) => texture,
+            ParticleShape::Rect => match engine_settings.white_texture() {
+                Ok(texture) => texture,
+                Err(e) => {
+                    eprintln!("Error: Could not draw particles - {}", e);
+                    return;
+                }
+            },
+        };
+
+        for particle in &self.particles {
+            let t = particle.age / particle.lifetime;
+            let size = (self.start_size + (self.end_size - self.start_size) * t).max(0.0);
+            let half = size / 2.0;
+            engine_settings.draw_sprite(
+                VisualSprite::new(
+                    Vec2::new((particle.x - half) as i32 - camera.x, (particle.y - half) as i32 - camera.y),
+                    Vec2::new(size as i32, size as i32),
+                    texture,
+                )
+                .with_tint(lerp_color(&self.start_color, &self.end_color, t))
+                .with_blend(self.blend)
+                .with_layer(self.layer),
+            );
+        }
+    }
+}
+
+fn lerp_color(from: &Color, to: &Color, t: f32) -> Color {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+    Color {
+        r: lerp(from.r, to.r),
+        g: lerp(from.g, to.g),
+        b: lerp(from.b, to.b),
+    }
+}
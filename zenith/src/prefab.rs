@@ -0,0 +1,207 @@
+// Named entity templates. A prefab is tags plus registered function names, and can
+// contain child prefabs that get spawned along with it. Prefab files use the same
+// formats as scenes and hold a map of prefab name -> prefab.
+use crate::scene::{parse_data, EntityFnNames, ScriptRegistry};
+use crate::{AssetPack, Entity, SceneFormat, TagValue, Vec2};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PrefabChild {
+    pub prefab: String,
+    #[serde(default)]
+    pub tags: BTreeMap<String, TagValue>, // Overrides, "location" is relative to the parent
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Prefab {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>, // Registered entity function names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub late_update: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destroy: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, TagValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PrefabChild>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tag(self, tag_name: &str, tag_value: TagValue) -> Self {
+        let mut x = self;
+        x.tags.insert(tag_name.to_string(), tag_value);
+        x
+    }
+
+    pub fn with_update_fn(self, name: &str) -> Self {
+        let mut x = self;
+        x.update = Some(name.to_string());
+        x
+    }
+
+    pub fn with_start_fn(self, name: &str) -> Self {
+        let mut x = self;
+        x.start = Some(name.to_string());
+        x
+    }
+
+    pub fn with_spawn_fn(self, name: &str) -> Self {
+        let mut x = self;
+        x.spawn = Some(name.to_string());
+        x
+    }
+
+    pub fn with_late_update_fn(self, name: &str) -> Self {
+        let mut x = self;
+        x.late_update = Some(name.to_string());
+        x
+    }
+
+    pub fn with_destroy_fn(self, name: &str) -> Self {
+        let mut x = self;
+        x.destroy = Some(name.to_string());
+        x
+    }
+
+    pub fn with_child(self, prefab: &str, overrides: &[(&str, TagValue)]) -> Self {
+        let mut x = self;
+        x.children.push(PrefabChild {
+            prefab: prefab.to_string(),
+            tags: overrides
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        });
+        x
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl Prefabs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registering a name again replaces the prefab
+    pub fn register(&mut self, name: &str, prefab: Prefab) {
+        self.prefabs.insert(name.to_string(), prefab);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Prefab> {
+        self.prefabs.remove(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.prefabs.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    // Adds every prefab in the file, returns how many there were
+    pub fn load_from(&mut self, pack: &AssetPack, path: &str) -> Result<usize, String> {
+        let format = SceneFormat::from_path(path)?;
+        let text = pack.read_to_string(path)?;
+        let prefabs: BTreeMap<String, Prefab> = parse_data(&text, format).map_err(|e| format!("{}: {}", path, e))?;
+        let count = prefabs.len();
+        self.prefabs.extend(prefabs);
+        Ok(count)
+    }
+
+    // The entity for the prefab followed by all of its children (and theirs), each child
+    // with the position of the entity it was spawned with in the list
+    pub fn instantiate(
+        &self,
+        name: &str,
+        overrides: &[(&str, TagValue)],
+        registry: &ScriptRegistry,
+    ) -> Result<Vec<(Entity, Option<usize>)>, String> {
+        let overrides: BTreeMap<String, TagValue> = overrides
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        let mut entities = Vec::new();
+        self.build(name, &overrides, None, registry, &mut Vec::new(), &mut entities)?;
+        Ok(entities)
+    }
+
+    fn build(
+        &self,
+        name: &str,
+        overrides: &BTreeMap<String, TagValue>,
+        parent: Option<usize>,
+        registry: &ScriptRegistry,
+        stack: &mut Vec<String>,
+        entities: &mut Vec<(Entity, Option<usize>)>,
+    ) -> Result<(), String> {
+        if stack.iter().any(|outer| outer == name) {
+            return Err(format!("Prefab {} contains itself", name));
+        }
+        let Some(prefab) = self.prefabs.get(name) else {
+            return Err(format!("No prefab named {}", name));
+        };
+
+        let function = |name: &Option<String>| match name {
+            None => Ok(None),
+            Some(name) => registry
+                .get_entity_fn(name)
+                .map(Some)
+                .ok_or_else(|| format!("No entity function registered as {}", name)),
+        };
+        let mut entity = Entity::new();
+        entity.start_function = function(&prefab.start)?;
+        entity.update_function = function(&prefab.update)?;
+        entity.spawn_function = function(&prefab.spawn)?;
+        entity.late_update_function = function(&prefab.late_update)?;
+        entity.destroy_function = function(&prefab.destroy)?;
+        entity.function_names = EntityFnNames {
+            start: prefab.start.clone(),
+            update: prefab.update.clone(),
+            spawn: prefab.spawn.clone(),
+            late_update: prefab.late_update.clone(),
+            destroy: prefab.destroy.clone(),
+        };
+        for (tag, value) in prefab.tags.iter().chain(overrides) {
+            entity.set_tag(tag, value.clone());
+        }
+
+        if let Some((parent, _)) = parent.and_then(|parent| entities.get(parent)) {
+            let parent_location = parent.get_tag("location").and_then(|tag| tag.extract_vec2());
+            let location = entity.get_tag("location").and_then(|tag| tag.extract_vec2());
+            if let (Some(parent_location), Some(location)) = (parent_location, location) {
+                entity.set_tag(
+                    "location",
+                    TagValue::Vec2(Vec2::new(parent_location.x + location.x, parent_location.y + location.y)),
+                );
+                // The hierarchy keeps it there relative to the parent from now on
+                entity.set_tag("local_location", TagValue::Vec2(location));
+            }
+        }
+
+        stack.push(name.to_string());
+        let index = entities.len();
+        entities.push((entity, parent));
+        for child in &prefab.children {
+            self.build(&child.prefab, &child.tags, Some(index), registry, stack, entities)?;
+        }
+        stack.pop();
+        Ok(())
+    }
+}
@@ -2,18 +2,21 @@ use crate::sdl2_renderer::{self};
 use crate::*;
 
 use sdl2::keyboard::Keycode;
-use sdl2::render::Canvas;
+use sdl2::mouse::MouseButton;
+use sdl2::render::{Canvas, Texture, TextureCreator};
 
-use sdl2::event::Event;
-use sdl2::video::Window;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::video::{Window, WindowContext};
 use sdl2::Sdl;
 
 
 
+#[derive(Clone, Debug)]
 pub struct VisualRect {
     pub location: Vec2,
     pub size: Vec2,
     pub color: Color,
+    pub layer: i32,
 }
 
 impl VisualRect {
@@ -22,6 +25,318 @@ impl VisualRect {
             location: location,
             size: size,
             color: color,
+            layer: 0,
+        }
+    }
+
+    pub fn with_layer(self, layer: i32) -> Self {
+        let mut x = self;
+        x.layer = layer;
+        x
+    }
+}
+
+// Slot and generation like EntityId, so an id kept after its texture was destroyed doesn't
+// draw whatever texture is made in the same slot next
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureId {
+    index: usize,
+    generation: u32,
+}
+
+struct TextureSlot {
+    generation: u32,
+    texture: Option<Texture>,
+    used: bool, // Still true while the texture is taken out to be drawn into
+}
+
+#[derive(Default)]
+pub struct TextureSlots {
+    slots: Vec<TextureSlot>,
+}
+
+impl TextureSlots {
+    pub(crate) fn insert(&mut self, texture: Texture) -> TextureId {
+        let index = match self.slots.iter().position(|slot| !slot.used) {
+            Some(index) => index,
+            None => {
+                self.slots.push(TextureSlot {
+                    generation: 0,
+                    texture: None,
+                    used: false,
+                });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.texture = Some(texture);
+        slot.used = true;
+        TextureId {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    fn slot(&self, id: TextureId) -> Option<&TextureSlot> {
+        self.slots.get(id.index).filter(|slot| slot.used && slot.generation == id.generation)
+    }
+
+    fn slot_mut(&mut self, id: TextureId) -> Option<&mut TextureSlot> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.used && slot.generation == id.generation)
+    }
+
+    pub(crate) fn get(&self, id: TextureId) -> Option<&Texture> {
+        self.slot(id)?.texture.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, id: TextureId) -> Option<&mut Texture> {
+        self.slot_mut(id)?.texture.as_mut()
+    }
+
+    // Borrows the texture out of its slot, the slot stays taken until it's put back
+    pub(crate) fn take(&mut self, id: TextureId) -> Option<Texture> {
+        self.slot_mut(id)?.texture.take()
+    }
+
+    pub(crate) fn put_back(&mut self, id: TextureId, texture: Texture) {
+        if let Some(slot) = self.slot_mut(id) {
+            slot.texture = Some(texture);
+        }
+    }
+
+    // Trades what two ids point at, false if either of them is gone
+    pub(crate) fn swap(&mut self, a: TextureId, b: TextureId) -> bool {
+        if a == b || self.get(a).is_none() || self.get(b).is_none() {
+            return false;
+        }
+        let (Some(first), Some(second)) = (self.take(a), self.take(b)) else {
+            return false;
+        };
+        self.put_back(a, second);
+        self.put_back(b, first);
+        true
+    }
+
+    // Frees the slot, ids for it stop working
+    pub(crate) fn remove(&mut self, id: TextureId) -> Option<Texture> {
+        let slot = self.slot_mut(id)?;
+        slot.used = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.texture.take()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    None,
+    Blend,
+    Add,
+    Mod,
+}
+
+#[derive(Clone, Debug)]
+pub struct VisualSprite {
+    pub location: Vec2,
+    pub size: Vec2,
+    pub texture: TextureId,
+    pub source: Option<(Vec2, Vec2)>, // (position, size) in texture pixels, None = whole texture
+    pub tint: Color,
+    pub blend: BlendMode,
+    pub layer: i32,
+}
+
+impl VisualSprite {
+    pub fn new(location: Vec2, size: Vec2, texture: TextureId) -> Self {
+        VisualSprite {
+            location,
+            size,
+            texture,
+            source: None,
+            tint: Color::white(),
+            blend: BlendMode::Blend,
+            layer: 0,
+        }
+    }
+
+    pub fn with_source(self, position: Vec2, size: Vec2) -> Self {
+        let mut x = self;
+        x.source = Some((position, size));
+        x
+    }
+
+    pub fn with_tint(self, tint: Color) -> Self {
+        let mut x = self;
+        x.tint = tint;
+        x
+    }
+
+    pub fn with_blend(self, blend: BlendMode) -> Self {
+        let mut x = self;
+        x.blend = blend;
+        x
+    }
+
+    pub fn with_layer(self, layer: i32) -> Self {
+        let mut x = self;
+        x.layer = layer;
+        x
+    }
+}
+
+enum DrawCommand {
+    Rect(VisualRect),
+    Sprite(VisualSprite),
+}
+
+impl DrawCommand {
+    // Layer first so draw order between layers is kept, then everything
+    // sharing the same state ends up next to each other
+    fn sort_key(&self) -> (i32, u8, usize, u8, u8, u8, u8) {
+        match self {
+            DrawCommand::Rect(rect) => (rect.layer, 0, 0, 0, rect.color.r, rect.color.g, rect.color.b),
+            DrawCommand::Sprite(sprite) => (sprite.layer, 1, sprite.texture.index, sprite.blend as u8, 0, 0, 0),
+        }
+    }
+}
+
+// A run of draw calls that can be submitted to the backend in one go
+pub enum Batch {
+    Rects { color: Color, rects: Vec<(i32, i32, u32, u32)> },
+    Sprites { texture: TextureId, blend: BlendMode, sprites: Vec<VisualSprite> },
+}
+
+// Draw calls are queued here every frame and handed to the backend in batches
+// when the display is updated. Within a layer the order between different
+// colors/textures is not guaranteed, use layers if something has to be on top.
+pub struct RenderQueue {
+    commands: Vec<DrawCommand>,
+}
+
+impl Default for RenderQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderQueue {
+    pub fn new() -> Self {
+        RenderQueue {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn push_rect(&mut self, rect: VisualRect) {
+        // Negative sizes are clamped to nothing instead of complaining every frame
+        if rect.size.x <= 0 || rect.size.y <= 0 {
+            return;
+        }
+        self.commands.push(DrawCommand::Rect(rect));
+    }
+
+    pub fn push_sprite(&mut self, sprite: VisualSprite) {
+        if sprite.size.x <= 0 || sprite.size.y <= 0 {
+            return;
+        }
+        self.commands.push(DrawCommand::Sprite(sprite));
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn take_batches(&mut self) -> Vec<Batch> {
+        self.commands.sort_by_key(|command| command.sort_key());
+
+        let mut batches: Vec<Batch> = Vec::new();
+        for command in self.commands.drain(..) {
+            match (command, batches.last_mut()) {
+                (DrawCommand::Rect(rect), Some(Batch::Rects { color, rects })) if *color == rect.color => {
+                    rects.push(rect_bounds(&rect));
+                }
+                (DrawCommand::Sprite(sprite), Some(Batch::Sprites { texture, blend, sprites }))
+                    if *texture == sprite.texture && *blend == sprite.blend =>
+                {
+                    sprites.push(sprite);
+                }
+                (DrawCommand::Rect(rect), _) => batches.push(Batch::Rects {
+                    color: rect.color.clone(),
+                    rects: vec![rect_bounds(&rect)],
+                }),
+                (DrawCommand::Sprite(sprite), _) => batches.push(Batch::Sprites {
+                    texture: sprite.texture,
+                    blend: sprite.blend,
+                    sprites: vec![sprite],
+                }),
+            }
+        }
+        batches
+    }
+}
+
+fn rect_bounds(rect: &VisualRect) -> (i32, i32, u32, u32) {
+    (rect.location.x, rect.location.y, rect.size.x as u32, rect.size.y as u32)
+}
+
+// CPU-side effects run in order over the offscreen target every frame
+#[derive(Clone, Debug)]
+pub enum PostEffect {
+    Fade { amount: f32 },                      // 0.0 = untouched, 1.0 = black
+    Tint { color: Color, strength: f32 },      // 0.0 = untouched, 1.0 = solid color
+    Scanlines { spacing: u32, darkness: f32 }, // Darkens every `spacing`th row
+    Custom(fn(&mut [u8], u32, u32)),           // RGBA pixels, width, height
+}
+
+pub fn apply_post_effects(pixels: &mut [u8], width: u32, height: u32, effects: &[PostEffect]) {
+    // A minimised window has nothing to work on
+    if width == 0 || height == 0 {
+        return;
+    }
+    for effect in effects {
+        match effect {
+            PostEffect::Fade { amount } => {
+                let keep = 1.0 - amount.clamp(0.0, 1.0);
+                for pixel in pixels.chunks_exact_mut(4) {
+                    for channel in &mut pixel[..3] {
+                        *channel = (*channel as f32 * keep) as u8;
+                    }
+                }
+            }
+            PostEffect::Tint { color, strength } => {
+                let strength = strength.clamp(0.0, 1.0);
+                let tint = [color.r as f32, color.g as f32, color.b as f32];
+                for pixel in pixels.chunks_exact_mut(4) {
+                    for (channel, target) in pixel[..3].iter_mut().zip(tint) {
+                        *channel = (*channel as f32 + (target - *channel as f32) * strength) as u8;
+                    }
+                }
+            }
+            PostEffect::Scanlines { spacing, darkness } => {
+                let keep = 1.0 - darkness.clamp(0.0, 1.0);
+                let Some(row_length) = (width as usize).checked_mul(4) else {
+                    continue;
+                };
+                for (y, row) in pixels.chunks_exact_mut(row_length).enumerate() {
+                    if *spacing == 0 || !(y as u32).is_multiple_of(*spacing) {
+                        continue;
+                    }
+                    for pixel in row.chunks_exact_mut(4) {
+                        for channel in &mut pixel[..3] {
+                            *channel = (*channel as f32 * keep) as u8;
+                        }
+                    }
+                }
+            }
+            PostEffect::Custom(effect) => effect(pixels, width, height),
         }
     }
 }
@@ -33,6 +348,79 @@ pub enum RenderingEnvironment {
 pub struct Sdl2Env {
     pub canvas: Canvas<Window>,
     pub sdl_context: Sdl,
+    pub texture_creator: TextureCreator<WindowContext>,
+    pub textures: TextureSlots,
+    pub queue: RenderQueue,
+    pub overlay: RenderQueue,            // Drawn straight to the window after the scene target
+    pub scene_target: Option<TextureId>, // Offscreen texture the scene gets rendered into
+    pub effects_target: bool,            // The scene target was only made for post effects
+    pub effect_output: Option<Texture>,  // Streaming texture post effects are written to
+    pub scaling: ScalingMode,            // How the scene target is fitted into the window
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    Stretch, // Fill the whole window, aspect ratio is ignored
+    Fit,     // Biggest size that keeps the aspect ratio, black bars on the sides
+    Integer, // Like Fit but only whole multiples so pixel art stays crisp
+}
+
+// Where the logical screen ends up inside the window as (x, y, width, height)
+pub fn letterbox(window: (u32, u32), logical: (u32, u32), scaling: ScalingMode) -> (i32, i32, u32, u32) {
+    if logical.0 == 0 || logical.1 == 0 {
+        return (0, 0, window.0, window.1);
+    }
+    let scale_x = window.0 as f32 / logical.0 as f32;
+    let scale_y = window.1 as f32 / logical.1 as f32;
+    let scale = match scaling {
+        ScalingMode::Stretch => return (0, 0, window.0, window.1),
+        ScalingMode::Fit => scale_x.min(scale_y),
+        ScalingMode::Integer => scale_x.min(scale_y).floor().max(1.0),
+    };
+    let width = (logical.0 as f32 * scale) as u32;
+    let height = (logical.1 as f32 * scale) as u32;
+    (
+        (window.0 as i32 - width as i32) / 2,
+        (window.1 as i32 - height as i32) / 2,
+        width,
+        height,
+    )
+}
+
+// Maps a point in window pixels back to logical coordinates, can end up outside
+// the logical screen when the point is on one of the black bars
+pub fn window_to_logical(
+    point: (i32, i32),
+    window: (u32, u32),
+    logical: (u32, u32),
+    scaling: ScalingMode,
+) -> (i32, i32) {
+    let (x, y, width, height) = letterbox(window, logical, scaling);
+    if width == 0 || height == 0 {
+        return point;
+    }
+    (
+        ((point.0 - x) as f32 * logical.0 as f32 / width as f32).floor() as i32,
+        ((point.1 - y) as f32 * logical.1 as f32 / height as f32).floor() as i32,
+    )
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Default)]
+pub struct Mouse {
+    pub x: i32, // Logical coordinates, same space things are drawn in
+    pub y: i32,
+    pub window_x: i32, // Raw window coordinates
+    pub window_y: i32,
+    pub LEFT: bool,
+    pub RIGHT: bool,
+    pub MIDDLE: bool,
+}
+
+impl Mouse {
+    pub fn new() -> Self {
+        Mouse::default()
+    }
 }
 #[allow(non_snake_case)]
 #[derive(Debug)]
@@ -127,6 +515,59 @@ impl Keys {
     }
 }
 
+impl Keys {
+    // Uses the same names as all_pressed_str, plus "quit" and "0" to "9".
+    // Returns false if the name isn't a known key.
+    pub fn set(&mut self, name: &str, pressed: bool) -> bool {
+        let key = match name {
+            "escape" => &mut self.ESCAPE,
+            "space" => &mut self.SPACE,
+            "lshift" => &mut self.LSHIFT,
+            "rshift" => &mut self.RSHIFT,
+            "quit" => &mut self.QUIT,
+            "a" => &mut self.A,
+            "b" => &mut self.B,
+            "c" => &mut self.C,
+            "d" => &mut self.D,
+            "e" => &mut self.E,
+            "f" => &mut self.F,
+            "g" => &mut self.G,
+            "h" => &mut self.H,
+            "i" => &mut self.I,
+            "j" => &mut self.J,
+            "k" => &mut self.K,
+            "l" => &mut self.L,
+            "m" => &mut self.M,
+            "n" => &mut self.N,
+            "o" => &mut self.O,
+            "p" => &mut self.P,
+            "q" => &mut self.Q,
+            "r" => &mut self.R,
+            "s" => &mut self.S,
+            "t" => &mut self.T,
+            "u" => &mut self.U,
+            "v" => &mut self.V,
+            "w" => &mut self.W,
+            "x" => &mut self.X,
+            "y" => &mut self.Y,
+            "z" => &mut self.Z,
+            "0" => &mut self.NUM_0,
+            "1" => &mut self.NUM_1,
+            "2" => &mut self.NUM_2,
+            "3" => &mut self.NUM_3,
+            "4" => &mut self.NUM_4,
+            "5" => &mut self.NUM_5,
+            "6" => &mut self.NUM_6,
+            "7" => &mut self.NUM_7,
+            "8" => &mut self.NUM_8,
+            "9" => &mut self.NUM_9,
+            _ => return false,
+        };
+        *key = pressed;
+        true
+    }
+}
+
 impl Keys {
     pub fn all_pressed_str(&self) -> Vec<&str> {
         let mut pressed: Vec<&str> = vec![];
@@ -227,9 +668,41 @@ impl Keys {
 pub fn update_keystrokes(instance: &mut Instance2D) {
     match &instance.engine_settings.engine_env {
         RenderingEnvironment::Sdl2(sdl2_env) => {
+            let window_size = sdl2_env.canvas.window().size();
+            let logical_size = sdl2_env
+                .scene_target
+                .and_then(|target| sdl2_env.textures.get(target))
+                .map(|texture| (texture.query().width, texture.query().height))
+                .unwrap_or(window_size);
+
             for event in sdl2_env.sdl_context.event_pump().unwrap().poll_iter() {
                 match event {
                     Event::Quit { .. } => instance.engine_settings.keys.QUIT = true,
+                    Event::Window {
+                        win_event: WindowEvent::Resized(width, height),
+                        ..
+                    } => instance.environment.mut_events().send(WindowResized {
+                        width: width as u32,
+                        height: height as u32,
+                    }),
+                    Event::MouseMotion { x, y, .. } => {
+                        let mouse = &mut instance.engine_settings.mouse;
+                        (mouse.window_x, mouse.window_y) = (x, y);
+                        (mouse.x, mouse.y) =
+                            window_to_logical((x, y), window_size, logical_size, sdl2_env.scaling);
+                    }
+                    Event::MouseButtonDown { mouse_btn, .. } => match mouse_btn {
+                        MouseButton::Left => instance.engine_settings.mouse.LEFT = true,
+                        MouseButton::Right => instance.engine_settings.mouse.RIGHT = true,
+                        MouseButton::Middle => instance.engine_settings.mouse.MIDDLE = true,
+                        _ => {}
+                    },
+                    Event::MouseButtonUp { mouse_btn, .. } => match mouse_btn {
+                        MouseButton::Left => instance.engine_settings.mouse.LEFT = false,
+                        MouseButton::Right => instance.engine_settings.mouse.RIGHT = false,
+                        MouseButton::Middle => instance.engine_settings.mouse.MIDDLE = false,
+                        _ => {}
+                    },
                     Event::KeyDown {
                         keycode: Some(key), ..
                     } => {
@@ -316,8 +789,9 @@ pub fn update_keystrokes(instance: &mut Instance2D) {
 #[allow(non_snake_case)]
 pub fn new_2D_window(engine: RenderingEngine2D, screen: Screen) -> RenderingEnvironment {
     match engine {
-        RenderingEngine2D::Sdl2 => {
-            return RenderingEnvironment::Sdl2(sdl2_renderer::new_window(screen));
+        RenderingEngine2D::Sdl2 => RenderingEnvironment::Sdl2(sdl2_renderer::new_window(screen)),
+        RenderingEngine2D::Sdl2Headless => {
+            RenderingEnvironment::Sdl2(sdl2_renderer::new_headless_window(screen))
         }
     }
 }
@@ -328,8 +802,107 @@ pub fn draw_rect(rect: VisualRect, env: &mut RenderingEnvironment) {
     }
 }
 
-pub fn update_display(env: &mut RenderingEnvironment) {
+pub fn draw_sprite(sprite: VisualSprite, env: &mut RenderingEnvironment) {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sdl2_renderer::sdl2_draw_sprite(sld2_env, sprite),
+    }
+}
+
+// Pixels are RGBA, 4 bytes each, row by row
+pub fn create_texture(
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    env: &mut RenderingEnvironment,
+) -> Result<TextureId, String> {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => {
+            sdl2_renderer::sdl2_create_texture(sld2_env, width, height, pixels)
+        }
+    }
+}
+
+pub fn destroy_texture(texture: TextureId, env: &mut RenderingEnvironment) {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sdl2_renderer::sdl2_destroy_texture(sld2_env, texture),
+    }
+}
+
+pub(crate) fn swap_textures(a: TextureId, b: TextureId, env: &mut RenderingEnvironment) -> bool {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sld2_env.textures.swap(a, b),
+    }
+}
+
+pub fn draw_overlay_rect(rect: VisualRect, env: &mut RenderingEnvironment) {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sld2_env.overlay.push_rect(rect),
+    }
+}
+
+pub fn draw_overlay_sprite(sprite: VisualSprite, env: &mut RenderingEnvironment) {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sld2_env.overlay.push_sprite(sprite),
+    }
+}
+
+// None goes back to drawing straight into the window
+pub fn set_render_target(size: Option<(u32, u32)>, env: &mut RenderingEnvironment) -> Result<(), String> {
     match env {
-        RenderingEnvironment::Sdl2(sld2_env) => sdl2_renderer::sdl2_update_display(sld2_env),
+        RenderingEnvironment::Sdl2(sld2_env) => sdl2_renderer::sdl2_set_render_target(sld2_env, size),
+    }
+}
+
+pub fn render_target(env: &RenderingEnvironment) -> Option<TextureId> {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sld2_env.scene_target,
+    }
+}
+
+pub fn view_size(env: &RenderingEnvironment) -> (u32, u32) {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sdl2_renderer::sdl2_view_size(sld2_env),
+    }
+}
+
+// Pushes the window settings, logical resolution and scaling from Screen to the backend
+pub fn apply_screen(screen: &Screen, env: &mut RenderingEnvironment) -> Result<(), String> {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sdl2_renderer::sdl2_apply_screen(sld2_env, screen),
+    }
+}
+
+// With capture set the finished frame is read back right before it is shown
+pub fn update_display(
+    env: &mut RenderingEnvironment,
+    effects: &[PostEffect],
+    capture: bool,
+) -> Option<Result<FrameBuffer, String>> {
+    match env {
+        RenderingEnvironment::Sdl2(sld2_env) => sdl2_renderer::sdl2_update_display(sld2_env, effects, capture),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effects_on_an_empty_frame_do_nothing() {
+        fn fill(pixels: &mut [u8], _: u32, _: u32) {
+            pixels.fill(255);
+        }
+        let effects = [
+            PostEffect::Scanlines { spacing: 2, darkness: 0.5 },
+            PostEffect::Custom(fill),
+        ];
+        let mut pixels = [10; 8];
+        apply_post_effects(&mut pixels, 0, 2, &effects);
+        apply_post_effects(&mut pixels, 2, 0, &effects);
+        assert_eq!(pixels, [10; 8]);
+
+        // One row of two pixels, the first row gets darkened
+        apply_post_effects(&mut pixels, 2, 1, &effects[..1]);
+        assert_eq!(pixels, [5, 5, 5, 10, 5, 5, 5, 10]);
     }
 }
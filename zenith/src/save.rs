@@ -0,0 +1,143 @@
+// Save games: the environment's entities, scripts, tilemaps and resources written as JSON
+// with a version number. Older saves are brought up to date by migrations that edit the
+// raw JSON one version at a time before it gets read.
+use crate::{Environment, Scene, TagValue, Tilemap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Turns the data of a save from one version into the next
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    scene: Scene,
+    #[serde(default)]
+    tilemaps: Vec<Tilemap>,
+    #[serde(default)]
+    resources: BTreeMap<String, TagValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    data: Value,
+}
+
+#[derive(Clone)]
+pub struct Saves {
+    version: u32,
+    migrations: Vec<(u32, Migration)>, // Version the migration upgrades from
+}
+
+impl Default for Saves {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Saves {
+    // `version` is what new saves get written as, bump it whenever the save layout changes
+    pub fn new(version: u32) -> Self {
+        Saves {
+            version,
+            migrations: Vec::new(),
+        }
+    }
+
+    // Upgrades saves of version `from` to `from + 1`
+    pub fn with_migration(self, from: u32, migration: Migration) -> Self {
+        let mut x = self;
+        x.migrations.retain(|(existing, _)| *existing != from);
+        x.migrations.push((from, migration));
+        x
+    }
+
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn to_json(&self, environment: &Environment) -> Result<String, String> {
+        let data = SaveData {
+            scene: environment.to_scene()?,
+            tilemaps: environment.list_tilemaps().clone(),
+            resources: environment
+                .list_resources()
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        };
+        let file = SaveFile {
+            version: self.version,
+            data: serde_json::to_value(data).map_err(|e| e.to_string())?,
+        };
+        serde_json::to_string_pretty(&file).map_err(|e| e.to_string())
+    }
+
+    // Writes to a temporary file next to `path` and renames it over the old save once
+    // everything is on disk, so a crash halfway leaves the previous save untouched
+    pub fn save(&self, environment: &Environment, path: &str) -> Result<(), String> {
+        let json = self.to_json(environment)?;
+        write_atomic(Path::new(path), json.as_bytes()).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Replaces the environment's entities, scripts, tilemaps and resources. Returns the
+    // version the save was written as. Nothing changes if the save can't be read.
+    // Tileset textures aren't part of the save, Instance2D::load_game loads them again.
+    pub fn load(&self, environment: &mut Environment, path: &str) -> Result<u32, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.load_json(environment, &text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn load_json(&self, environment: &mut Environment, json: &str) -> Result<u32, String> {
+        let file: SaveFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if file.version > self.version {
+            return Err(format!(
+                "Save is version {} but this game only knows up to version {}",
+                file.version, self.version
+            ));
+        }
+
+        let mut data = file.data;
+        for version in file.version..self.version {
+            let Some((_, migration)) = self.migrations.iter().find(|(from, _)| *from == version) else {
+                return Err(format!("No migration from save version {} to {}", version, version + 1));
+            };
+            migration(&mut data).map_err(|e| format!("Migration from version {} failed - {}", version, e))?;
+        }
+
+        let data: SaveData = serde_json::from_value(data).map_err(|e| e.to_string())?;
+        environment.load_scene(&data.scene)?;
+        *environment.mut_tilemaps() = data.tilemaps;
+        environment.clear_resources();
+        for (name, value) in data.resources {
+            environment.set_resource(&name, value);
+        }
+        Ok(file.version)
+    }
+}
+
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut temporary = PathBuf::from(path);
+    let mut name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    name.push(".tmp");
+    temporary.set_file_name(name);
+
+    let mut file = File::create(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temporary, path)?;
+
+    // Makes the rename itself stick, only possible on unix
+    #[cfg(unix)]
+    if let Some(directory) = path.parent() {
+        let directory = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+        File::open(directory)?.sync_all()?;
+    }
+    Ok(())
+}
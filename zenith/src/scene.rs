@@ -0,0 +1,300 @@
+// Scenes are entities, their tags and the scripts that run on them written out as
+// RON, JSON or TOML. Functions can't be saved so they are stored by the name they
+// were registered under and looked up again when the scene is loaded.
+use crate::entities::EntityStore;
+use crate::{Asset, AssetPack, EngineSettings2D, Entity, Instance2D, ScriptStage, TagValue};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneFormat {
+    Ron,
+    Json,
+    Toml,
+}
+
+impl SceneFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") => Ok(SceneFormat::Ron),
+            Some("json") => Ok(SceneFormat::Json),
+            Some("toml") => Ok(SceneFormat::Toml),
+            _ => Err(format!("{}: Unknown scene format, expected .ron, .json or .toml", path.display())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneEntity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>, // Registered entity function names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub late_update: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destroy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>, // Position of the parent in Scene::entities
+    #[serde(default)]
+    pub tags: BTreeMap<String, TagValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    #[serde(default)]
+    pub start_scripts: Vec<String>, // Registered script names, built-in scripts are left out
+    #[serde(default)]
+    pub update_scripts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixed_update_scripts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub late_update_scripts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shutdown_scripts: Vec<String>,
+    #[serde(default)]
+    pub entities: Vec<SceneEntity>,
+}
+
+// Shared with the other data files that come in the scene formats
+pub(crate) fn parse_data<T: DeserializeOwned>(text: &str, format: SceneFormat) -> Result<T, String> {
+    match format {
+        SceneFormat::Ron => ron::from_str(text).map_err(|e| e.to_string()),
+        SceneFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+        SceneFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+    }
+}
+
+impl Scene {
+    pub fn get_scripts(&self, stage: ScriptStage) -> &Vec<String> {
+        match stage {
+            ScriptStage::Start => &self.start_scripts,
+            ScriptStage::FixedUpdate => &self.fixed_update_scripts,
+            ScriptStage::Update => &self.update_scripts,
+            ScriptStage::LateUpdate => &self.late_update_scripts,
+            ScriptStage::Shutdown => &self.shutdown_scripts,
+        }
+    }
+
+    pub fn mut_scripts(&mut self, stage: ScriptStage) -> &mut Vec<String> {
+        match stage {
+            ScriptStage::Start => &mut self.start_scripts,
+            ScriptStage::FixedUpdate => &mut self.fixed_update_scripts,
+            ScriptStage::Update => &mut self.update_scripts,
+            ScriptStage::LateUpdate => &mut self.late_update_scripts,
+            ScriptStage::Shutdown => &mut self.shutdown_scripts,
+        }
+    }
+
+    pub fn parse(text: &str, format: SceneFormat) -> Result<Scene, String> {
+        parse_data(text, format)
+    }
+
+    pub fn to_text(&self, format: SceneFormat) -> Result<String, String> {
+        match format {
+            SceneFormat::Ron => {
+                // Lets hand written scenes say update: "name" instead of update: Some("name")
+                let config = ron::ser::PrettyConfig::default().extensions(ron::extensions::Extensions::IMPLICIT_SOME);
+                ron::ser::to_string_pretty(self, config).map_err(|e| e.to_string())
+            }
+            SceneFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+            SceneFormat::Toml => toml::to_string_pretty(self).map_err(|e| e.to_string()),
+        }
+    }
+
+    // Format by extension
+    pub fn load(path: &str) -> Result<Scene, String> {
+        Self::load_from(&AssetPack::default(), path)
+    }
+
+    pub fn load_from(pack: &AssetPack, path: &str) -> Result<Scene, String> {
+        let format = SceneFormat::from_path(path)?;
+        let text = pack.read_to_string(path)?;
+        Self::parse(&text, format).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = self.to_text(SceneFormat::from_path(path)?)?;
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+impl Asset for Scene {
+    fn load(path: &Path, pack: &AssetPack, _engine_settings: &mut EngineSettings2D) -> Result<Self, String> {
+        Scene::load_from(pack, &path.to_string_lossy())
+    }
+}
+
+pub(crate) type Script = fn(&mut Instance2D);
+pub(crate) type EntityFn = fn(&mut Entity);
+
+// The registered names of an entity's functions, kept on the entity because a function
+// pointer can't be reliably turned back into the name it was registered under
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct EntityFnNames {
+    pub(crate) start: Option<String>,
+    pub(crate) update: Option<String>,
+    pub(crate) spawn: Option<String>,
+    pub(crate) late_update: Option<String>,
+    pub(crate) destroy: Option<String>,
+}
+
+// Name -> function lookups for everything a scene can refer to
+#[derive(Clone, Default)]
+pub struct ScriptRegistry {
+    scripts: Vec<(String, Script)>,
+    entity_functions: Vec<(String, EntityFn)>,
+    builtins: Vec<String>,
+}
+
+impl ScriptRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registering a name again replaces the function
+    pub fn register_script(&mut self, name: &str, script: fn(&mut Instance2D)) {
+        self.scripts.retain(|(existing, _)| existing != name);
+        self.scripts.push((name.to_string(), script));
+    }
+
+    pub(crate) fn register_builtin(&mut self, name: &str, script: fn(&mut Instance2D)) {
+        self.register_script(name, script);
+        self.builtins.push(name.to_string());
+    }
+
+    pub fn register_entity_fn(&mut self, name: &str, function: fn(&mut Entity)) {
+        self.entity_functions.retain(|(existing, _)| existing != name);
+        self.entity_functions.push((name.to_string(), function));
+    }
+
+    pub fn get_script(&self, name: &str) -> Option<fn(&mut Instance2D)> {
+        self.scripts.iter().find(|(existing, _)| existing == name).map(|(_, script)| *script)
+    }
+
+    pub fn get_entity_fn(&self, name: &str) -> Option<fn(&mut Entity)> {
+        self.entity_functions
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, function)| *function)
+    }
+
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.builtins.iter().any(|builtin| builtin == name)
+    }
+}
+
+pub(crate) fn entity_to_scene(entity: &Entity, registry: &ScriptRegistry) -> Result<SceneEntity, String> {
+    let name_of = |function: Option<EntityFn>, name: &Option<String>| -> Result<Option<String>, String> {
+        match (function, name) {
+            (None, _) => Ok(None),
+            (Some(_), Some(name)) if registry.get_entity_fn(name).is_some() => Ok(Some(name.clone())),
+            (Some(_), Some(name)) => Err(format!(
+                "Entity {} has a function named {} that was never registered with register_entity_fn",
+                entity.get_name(),
+                name
+            )),
+            (Some(_), None) => Err(format!(
+                "Entity {} has a function without a name, add it with one of the with_named_ functions",
+                entity.get_name()
+            )),
+        }
+    };
+    let names = &entity.function_names;
+    Ok(SceneEntity {
+        start: name_of(entity.start_function, &names.start)?,
+        update: name_of(entity.update_function, &names.update)?,
+        spawn: name_of(entity.spawn_function, &names.spawn)?,
+        late_update: name_of(entity.late_update_function, &names.late_update)?,
+        destroy: name_of(entity.destroy_function, &names.destroy)?,
+        parent: None,
+        tags: entity.tags.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+    })
+}
+
+pub(crate) fn entity_from_scene(entity: &SceneEntity, registry: &ScriptRegistry) -> Result<Entity, String> {
+    let lookup = |name: &Option<String>| -> Result<Option<EntityFn>, String> {
+        match name {
+            None => Ok(None),
+            Some(name) => registry
+                .get_entity_fn(name)
+                .map(Some)
+                .ok_or_else(|| format!("No entity function registered as {}", name)),
+        }
+    };
+    let mut result = Entity::new();
+    result.start_function = lookup(&entity.start)?;
+    result.update_function = lookup(&entity.update)?;
+    result.spawn_function = lookup(&entity.spawn)?;
+    result.late_update_function = lookup(&entity.late_update)?;
+    result.destroy_function = lookup(&entity.destroy)?;
+    result.function_names = EntityFnNames {
+        start: entity.start.clone(),
+        update: entity.update.clone(),
+        spawn: entity.spawn.clone(),
+        late_update: entity.late_update.clone(),
+        destroy: entity.destroy.clone(),
+    };
+    result.tags = entity.tags.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+    Ok(result)
+}
+
+// Parents are written as positions in the list since ids only mean something while the
+// game is running
+pub(crate) fn entities_to_scene(entities: &EntityStore, registry: &ScriptRegistry) -> Result<Vec<SceneEntity>, String> {
+    entities
+        .as_slice()
+        .iter()
+        .map(|entity| {
+            let mut result = entity_to_scene(entity, registry)?;
+            result.parent = entity.parent.and_then(|parent| entities.position(parent));
+            Ok(result)
+        })
+        .collect()
+}
+
+pub(crate) fn entities_from_scene(
+    entities: &[SceneEntity],
+    registry: &ScriptRegistry,
+) -> Result<Vec<(Entity, Option<usize>)>, String> {
+    let mut result = Vec::with_capacity(entities.len());
+    for entity in entities {
+        if entity.parent.is_some_and(|parent| parent >= entities.len()) {
+            return Err(format!("Entity {:?} has a parent that isn't in the scene", entity.tags.get("name")));
+        }
+        result.push(entity_from_scene(entity, registry)?);
+    }
+    let parents: Vec<Option<usize>> = entities.iter().map(|entity| entity.parent).collect();
+    Ok(link_parent_tags(result)
+        .into_iter()
+        .zip(parents)
+        .map(|((entity, from_tag), parent)| (entity, parent.or(from_tag)))
+        .collect())
+}
+
+// Scenes written before parents were ids name the parent in a "parent" tag, that gets
+// turned into the position of the first entity in the list with the name
+pub(crate) fn link_parent_tags(entities: Vec<Entity>) -> Vec<(Entity, Option<usize>)> {
+    let names: Vec<String> = entities.iter().map(|entity| entity.get_name()).collect();
+    entities
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut entity)| {
+            let parent = match entity.tags.remove("parent") {
+                Some(TagValue::String(parent)) => names.iter().position(|name| *name == parent).filter(|parent| *parent != index),
+                Some(other) => {
+                    entity.tags.insert("parent".to_string(), other);
+                    None
+                }
+                None => None,
+            };
+            (entity, parent)
+        })
+        .collect()
+}
@@ -0,0 +1,330 @@
+// Which scripts run when. Scripts are added to a stage, and inside a stage they run by
+// priority (lowest first) unless a before/after constraint says otherwise. Scripts with
+// the same priority and no constraints run in the order they were added.
+use crate::scene::Script;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScriptStage {
+    Start,       // Once, before the first frame
+    FixedUpdate, // Every EngineSettings2D::fixed_timestep seconds, can be several times a frame
+    Update,      // Every frame, before the entities are updated
+    LateUpdate,  // Every frame, after the entities are updated and moved with their parents
+    Shutdown,    // Once, after the last frame
+}
+
+impl ScriptStage {
+    pub const ALL: [ScriptStage; 5] = [
+        ScriptStage::Start,
+        ScriptStage::FixedUpdate,
+        ScriptStage::Update,
+        ScriptStage::LateUpdate,
+        ScriptStage::Shutdown,
+    ];
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScriptOrder {
+    pub priority: i32,
+    pub before: Vec<String>, // Names of scripts in the same stage, missing ones are ignored
+    pub after: Vec<String>,
+}
+
+impl ScriptOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_priority(self, priority: i32) -> Self {
+        let mut x = self;
+        x.priority = priority;
+        x
+    }
+
+    pub fn with_before(self, name: &str) -> Self {
+        let mut x = self;
+        x.before.push(name.to_string());
+        x
+    }
+
+    pub fn with_after(self, name: &str) -> Self {
+        let mut x = self;
+        x.after.push(name.to_string());
+        x
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ScheduledScript {
+    pub(crate) name: String,
+    pub(crate) script: Script,
+    pub(crate) stage: ScriptStage,
+    pub(crate) order: ScriptOrder,
+    pub(crate) enabled: bool,
+    added: usize,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Schedule {
+    scripts: Vec<ScheduledScript>, // Kept in run order
+    added: usize,
+}
+
+impl Schedule {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    // Adding a name that's already in the stage replaces it
+    pub(crate) fn add(&mut self, stage: ScriptStage, name: &str, script: Script, order: ScriptOrder) {
+        self.insert(stage, name, script, order);
+        self.sort();
+    }
+
+    // Sorts once at the end instead of after every script
+    pub(crate) fn add_all(&mut self, scripts: Vec<(ScriptStage, String, Script, ScriptOrder)>) {
+        for (stage, name, script, order) in scripts {
+            self.insert(stage, &name, script, order);
+        }
+        self.sort();
+    }
+
+    fn insert(&mut self, stage: ScriptStage, name: &str, script: Script, order: ScriptOrder) {
+        self.scripts.retain(|existing| existing.stage != stage || existing.name != name);
+        self.scripts.push(ScheduledScript {
+            name: name.to_string(),
+            script,
+            stage,
+            order,
+            enabled: true,
+            added: self.added,
+        });
+        self.added += 1;
+    }
+
+    pub(crate) fn set_order(&mut self, stage: ScriptStage, name: &str, order: ScriptOrder) -> bool {
+        let Some(existing) = self
+            .scripts
+            .iter_mut()
+            .find(|existing| existing.stage == stage && existing.name == name)
+        else {
+            return false;
+        };
+        existing.order = order;
+        self.sort();
+        true
+    }
+
+    pub(crate) fn get_order(&self, stage: ScriptStage, name: &str) -> Option<&ScriptOrder> {
+        self.scripts
+            .iter()
+            .find(|existing| existing.stage == stage && existing.name == name)
+            .map(|existing| &existing.order)
+    }
+
+    // In every stage, returns whether there was a script with the name
+    pub(crate) fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+        for script in self.scripts.iter_mut().filter(|script| script.name == name) {
+            script.enabled = enabled;
+            found = true;
+        }
+        found
+    }
+
+    pub(crate) fn is_enabled(&self, name: &str) -> bool {
+        self.scripts.iter().any(|script| script.name == name && script.enabled)
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        let before = self.scripts.len();
+        self.scripts.retain(|script| script.name != name);
+        before != self.scripts.len()
+    }
+
+    pub(crate) fn retain(&mut self, keep: impl FnMut(&ScheduledScript) -> bool) {
+        self.scripts.retain(keep);
+    }
+
+    pub(crate) fn in_stage(&self, stage: ScriptStage) -> impl Iterator<Item = &ScheduledScript> {
+        self.scripts.iter().filter(move |script| script.stage == stage)
+    }
+
+    // What the event loop runs, copied so scripts can change the schedule while it runs
+    pub(crate) fn enabled(&self, stage: ScriptStage) -> Vec<Script> {
+        self.in_stage(stage)
+            .filter(|script| script.enabled)
+            .map(|script| script.script)
+            .collect()
+    }
+
+    fn sort(&mut self) {
+        let mut sorted = Vec::with_capacity(self.scripts.len());
+        for stage in ScriptStage::ALL {
+            let scripts: Vec<ScheduledScript> = self.in_stage(stage).cloned().collect();
+            let (scripts, looped) = run_order(scripts);
+            for name in looped {
+                eprintln!(
+                    "Error: {:?} scripts have before/after constraints that loop - running {} anyway",
+                    stage, name
+                );
+            }
+            sorted.extend(scripts);
+        }
+        self.scripts = sorted;
+    }
+}
+
+// Before/after constraints first, then priority, then the order they were added. A loop in
+// the constraints is broken by priority, the scripts that had to be run early to break it
+// are returned so they can be reported.
+fn run_order(scripts: Vec<ScheduledScript>) -> (Vec<ScheduledScript>, Vec<String>) {
+    let count = scripts.len();
+    let position = |name: &String| scripts.iter().position(|script| &script.name == name);
+    let mut later: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut waiting_on = vec![0usize; count];
+    for (index, script) in scripts.iter().enumerate() {
+        for other in script.order.before.iter().filter_map(position) {
+            if other != index {
+                later[index].push(other);
+                waiting_on[other] += 1;
+            }
+        }
+        for other in script.order.after.iter().filter_map(position) {
+            if other != index {
+                later[other].push(index);
+                waiting_on[index] += 1;
+            }
+        }
+    }
+
+    let key = |index: &usize| (scripts[*index].order.priority, scripts[*index].added);
+    let mut done = vec![false; count];
+    let mut order = Vec::with_capacity(count);
+    let mut looped = Vec::new();
+    while order.len() < count {
+        let ready = (0..count).filter(|index| !done[*index] && waiting_on[*index] == 0).min_by_key(key);
+        let next = match ready {
+            Some(next) => next,
+            None => {
+                let next = (0..count).filter(|index| !done[*index]).min_by_key(key).unwrap_or(0);
+                looped.push(scripts[next].name.clone());
+                next
+            }
+        };
+        done[next] = true;
+        order.push(next);
+        for other in &later[next] {
+            waiting_on[*other] = waiting_on[*other].saturating_sub(1);
+        }
+    }
+
+    let mut scripts: Vec<Option<ScheduledScript>> = scripts.into_iter().map(Some).collect();
+    let scripts = order.into_iter().filter_map(|index| scripts[index].take()).collect();
+    (scripts, looped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instance2D;
+
+    fn nothing(_: &mut Instance2D) {}
+
+    fn scripts(orders: &[(&str, ScriptOrder)]) -> Vec<ScheduledScript> {
+        orders
+            .iter()
+            .enumerate()
+            .map(|(added, (name, order))| ScheduledScript {
+                name: name.to_string(),
+                script: nothing,
+                stage: ScriptStage::Update,
+                order: order.clone(),
+                enabled: true,
+                added,
+            })
+            .collect()
+    }
+
+    fn names(scripts: &[ScheduledScript]) -> Vec<&str> {
+        scripts.iter().map(|script| script.name.as_str()).collect()
+    }
+
+    fn stage_names(schedule: &Schedule, stage: ScriptStage) -> Vec<&str> {
+        schedule.in_stage(stage).map(|script| script.name.as_str()).collect()
+    }
+
+    #[test]
+    fn lower_priority_runs_first_then_the_order_they_were_added() {
+        let (order, looped) = run_order(scripts(&[
+            ("draw", ScriptOrder::new().with_priority(10)),
+            ("input", ScriptOrder::new().with_priority(-5)),
+            ("move", ScriptOrder::new()),
+            ("ai", ScriptOrder::new()),
+        ]));
+        assert_eq!(names(&order), ["input", "move", "ai", "draw"]);
+        assert!(looped.is_empty());
+    }
+
+    #[test]
+    fn before_and_after_win_over_priority() {
+        let (order, _) = run_order(scripts(&[
+            ("physics", ScriptOrder::new().with_priority(-10).with_after("input")),
+            ("input", ScriptOrder::new().with_priority(5)),
+            ("camera", ScriptOrder::new().with_priority(-20)),
+            ("sound", ScriptOrder::new().with_priority(100).with_before("camera")),
+        ]));
+        assert_eq!(names(&order), ["input", "physics", "sound", "camera"]);
+    }
+
+    #[test]
+    fn missing_names_are_ignored() {
+        let (order, looped) = run_order(scripts(&[
+            ("b", ScriptOrder::new().with_priority(1).with_after("nothing")),
+            ("a", ScriptOrder::new().with_before("gone").with_after("a")),
+        ]));
+        assert_eq!(names(&order), ["a", "b"]);
+        assert!(looped.is_empty());
+    }
+
+    #[test]
+    fn loops_are_broken_by_priority_and_reported() {
+        let (order, looped) = run_order(scripts(&[
+            ("first", ScriptOrder::new()),
+            ("a", ScriptOrder::new().with_priority(2).with_before("b")),
+            ("b", ScriptOrder::new().with_priority(1).with_before("c")),
+            ("c", ScriptOrder::new().with_priority(3).with_before("a")),
+        ]));
+        assert_eq!(names(&order), ["first", "b", "c", "a"]);
+        assert_eq!(looped, ["b"]);
+    }
+
+    #[test]
+    fn enabling_disabling_and_removing_cover_every_stage() {
+        let mut schedule = Schedule::new();
+        schedule.add(ScriptStage::Update, "tick", nothing, ScriptOrder::new());
+        schedule.add(ScriptStage::LateUpdate, "tick", nothing, ScriptOrder::new());
+        schedule.add(ScriptStage::Update, "other", nothing, ScriptOrder::new().with_before("tick"));
+        assert_eq!(stage_names(&schedule, ScriptStage::Update), ["other", "tick"]);
+
+        assert!(schedule.set_enabled("tick", false));
+        assert!(!schedule.is_enabled("tick"));
+        assert_eq!(schedule.enabled(ScriptStage::Update).len(), 1);
+        assert!(schedule.enabled(ScriptStage::LateUpdate).is_empty());
+        // Still listed, just not run
+        assert_eq!(stage_names(&schedule, ScriptStage::LateUpdate), ["tick"]);
+
+        assert!(schedule.set_enabled("tick", true));
+        assert_eq!(schedule.enabled(ScriptStage::LateUpdate).len(), 1);
+        assert!(!schedule.set_enabled("missing", false));
+
+        // Replacing only touches its own stage
+        schedule.add(ScriptStage::Update, "tick", nothing, ScriptOrder::new().with_priority(-1));
+        assert_eq!(schedule.get_order(ScriptStage::LateUpdate, "tick"), Some(&ScriptOrder::new()));
+        assert_eq!(stage_names(&schedule, ScriptStage::Update), ["other", "tick"]);
+
+        assert!(schedule.remove("tick"));
+        assert!(!schedule.remove("tick"));
+        assert_eq!(stage_names(&schedule, ScriptStage::Update), ["other"]);
+        assert!(stage_names(&schedule, ScriptStage::LateUpdate).is_empty());
+    }
+}
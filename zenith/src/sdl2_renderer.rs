@@ -1,8 +1,13 @@
-use crate::render::Sdl2Env;
-use crate::render::VisualRect;
-use crate::Screen;
+use crate::render::{
+    apply_post_effects, letterbox, Batch, BlendMode, PostEffect, RenderQueue, Sdl2Env, TextureId,
+    TextureSlots, VisualRect, VisualSprite,
+};
+use crate::capture::{rgba_len, rgba_len_text};
+use crate::{FrameBuffer, Screen};
 
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
+use sdl2::render::{Canvas, RenderTarget, Texture};
 
 pub fn new_window(screen: Screen) -> Sdl2Env {
     let sdl_context = sdl2::init().unwrap();
@@ -11,50 +16,363 @@ pub fn new_window(screen: Screen) -> Sdl2Env {
     let window = video_subsystem
         .window(&screen.caption, screen.window_size.0, screen.window_size.1)
         .position_centered()
+        .resizable()
         .build()
         .unwrap();
 
     let canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
 
     Sdl2Env {
         canvas: canvas,
         sdl_context: sdl_context,
+        texture_creator,
+        textures: TextureSlots::default(),
+        queue: RenderQueue::new(),
+        overlay: RenderQueue::new(),
+        scene_target: None,
+        effects_target: false,
+        effect_output: None,
+        scaling: screen.scaling,
+    }
+}
+
+// No real window and a software renderer, for benchmarks and tests on machines without a display
+pub fn new_headless_window(screen: Screen) -> Sdl2Env {
+    sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+
+    let window = video_subsystem
+        .window(&screen.caption, screen.window_size.0, screen.window_size.1)
+        .hidden()
+        .build()
+        .unwrap();
+
+    let canvas = window.into_canvas().software().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+
+    Sdl2Env {
+        canvas,
+        sdl_context,
+        texture_creator,
+        textures: TextureSlots::default(),
+        queue: RenderQueue::new(),
+        overlay: RenderQueue::new(),
+        scene_target: None,
+        effects_target: false,
+        effect_output: None,
+        scaling: screen.scaling,
     }
 }
 
 pub fn sdl2_draw_rect(sdl2_env: &mut Sdl2Env, rect: VisualRect) {
-    sdl2_env.canvas.set_draw_color(sdl2::pixels::Color::RGB(
-        rect.color.r,
-        rect.color.g,
-        rect.color.b,
-    ));
-
-    let width_result = rect.size.x.try_into();
-    let width: u32 = match width_result {
-        Ok(val) => val,
-        Err(_) => {
-            // Handle the error, for example:
-            println!("Error: Width cannot be negitive - setting Width to 0");
-            0 // Default value
+    sdl2_env.queue.push_rect(rect);
+}
+
+pub fn sdl2_draw_sprite(sdl2_env: &mut Sdl2Env, sprite: VisualSprite) {
+    sdl2_env.queue.push_sprite(sprite);
+}
+
+pub fn sdl2_create_texture(
+    sdl2_env: &mut Sdl2Env,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Result<TextureId, String> {
+    if rgba_len(width, height) != Some(pixels.len()) {
+        return Err(format!(
+            "Texture data is {} bytes but {}x{} RGBA needs {}",
+            pixels.len(),
+            width,
+            height,
+            rgba_len_text(width, height)
+        ));
+    }
+
+    let mut texture = sdl2_env
+        .texture_creator
+        .create_texture_static(PixelFormatEnum::RGBA32, width, height)
+        .map_err(|e| e.to_string())?;
+    texture
+        .update(None, pixels, width as usize * 4)
+        .map_err(|e| e.to_string())?;
+    texture.set_blend_mode(sdl2::render::BlendMode::Blend);
+
+    Ok(sdl2_env.textures.insert(texture))
+}
+
+pub fn sdl2_destroy_texture(sdl2_env: &mut Sdl2Env, texture: TextureId) {
+    if let Some(texture) = sdl2_env.textures.remove(texture) {
+        // The canvas outlives every texture in the list so this is fine
+        unsafe { texture.destroy() }
+    }
+}
+
+pub fn sdl2_flush(sdl2_env: &mut Sdl2Env) {
+    let batches = sdl2_env.queue.take_batches();
+    submit_batches(&mut sdl2_env.canvas, &mut sdl2_env.textures, batches);
+}
+
+pub fn submit_batches<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    textures: &mut TextureSlots,
+    batches: Vec<Batch>,
+) {
+    for batch in batches {
+        match batch {
+            Batch::Rects { color, rects } => {
+                canvas.set_draw_color(sdl2::pixels::Color::RGB(color.r, color.g, color.b));
+                let rects: Vec<Rect> = rects
+                    .into_iter()
+                    .map(|(x, y, w, h)| Rect::new(x, y, w, h))
+                    .collect();
+                let _ = canvas.fill_rects(&rects);
+            }
+            Batch::Sprites { texture, blend, sprites } => {
+                match textures.get_mut(texture) {
+                    Some(texture) => {
+                        if let Err(e) = render_sprites(canvas, texture, blend, &sprites) {
+                            eprintln!("Error: Could not draw sprites - {}", e)
+                        }
+                    }
+                    None => eprintln!("Error: Cannot draw sprites - texture {:?} does not exist", texture),
+                }
+            }
         }
+    }
+}
+
+// Every sprite in the batch becomes two triangles and the whole batch is one SDL_RenderGeometry call
+fn render_sprites<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    texture: &mut Texture,
+    blend: BlendMode,
+    sprites: &[VisualSprite],
+) -> Result<(), String> {
+    texture.set_blend_mode(match blend {
+        BlendMode::None => sdl2::render::BlendMode::None,
+        BlendMode::Blend => sdl2::render::BlendMode::Blend,
+        BlendMode::Add => sdl2::render::BlendMode::Add,
+        BlendMode::Mod => sdl2::render::BlendMode::Mod,
+    });
+
+    let query = texture.query();
+    let (texture_width, texture_height) = (query.width as f32, query.height as f32);
+
+    let mut vertices: Vec<sdl2::sys::SDL_Vertex> = Vec::with_capacity(sprites.len() * 4);
+    let mut indices: Vec<i32> = Vec::with_capacity(sprites.len() * 6);
+
+    for sprite in sprites {
+        let (sx, sy, sw, sh) = match &sprite.source {
+            Some((position, size)) => (
+                position.x as f32,
+                position.y as f32,
+                size.x as f32,
+                size.y as f32,
+            ),
+            None => (0.0, 0.0, texture_width, texture_height),
+        };
+        let (u0, v0) = (sx / texture_width, sy / texture_height);
+        let (u1, v1) = ((sx + sw) / texture_width, (sy + sh) / texture_height);
+
+        let (x0, y0) = (sprite.location.x as f32, sprite.location.y as f32);
+        let (x1, y1) = (x0 + sprite.size.x as f32, y0 + sprite.size.y as f32);
+
+        let color = sdl2::sys::SDL_Color {
+            r: sprite.tint.r,
+            g: sprite.tint.g,
+            b: sprite.tint.b,
+            a: 255,
+        };
+
+        let base = vertices.len() as i32;
+        for (x, y, u, v) in [(x0, y0, u0, v0), (x1, y0, u1, v0), (x1, y1, u1, v1), (x0, y1, u0, v1)] {
+            vertices.push(sdl2::sys::SDL_Vertex {
+                position: sdl2::sys::SDL_FPoint { x, y },
+                color,
+                tex_coord: sdl2::sys::SDL_FPoint { x: u, y: v },
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+
+    let result = unsafe {
+        sdl2::sys::SDL_RenderGeometry(
+            canvas.raw(),
+            texture.raw(),
+            vertices.as_ptr(),
+            vertices.len() as i32,
+            indices.as_ptr(),
+            indices.len() as i32,
+        )
+    };
+    if result != 0 {
+        return Err(sdl2::get_error());
+    }
+    Ok(())
+}
+
+pub fn sdl2_set_render_target(sdl2_env: &mut Sdl2Env, size: Option<(u32, u32)>) -> Result<(), String> {
+    if let Some(old_target) = sdl2_env.scene_target.take() {
+        sdl2_destroy_texture(sdl2_env, old_target);
+    }
+    sdl2_env.effects_target = false;
+    let Some((width, height)) = size else {
+        return Ok(());
     };
 
-    // Handling errors for converting rect.size.y to u32
-    let length_result = rect.size.y.try_into();
-    let length: u32 = match length_result {
-        Ok(val) => val,
-        Err(_) => {
-            // Handle the error, for example:
-            eprintln!("Error: Length cannot be negitive - setting Length to 0");
-            0 // Default value
+    let texture = sdl2_env
+        .texture_creator
+        .create_texture_target(PixelFormatEnum::RGBA32, width, height)
+        .map_err(|e| e.to_string())?;
+    // Nearest neighbour so low resolution targets stay crisp when scaled up
+    unsafe {
+        sdl2::sys::SDL_SetTextureScaleMode(texture.raw(), sdl2::sys::SDL_ScaleMode::SDL_ScaleModeNearest);
+    }
+
+    sdl2_env.scene_target = Some(sdl2_env.textures.insert(texture));
+    Ok(())
+}
+
+pub fn sdl2_view_size(sdl2_env: &Sdl2Env) -> (u32, u32) {
+    sdl2_env
+        .scene_target
+        .and_then(|target| sdl2_env.textures.get(target))
+        .map(|texture| (texture.query().width, texture.query().height))
+        .unwrap_or_else(|| sdl2_env.canvas.output_size().unwrap_or((0, 0)))
+}
+
+pub fn sdl2_apply_screen(sdl2_env: &mut Sdl2Env, screen: &Screen) -> Result<(), String> {
+    let window = sdl2_env.canvas.window_mut();
+    window.set_title(&screen.caption).map_err(|e| e.to_string())?;
+    window
+        .set_size(screen.window_size.0, screen.window_size.1)
+        .map_err(|e| e.to_string())?;
+
+    sdl2_env.scaling = screen.scaling;
+    // None takes away a target from an earlier logical size
+    sdl2_set_render_target(sdl2_env, screen.logical_size)
+}
+
+pub fn sdl2_update_display(
+    sdl2_env: &mut Sdl2Env,
+    effects: &[PostEffect],
+    capture: bool,
+) -> Option<Result<FrameBuffer, String>> {
+    // Effects need pixels to work on so make a window sized target if there isn't one yet.
+    // It goes again once the effects are cleared and is remade when the window changes size.
+    let window_size = sdl2_env.canvas.output_size().unwrap_or((1, 1));
+    if sdl2_env.effects_target && (effects.is_empty() || sdl2_view_size(sdl2_env) != window_size) {
+        if let Err(e) = sdl2_set_render_target(sdl2_env, None) {
+            eprintln!("Error: Could not remove render target for post effects - {}", e)
         }
+    }
+    if effects.is_empty() {
+        if let Some(output) = sdl2_env.effect_output.take() {
+            // Same as sdl2_destroy_texture, the canvas outlives it
+            unsafe { output.destroy() }
+        }
+    } else if sdl2_env.scene_target.is_none() && window_size.0 > 0 && window_size.1 > 0 {
+        match sdl2_set_render_target(sdl2_env, Some(window_size)) {
+            Ok(()) => sdl2_env.effects_target = true,
+            Err(e) => eprintln!("Error: Could not create render target for post effects - {}", e),
+        }
+    }
+
+    match sdl2_env.scene_target {
+        Some(target) => compose_scene_target(sdl2_env, target, effects),
+        None => sdl2_flush(sdl2_env),
+    }
+
+    let overlay = sdl2_env.overlay.take_batches();
+    submit_batches(&mut sdl2_env.canvas, &mut sdl2_env.textures, overlay);
+
+    let frame = capture.then(|| sdl2_read_frame(sdl2_env));
+    sdl2_env.canvas.present();
+    frame
+}
+
+fn sdl2_read_frame(sdl2_env: &Sdl2Env) -> Result<FrameBuffer, String> {
+    let (width, height) = sdl2_env.canvas.output_size()?;
+    let pixels = sdl2_env.canvas.read_pixels(None, PixelFormatEnum::RGBA32)?;
+    FrameBuffer::from_pixels(width, height, pixels)
+}
+
+fn compose_scene_target(sdl2_env: &mut Sdl2Env, target: TextureId, effects: &[PostEffect]) {
+    // Taken out of the list while it's being drawn into so it can't be sampled from at the same time
+    let Some(mut texture) = sdl2_env.textures.take(target) else {
+        return;
     };
 
-    let _ = sdl2_env
-        .canvas
-        .fill_rect(Rect::new(rect.location.x, rect.location.y, width, length));
+    let batches = sdl2_env.queue.take_batches();
+    let textures = &mut sdl2_env.textures;
+    let mut pixels: Option<Result<Vec<u8>, String>> = None;
+
+    let result = sdl2_env.canvas.with_texture_canvas(&mut texture, |canvas| {
+        // Cleared every frame so nothing from the last one shows through
+        canvas.set_draw_color(sdl2::pixels::Color::RGBA(0, 0, 0, 255));
+        canvas.clear();
+        submit_batches(canvas, textures, batches);
+        if !effects.is_empty() {
+            pixels = Some(canvas.read_pixels(None, PixelFormatEnum::RGBA32));
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("Error: Could not draw into render target - {}", e)
+    }
+
+    // Effects go into a separate texture so the scene target never has them baked in
+    let query = texture.query();
+    let mut with_effects = false;
+    match pixels {
+        Some(Ok(mut pixels)) => {
+            apply_post_effects(&mut pixels, query.width, query.height, effects);
+            match write_effect_output(sdl2_env, query.width, query.height, &pixels) {
+                Ok(()) => with_effects = true,
+                Err(e) => eprintln!("Error: Could not apply post effects - {}", e),
+            }
+        }
+        Some(Err(e)) => eprintln!("Error: Could not read render target - {}", e),
+        None => {}
+    }
+
+    // Clear first so the letterbox bars are black
+    let window_size = sdl2_env.canvas.output_size().unwrap_or((query.width, query.height));
+    let (x, y, width, height) = letterbox(window_size, (query.width, query.height), sdl2_env.scaling);
+    sdl2_env.canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
+    sdl2_env.canvas.clear();
+    let shown = match &sdl2_env.effect_output {
+        Some(output) if with_effects => output,
+        _ => &texture,
+    };
+    let _ = sdl2_env.canvas.copy(shown, None, Rect::new(x, y, width, height));
+    sdl2_env.textures.put_back(target, texture);
 }
 
-pub fn sdl2_update_display(sdl2_env: &mut Sdl2Env) {
-    sdl2_env.canvas.present()
+// Made again whenever the scene target changes size
+fn write_effect_output(sdl2_env: &mut Sdl2Env, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+    let matches = sdl2_env
+        .effect_output
+        .as_ref()
+        .is_some_and(|output| output.query().width == width && output.query().height == height);
+    if !matches {
+        if let Some(old) = sdl2_env.effect_output.take() {
+            // Same as sdl2_destroy_texture, the canvas outlives it
+            unsafe { old.destroy() }
+        }
+        let output = sdl2_env
+            .texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+            .map_err(|e| e.to_string())?;
+        unsafe {
+            sdl2::sys::SDL_SetTextureScaleMode(output.raw(), sdl2::sys::SDL_ScaleMode::SDL_ScaleModeNearest);
+        }
+        sdl2_env.effect_output = Some(output);
+    }
+    let Some(output) = sdl2_env.effect_output.as_mut() else {
+        return Ok(());
+    };
+    output.update(None, pixels, width as usize * 4).map_err(|e| e.to_string())
 }
@@ -0,0 +1,264 @@
+// Systems are functions over the entities that say up front which tags and resources they
+// read and write. Systems that don't touch each other's writes get put in the same batch
+// and run at the same time on rayon's thread pool. Inside a batch every system sees the
+// entities as they were before the batch and its writes are applied once the batch is
+// done, which gives the same result as running them one by one in the order they were added.
+use crate::entities::EntityStore;
+use crate::{EntityId, TagValue};
+
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct System {
+    name: String,
+    function: fn(&mut SystemContext),
+    reads: Vec<String>, // Tags
+    writes: Vec<String>,
+    resource_reads: Vec<String>,
+    resource_writes: Vec<String>,
+}
+
+impl System {
+    pub fn new(name: &str, function: fn(&mut SystemContext)) -> Self {
+        System {
+            name: name.to_string(),
+            function,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            resource_reads: Vec::new(),
+            resource_writes: Vec::new(),
+        }
+    }
+
+    pub fn with_read(self, tag: &str) -> Self {
+        let mut x = self;
+        x.reads.push(tag.to_string());
+        x
+    }
+
+    // Writing a tag also lets the system read it
+    pub fn with_write(self, tag: &str) -> Self {
+        let mut x = self;
+        x.writes.push(tag.to_string());
+        x
+    }
+
+    pub fn with_resource_read(self, name: &str) -> Self {
+        let mut x = self;
+        x.resource_reads.push(name.to_string());
+        x
+    }
+
+    pub fn with_resource_write(self, name: &str) -> Self {
+        let mut x = self;
+        x.resource_writes.push(name.to_string());
+        x
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn can_read(&self, tag: &str) -> bool {
+        self.reads.iter().chain(&self.writes).any(|declared| declared == tag)
+    }
+
+    fn can_read_resource(&self, name: &str) -> bool {
+        self.resource_reads.iter().chain(&self.resource_writes).any(|declared| declared == name)
+    }
+
+    // Whether running the two at the same time could change what either of them sees
+    fn conflicts_with(&self, other: &System) -> bool {
+        let overlaps = |writes: &Vec<String>, reads: &Vec<String>, other_writes: &Vec<String>| {
+            writes.iter().any(|name| reads.contains(name) || other_writes.contains(name))
+        };
+        overlaps(&self.writes, &other.reads, &other.writes)
+            || overlaps(&other.writes, &self.reads, &self.writes)
+            || overlaps(&self.resource_writes, &other.resource_reads, &other.resource_writes)
+            || overlaps(&other.resource_writes, &self.resource_reads, &self.resource_writes)
+    }
+}
+
+// What a system gets to see. Reading or writing something the system didn't declare is
+// an error, it gets reported and ignored.
+pub struct SystemContext<'a> {
+    system: &'a System,
+    entities: &'a EntityStore,
+    resources: &'a HashMap<String, TagValue>,
+    delta_time: f32,
+    writes: HashMap<(EntityId, String), TagValue>,
+    resource_writes: HashMap<String, TagValue>,
+}
+
+impl SystemContext<'_> {
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
+    // Every entity, in the order they were added
+    pub fn entity_ids(&self) -> &[EntityId] {
+        self.entities.ids()
+    }
+
+    // Entities that have all of the tags
+    pub fn query(&self, tags: &[&str]) -> Vec<EntityId> {
+        if let Some(tag) = tags.iter().find(|tag| !self.system.can_read(tag)) {
+            self.undeclared("reads tag", tag);
+            return Vec::new();
+        }
+        self.entities
+            .ids()
+            .iter()
+            .zip(self.entities.as_slice())
+            .filter(|(_, entity)| tags.iter().all(|tag| entity.tags.contains_key(*tag)))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // Sees the system's own writes straight away
+    pub fn get(&self, id: EntityId, tag: &str) -> Option<&TagValue> {
+        if !self.system.can_read(tag) {
+            self.undeclared("reads tag", tag);
+            return None;
+        }
+        if let Some(value) = self.writes.get(&(id, tag.to_string())) {
+            return Some(value);
+        }
+        self.entities.get(id)?.tags.get(tag)
+    }
+
+    pub fn set(&mut self, id: EntityId, tag: &str, value: TagValue) {
+        if !self.system.writes.iter().any(|declared| declared == tag) {
+            self.undeclared("writes tag", tag);
+            return;
+        }
+        if self.entities.contains(id) {
+            self.writes.insert((id, tag.to_string()), value);
+        }
+    }
+
+    pub fn get_resource(&self, name: &str) -> Option<&TagValue> {
+        if !self.system.can_read_resource(name) {
+            self.undeclared("reads resource", name);
+            return None;
+        }
+        self.resource_writes.get(name).or_else(|| self.resources.get(name))
+    }
+
+    pub fn set_resource(&mut self, name: &str, value: TagValue) {
+        if !self.system.resource_writes.iter().any(|declared| declared == name) {
+            self.undeclared("writes resource", name);
+            return;
+        }
+        self.resource_writes.insert(name.to_string(), value);
+    }
+
+    fn undeclared(&self, what: &str, name: &str) {
+        eprintln!(
+            "Error: System {} {} {} without declaring it",
+            self.system.name, what, name
+        );
+    }
+}
+
+type Writes = (HashMap<(EntityId, String), TagValue>, HashMap<String, TagValue>);
+
+#[derive(Clone)]
+pub(crate) struct Systems {
+    systems: Vec<System>,
+    batches: Vec<Vec<usize>>, // Indexes into systems, worked out when a system is added
+    pub(crate) parallel: bool,
+}
+
+impl Default for Systems {
+    fn default() -> Self {
+        Systems {
+            systems: Vec::new(),
+            batches: Vec::new(),
+            // Setting ZENITH_SINGLE_THREADED runs everything on one thread, for debugging
+            parallel: std::env::var_os("ZENITH_SINGLE_THREADED").is_none(),
+        }
+    }
+}
+
+impl Systems {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    // Adding a name again replaces the system
+    pub(crate) fn add(&mut self, system: System) {
+        self.systems.retain(|existing| existing.name != system.name);
+        self.systems.push(system);
+        self.plan();
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        let before = self.systems.len();
+        self.systems.retain(|existing| existing.name != name);
+        self.plan();
+        before != self.systems.len()
+    }
+
+    pub(crate) fn names(&self) -> Vec<&str> {
+        self.systems.iter().map(|system| system.name.as_str()).collect()
+    }
+
+    // Names of the systems in each batch
+    pub(crate) fn batches(&self) -> Vec<Vec<&str>> {
+        self.batches
+            .iter()
+            .map(|batch| batch.iter().map(|index| self.systems[*index].name.as_str()).collect())
+            .collect()
+    }
+
+    // Each system goes in the first batch after the last one holding a system it conflicts
+    // with, so conflicting systems always run in the order they were added
+    fn plan(&mut self) {
+        self.batches.clear();
+        for (index, system) in self.systems.iter().enumerate() {
+            let first = self
+                .batches
+                .iter()
+                .rposition(|batch| batch.iter().any(|other| self.systems[*other].conflicts_with(system)))
+                .map(|batch| batch + 1)
+                .unwrap_or(0);
+            match self.batches.get_mut(first) {
+                Some(batch) => batch.push(index),
+                None => self.batches.push(vec![index]),
+            }
+        }
+    }
+
+    pub(crate) fn run(&self, entities: &mut EntityStore, resources: &mut HashMap<String, TagValue>, delta_time: f32) {
+        for batch in &self.batches {
+            let run = |index: &usize| -> Writes {
+                let mut context = SystemContext {
+                    system: &self.systems[*index],
+                    entities,
+                    resources,
+                    delta_time,
+                    writes: HashMap::new(),
+                    resource_writes: HashMap::new(),
+                };
+                (self.systems[*index].function)(&mut context);
+                (context.writes, context.resource_writes)
+            };
+            let results: Vec<Writes> = if self.parallel && batch.len() > 1 {
+                batch.par_iter().map(run).collect()
+            } else {
+                batch.iter().map(run).collect()
+            };
+
+            for (writes, resource_writes) in results {
+                for ((id, tag), value) in writes {
+                    if let Some(entity) = entities.get_mut(id) {
+                        entity.set_tag(&tag, value);
+                    }
+                }
+                resources.extend(resource_writes);
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+// Typed access and arithmetic for TagValues. The accessors return a TagError saying what
+// the tag actually was instead of a bare None, so `entity.try_get_tag("hp")?.as_int()?`
+// tells you why it didn't work.
+use crate::{Color, TagValue, Vec2};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagError {
+    Missing(String), // Name of the tag or resource
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    Incompatible {
+        operation: &'static str,
+        left: &'static str,
+        right: &'static str,
+    },
+    DivideByZero,
+    Overflow,
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagError::Missing(name) => write!(f, "No tag or resource named {}", name),
+            TagError::WrongType { expected, found } => write!(f, "Expected {} but the value is {}", expected, found),
+            TagError::Incompatible { operation, left, right } => {
+                write!(f, "Can't {} {} and {}", operation, left, right)
+            }
+            TagError::DivideByZero => write!(f, "Divided by zero"),
+            TagError::Overflow => write!(f, "Result doesn't fit in an Int"),
+        }
+    }
+}
+
+impl std::error::Error for TagError {}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Operation {
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Add => "add",
+            Operation::Sub => "subtract",
+            Operation::Mul => "multiply",
+            Operation::Div => "divide",
+        }
+    }
+
+    fn ints(&self, a: i32, b: i32) -> Result<i32, TagError> {
+        match self {
+            Operation::Add => a.checked_add(b),
+            Operation::Sub => a.checked_sub(b),
+            Operation::Mul => a.checked_mul(b),
+            Operation::Div if b == 0 => return Err(TagError::DivideByZero),
+            Operation::Div => a.checked_div(b),
+        }
+        .ok_or(TagError::Overflow)
+    }
+
+    // Floats follow the usual float rules, dividing by zero gives infinity
+    fn floats(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Operation::Add => a + b,
+            Operation::Sub => a - b,
+            Operation::Mul => a * b,
+            Operation::Div => a / b,
+        }
+    }
+}
+
+// Ints, floats and doubles as a double, for scaling Vec2s and colors
+fn scalar(value: &TagValue) -> Option<f64> {
+    match value {
+        TagValue::Int(x) => Some(*x as f64),
+        TagValue::Float(x) => Some(*x as f64),
+        TagValue::Double(x) => Some(*x),
+        _ => None,
+    }
+}
+
+impl TagValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TagValue::String(_) => "String",
+            TagValue::Int(_) => "Int",
+            TagValue::Float(_) => "Float",
+            TagValue::Double(_) => "Double",
+            TagValue::Color(_) => "Color",
+            TagValue::Vec2(_) => "Vec2",
+            TagValue::Bool(_) => "Bool",
+            TagValue::List(_) => "List",
+            TagValue::Map(_) => "Map",
+        }
+    }
+
+    fn wrong_type(&self, expected: &'static str) -> TagError {
+        TagError::WrongType {
+            expected,
+            found: self.type_name(),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, TagError> {
+        match self {
+            TagValue::String(x) => Ok(x),
+            _ => Err(self.wrong_type("String")),
+        }
+    }
+
+    pub fn as_int(&self) -> Result<i32, TagError> {
+        match self {
+            TagValue::Int(x) => Ok(*x),
+            _ => Err(self.wrong_type("Int")),
+        }
+    }
+
+    pub fn as_float(&self) -> Result<f32, TagError> {
+        match self {
+            TagValue::Float(x) => Ok(*x),
+            _ => Err(self.wrong_type("Float")),
+        }
+    }
+
+    pub fn as_double(&self) -> Result<f64, TagError> {
+        match self {
+            TagValue::Double(x) => Ok(*x),
+            _ => Err(self.wrong_type("Double")),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, TagError> {
+        match self {
+            TagValue::Bool(x) => Ok(*x),
+            _ => Err(self.wrong_type("Bool")),
+        }
+    }
+
+    pub fn as_color(&self) -> Result<&Color, TagError> {
+        match self {
+            TagValue::Color(x) => Ok(x),
+            _ => Err(self.wrong_type("Color")),
+        }
+    }
+
+    pub fn as_vec2(&self) -> Result<&Vec2, TagError> {
+        match self {
+            TagValue::Vec2(x) => Ok(x),
+            _ => Err(self.wrong_type("Vec2")),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&Vec<TagValue>, TagError> {
+        match self {
+            TagValue::List(x) => Ok(x),
+            _ => Err(self.wrong_type("List")),
+        }
+    }
+
+    pub fn as_mut_list(&mut self) -> Result<&mut Vec<TagValue>, TagError> {
+        match self {
+            TagValue::List(x) => Ok(x),
+            _ => Err(self.wrong_type("List")),
+        }
+    }
+
+    pub fn as_map(&self) -> Result<&BTreeMap<String, TagValue>, TagError> {
+        match self {
+            TagValue::Map(x) => Ok(x),
+            _ => Err(self.wrong_type("Map")),
+        }
+    }
+
+    pub fn as_mut_map(&mut self) -> Result<&mut BTreeMap<String, TagValue>, TagError> {
+        match self {
+            TagValue::Map(x) => Ok(x),
+            _ => Err(self.wrong_type("Map")),
+        }
+    }
+
+    // Numbers mix, the result is the wider of the two (Int, then Float, then Double).
+    // Vec2s and colors work with each other and can be scaled by a number, colors stay in
+    // 0 to 255. Strings and lists can be added together.
+    pub fn try_add(&self, other: &TagValue) -> Result<TagValue, TagError> {
+        self.operate(other, Operation::Add)
+    }
+
+    pub fn try_sub(&self, other: &TagValue) -> Result<TagValue, TagError> {
+        self.operate(other, Operation::Sub)
+    }
+
+    pub fn try_mul(&self, other: &TagValue) -> Result<TagValue, TagError> {
+        self.operate(other, Operation::Mul)
+    }
+
+    pub fn try_div(&self, other: &TagValue) -> Result<TagValue, TagError> {
+        self.operate(other, Operation::Div)
+    }
+
+    fn operate(&self, other: &TagValue, operation: Operation) -> Result<TagValue, TagError> {
+        let incompatible = || TagError::Incompatible {
+            operation: operation.name(),
+            left: self.type_name(),
+            right: other.type_name(),
+        };
+        let scaled = |value: i32, by: f64| -> Result<f64, TagError> {
+            if matches!(operation, Operation::Div) && by == 0.0 {
+                return Err(TagError::DivideByZero);
+            }
+            Ok(operation.floats(value as f64, by).round())
+        };
+        // `as` would quietly saturate, NaN fails the check too
+        let int = |value: f64| -> Result<i32, TagError> {
+            if (i32::MIN as f64..=i32::MAX as f64).contains(&value) {
+                Ok(value as i32)
+            } else {
+                Err(TagError::Overflow)
+            }
+        };
+        let channel = |value: f64| value.round().clamp(0.0, 255.0) as u8;
+
+        match (self, other) {
+            (TagValue::Int(a), TagValue::Int(b)) => operation.ints(*a, *b).map(TagValue::Int),
+            (TagValue::Vec2(a), TagValue::Vec2(b)) => Ok(TagValue::Vec2(Vec2::new(
+                operation.ints(a.x, b.x)?,
+                operation.ints(a.y, b.y)?,
+            ))),
+            (TagValue::Vec2(a), _) => match scalar(other) {
+                Some(b) if matches!(operation, Operation::Mul | Operation::Div) => {
+                    Ok(TagValue::Vec2(Vec2::new(int(scaled(a.x, b)?)?, int(scaled(a.y, b)?)?)))
+                }
+                _ => Err(incompatible()),
+            },
+            (TagValue::Color(a), TagValue::Color(b)) => {
+                let mix = |a: u8, b: u8| -> Result<u8, TagError> {
+                    if matches!(operation, Operation::Div) && b == 0 {
+                        return Err(TagError::DivideByZero);
+                    }
+                    Ok(channel(operation.floats(a as f64, b as f64)))
+                };
+                Ok(TagValue::Color(Color {
+                    r: mix(a.r, b.r)?,
+                    g: mix(a.g, b.g)?,
+                    b: mix(a.b, b.b)?,
+                }))
+            }
+            (TagValue::Color(a), _) => match scalar(other) {
+                Some(b) if matches!(operation, Operation::Mul | Operation::Div) => {
+                    let mix = |a: u8| scaled(a as i32, b).map(channel);
+                    Ok(TagValue::Color(Color {
+                        r: mix(a.r)?,
+                        g: mix(a.g)?,
+                        b: mix(a.b)?,
+                    }))
+                }
+                _ => Err(incompatible()),
+            },
+            // After the Vec2s and colors so they can be scaled by a Double too
+            (TagValue::Double(_), _) | (_, TagValue::Double(_)) => match (scalar(self), scalar(other)) {
+                (Some(a), Some(b)) => Ok(TagValue::Double(operation.floats(a, b))),
+                _ => Err(incompatible()),
+            },
+            (TagValue::Float(_) | TagValue::Int(_), TagValue::Float(_) | TagValue::Int(_)) => {
+                match (scalar(self), scalar(other)) {
+                    (Some(a), Some(b)) => Ok(TagValue::Float(operation.floats(a, b) as f32)),
+                    _ => Err(incompatible()),
+                }
+            }
+            (TagValue::String(a), TagValue::String(b)) if matches!(operation, Operation::Add) => {
+                Ok(TagValue::String(format!("{}{}", a, b)))
+            }
+            (TagValue::List(a), TagValue::List(b)) if matches!(operation, Operation::Add) => {
+                Ok(TagValue::List(a.iter().chain(b).cloned().collect()))
+            }
+            _ => Err(incompatible()),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::eventloop;
+use crate::{FrameBuffer, Instance2D};
+
+use std::path::{Path, PathBuf};
+
+// Key presses/releases applied at the start of a given frame
+#[derive(Clone, Debug)]
+struct ScriptedInput {
+    frame: u32,
+    key: String,
+    pressed: bool,
+}
+
+// Runs an Instance2D without a window for a fixed number of frames, feeding it
+// scripted input and keeping the last presented frame for snapshot comparisons.
+pub struct HeadlessRunner {
+    pub instance: Instance2D,
+    inputs: Vec<ScriptedInput>,
+    frame: u32,
+    started: bool,
+    shut_down: bool,
+}
+
+impl Default for HeadlessRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeadlessRunner {
+    pub fn new() -> Self {
+        HeadlessRunner::with_instance(Instance2D::new_headless())
+    }
+
+    // The instance should come from Instance2D::new_headless() unless a window popping up is fine
+    pub fn with_instance(instance: Instance2D) -> Self {
+        let mut instance = instance;
+        instance.set_keep_last_frame(true);
+        // Same time step every frame so runs don't depend on how fast the machine is
+        instance.engine_settings.fixed_delta_time = Some(1.0 / instance.screen.get_framerate_cap() as f32);
+        HeadlessRunner {
+            instance,
+            inputs: Vec::new(),
+            frame: 0,
+            started: false,
+            shut_down: false,
+        }
+    }
+
+    // Key names are the ones Keys::set takes
+    pub fn press(self, frame: u32, key: &str) -> Self {
+        let mut x = self;
+        x.inputs.push(ScriptedInput {
+            frame,
+            key: key.to_string(),
+            pressed: true,
+        });
+        x
+    }
+
+    pub fn release(self, frame: u32, key: &str) -> Self {
+        let mut x = self;
+        x.inputs.push(ScriptedInput {
+            frame,
+            key: key.to_string(),
+            pressed: false,
+        });
+        x
+    }
+
+    // Frames that have been run so far
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    // Runs the given number of frames (stops early if a script quits) and returns the last one shown
+    pub fn run_frames(&mut self, frames: u32) -> Result<FrameBuffer, String> {
+        if !self.started {
+            eventloop::startup(&mut self.instance);
+            self.started = true;
+        }
+
+        for _ in 0..frames {
+            if !self.instance.engine_settings.is_running {
+                break;
+            }
+            for input in self.inputs.iter().filter(|input| input.frame == self.frame) {
+                if !self.instance.engine_settings.keys.set(&input.key, input.pressed) {
+                    return Err(format!("Unknown key in scripted input: {}", input.key));
+                }
+            }
+            eventloop::frame(&mut self.instance);
+            self.frame += 1;
+        }
+
+        self.instance
+            .last_frame()
+            .cloned()
+            .ok_or_else(|| "No frame has been presented yet".to_string())
+    }
+
+    // Runs the shutdown scripts, only the first call does anything
+    pub fn shutdown(&mut self) {
+        if !self.shut_down {
+            eventloop::shutdown(&mut self.instance);
+            self.shut_down = true;
+        }
+    }
+}
+
+// Result of comparing two frames that didn't match
+#[derive(Clone, Debug)]
+pub struct SnapshotDiff {
+    pub mismatched_pixels: usize,
+    pub max_difference: u8,
+    pub diff: FrameBuffer, // Mismatches in red over a faded copy of the expected frame
+}
+
+// Pixels match when no channel differs by more than `tolerance`
+pub fn compare_frames(actual: &FrameBuffer, expected: &FrameBuffer, tolerance: u8) -> Result<(), SnapshotDiff> {
+    let width = actual.width.max(expected.width);
+    let height = actual.height.max(expected.height);
+    let mut diff = FrameBuffer::new(width, height);
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            match (actual.get_pixel(x, y), expected.get_pixel(x, y)) {
+                (Some(a), Some(e)) => {
+                    let difference = a.iter().zip(e).map(|(a, e)| a.abs_diff(e)).max().unwrap_or(0);
+                    max_difference = max_difference.max(difference);
+                    if difference > tolerance {
+                        mismatched_pixels += 1;
+                        diff.set_pixel(x, y, [255, 0, 0, 255]);
+                    } else {
+                        let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 3 / 3) as u8;
+                        diff.set_pixel(x, y, [gray, gray, gray, 255]);
+                    }
+                }
+                // Only one of the frames covers this pixel, sizes differ
+                _ => {
+                    mismatched_pixels += 1;
+                    max_difference = 255;
+                    diff.set_pixel(x, y, [255, 0, 255, 255]);
+                }
+            }
+        }
+    }
+
+    if mismatched_pixels == 0 {
+        return Ok(());
+    }
+    Err(SnapshotDiff {
+        mismatched_pixels,
+        max_difference,
+        diff,
+    })
+}
+
+// Where reference images live, tests/snapshots in the crate being tested
+pub fn snapshot_dir() -> PathBuf {
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    Path::new(&root).join("tests").join("snapshots")
+}
+
+// Compares the frame against tests/snapshots/<name>.png. With ZENITH_BLESS=1 set the
+// frame is written as the new reference instead, a missing reference is an error
+// otherwise so tests can't pass just because nothing was committed.
+// On a mismatch <name>.actual.png and <name>.diff.png are written next to it.
+pub fn check_snapshot(name: &str, frame: &FrameBuffer, tolerance: u8) -> Result<(), String> {
+    let dir = snapshot_dir();
+    let reference = dir.join(format!("{}.png", name));
+
+    if std::env::var("ZENITH_BLESS").is_ok_and(|v| v == "1") {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        return frame.save_png(&reference);
+    }
+    if !reference.exists() {
+        return Err(format!(
+            "No reference image for snapshot {} at {} - run with ZENITH_BLESS=1 to record it",
+            name,
+            reference.display()
+        ));
+    }
+
+    let expected = FrameBuffer::load_png(&reference)?;
+    match compare_frames(frame, &expected, tolerance) {
+        Ok(()) => Ok(()),
+        Err(diff) => {
+            let actual_path = dir.join(format!("{}.actual.png", name));
+            let diff_path = dir.join(format!("{}.diff.png", name));
+            frame.save_png(&actual_path)?;
+            diff.diff.save_png(&diff_path)?;
+            Err(format!(
+                "Snapshot {} does not match: {} pixels differ (max difference {}, tolerance {}), see {}",
+                name,
+                diff.mismatched_pixels,
+                diff.max_difference,
+                tolerance,
+                diff_path.display()
+            ))
+        }
+    }
+}
+
+pub fn assert_snapshot(name: &str, frame: &FrameBuffer, tolerance: u8) {
+    if let Err(e) = check_snapshot(name, frame, tolerance) {
+        panic!("{}", e)
+    }
+}
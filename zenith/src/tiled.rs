@@ -0,0 +1,513 @@
+// Importer for maps made with Tiled (https://www.mapeditor.org), both the
+// JSON (.tmj/.json) and XML (.tmx) formats, including external tilesets.
+use crate::tilemap::{AnimationFrame, TileLayer, Tilemap, Tileset};
+use crate::{AssetPack, Color, Entity, TagValue, Vec2};
+
+use base64::Engine;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+// A loaded map, objects from object layers are turned into entities with
+// "location", "size", "type" and "layer" tags plus their custom properties
+pub struct TiledMap {
+    pub tilemap: Tilemap,
+    pub objects: Vec<Entity>,
+}
+
+pub fn load_tiled_map(path: &str) -> Result<TiledMap, String> {
+    load_tiled_map_from(&AssetPack::default(), path)
+}
+
+// External tilesets are read from the same pack, image paths are kept relative to it
+pub fn load_tiled_map_from(pack: &AssetPack, path: &str) -> Result<TiledMap, String> {
+    let path = Path::new(path);
+    let text = pack.read_to_string(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let result = match path.extension().and_then(|e| e.to_str()) {
+        Some("tmx") => parse_tmx(&text, base_dir, pack),
+        Some("tmj") | Some("json") => parse_tmj(&text, base_dir, pack),
+        _ => Err("Unknown map format, expected .tmx, .tmj or .json".to_string()),
+    };
+    result.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Layers and objects are gathered in file order, tile layers get negative render
+// layers so the map draws under everything on layer 0
+struct MapBuilder {
+    tilemap: Tilemap,
+    objects: Vec<Entity>,
+}
+
+impl MapBuilder {
+    fn finish(self) -> TiledMap {
+        let mut tilemap = self.tilemap;
+        let count = tilemap.layers.len() as i32;
+        for (i, layer) in tilemap.layers.iter_mut().enumerate() {
+            layer.render_layer = i as i32 - count;
+        }
+        TiledMap {
+            tilemap,
+            objects: self.objects,
+        }
+    }
+}
+
+struct ObjectData {
+    id: i32,
+    name: String,
+    class: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    gid: Option<u32>,
+    properties: HashMap<String, TagValue>,
+}
+
+fn object_entity(object: ObjectData, layer_name: &str, offset: &Vec2) -> Entity {
+    // Tile objects are positioned by their bottom left corner in Tiled
+    let y = if object.gid.is_some() {
+        object.y - object.height
+    } else {
+        object.y
+    };
+
+    let mut entity = Entity::new()
+        .with_tag("tiled_id", TagValue::Int(object.id))
+        .with_tag("layer", TagValue::String(layer_name.to_string()))
+        .with_tag(
+            "location",
+            TagValue::Vec2(Vec2::new(object.x as i32 + offset.x, y as i32 + offset.y)),
+        )
+        .with_tag(
+            "size",
+            TagValue::Vec2(Vec2::new(object.width as i32, object.height as i32)),
+        );
+    if !object.name.is_empty() {
+        entity = entity.with_name_tag(&object.name);
+    }
+    if !object.class.is_empty() {
+        entity = entity.with_tag("type", TagValue::String(object.class));
+    }
+    if let Some(gid) = object.gid {
+        entity = entity.with_tag("gid", TagValue::Int(gid as i32));
+    }
+    for (name, value) in object.properties {
+        entity = entity.with_tag(&name, value);
+    }
+    entity
+}
+
+fn property_value(kind: &str, value: &str) -> Option<TagValue> {
+    match kind {
+        "" | "string" | "file" => Some(TagValue::String(value.to_string())),
+        "int" | "object" => value.parse().ok().map(TagValue::Int),
+        "float" => value.parse().ok().map(TagValue::Float),
+        // Still 0 or 1 rather than a Bool tag, games already read these with extract_int
+        "bool" => Some(TagValue::Int((value == "true") as i32)),
+        "color" => parse_color(value).map(TagValue::Color),
+        _ => None, // Class properties have no TagValue to go in
+    }
+}
+
+// "#AARRGGBB" or "#RRGGBB", alpha is dropped
+fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.trim_start_matches('#');
+    // The slicing below is by bytes
+    if !hex.is_ascii() {
+        return None;
+    }
+    let hex = match hex.len() {
+        8 => &hex[2..],
+        6 => hex,
+        _ => return None,
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+    })
+}
+
+fn decode_tile_data(data: &str, encoding: Option<&str>, compression: Option<&str>) -> Result<Vec<u32>, String> {
+    match encoding {
+        Some("csv") => data
+            .split(',')
+            .map(|gid| gid.trim())
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| gid.parse::<u32>().map_err(|e| format!("Bad tile id '{}': {}", gid, e)))
+            .collect(),
+        Some("base64") => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(data.trim())
+                .map_err(|e| format!("Bad base64 tile data: {}", e))?;
+            let mut decompressed = Vec::new();
+            let bytes = match compression {
+                None | Some("") => bytes,
+                Some("zlib") => {
+                    flate2::read::ZlibDecoder::new(&bytes[..])
+                        .read_to_end(&mut decompressed)
+                        .map_err(|e| format!("Bad zlib tile data: {}", e))?;
+                    decompressed
+                }
+                Some("gzip") => {
+                    flate2::read::GzDecoder::new(&bytes[..])
+                        .read_to_end(&mut decompressed)
+                        .map_err(|e| format!("Bad gzip tile data: {}", e))?;
+                    decompressed
+                }
+                Some(other) => return Err(format!("Unsupported tile data compression '{}'", other)),
+            };
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect())
+        }
+        Some(other) => Err(format!("Unsupported tile data encoding '{}'", other)),
+        None => Err("Tile data has no encoding".to_string()),
+    }
+}
+
+// The tiles have to fill the layer exactly, get_tile and set_tile index them by x and y
+fn tile_layer(name: &str, width: u32, height: u32, tiles: Vec<u32>) -> Result<TileLayer, String> {
+    let expected = width as u64 * height as u64;
+    if tiles.len() as u64 != expected {
+        return Err(format!(
+            "Tile layer '{}' has {} tiles but is {}x{} ({} tiles)",
+            name,
+            tiles.len(),
+            width,
+            height,
+            expected
+        ));
+    }
+    let mut layer = TileLayer::new(name, 0, 0);
+    layer.width = width;
+    layer.height = height;
+    layer.tiles = tiles;
+    Ok(layer)
+}
+
+fn resolve_path(base_dir: &Path, path: &str) -> String {
+    base_dir.join(path).to_string_lossy().to_string()
+}
+
+// ---- JSON (.tmj) ----
+
+fn json_u32(value: &Value, key: &str) -> u32 {
+    value.get(key).and_then(Value::as_u64).unwrap_or(0) as u32
+}
+
+fn json_f32(value: &Value, key: &str) -> f32 {
+    value.get(key).and_then(Value::as_f64).unwrap_or(0.0) as f32
+}
+
+fn json_str<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+fn json_properties(value: &Value) -> HashMap<String, TagValue> {
+    let mut properties = HashMap::new();
+    for property in value.get("properties").and_then(Value::as_array).into_iter().flatten() {
+        let kind = json_str(property, "type");
+        let raw = match property.get("value") {
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => continue,
+        };
+        if let Some(tag) = property_value(kind, &raw) {
+            properties.insert(json_str(property, "name").to_string(), tag);
+        }
+    }
+    properties
+}
+
+pub fn parse_tmj(text: &str, base_dir: &Path, pack: &AssetPack) -> Result<TiledMap, String> {
+    let map: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if map.get("infinite").and_then(Value::as_bool).unwrap_or(false) {
+        return Err("Infinite maps are not supported".to_string());
+    }
+
+    let mut tilemap = Tilemap::new(
+        json_u32(&map, "width"),
+        json_u32(&map, "height"),
+        Vec2::new(json_u32(&map, "tilewidth") as i32, json_u32(&map, "tileheight") as i32),
+    );
+    tilemap.properties = json_properties(&map);
+
+    for tileset in map.get("tilesets").and_then(Value::as_array).into_iter().flatten() {
+        let first_gid = json_u32(tileset, "firstgid");
+        let tileset = match tileset.get("source").and_then(Value::as_str) {
+            Some(source) => load_external_tileset(&base_dir.join(source), first_gid, pack)?,
+            None => json_tileset(tileset, first_gid, base_dir),
+        };
+        tilemap.tilesets.push(tileset);
+    }
+
+    let mut builder = MapBuilder {
+        tilemap,
+        objects: Vec::new(),
+    };
+    for layer in map.get("layers").and_then(Value::as_array).into_iter().flatten() {
+        json_layer(layer, &Vec2::new(0, 0), &mut builder)?;
+    }
+    Ok(builder.finish())
+}
+
+fn json_tileset(tileset: &Value, first_gid: u32, base_dir: &Path) -> Tileset {
+    let mut result = Tileset::new(
+        json_str(tileset, "name"),
+        first_gid,
+        Vec2::new(json_u32(tileset, "tilewidth") as i32, json_u32(tileset, "tileheight") as i32),
+        json_u32(tileset, "columns"),
+        json_u32(tileset, "tilecount"),
+    );
+    result.margin = json_u32(tileset, "margin");
+    result.spacing = json_u32(tileset, "spacing");
+    if let Some(image) = tileset.get("image").and_then(Value::as_str) {
+        result.image = Some(resolve_path(base_dir, image));
+    }
+
+    for tile in tileset.get("tiles").and_then(Value::as_array).into_iter().flatten() {
+        let id = json_u32(tile, "id");
+        let properties = json_properties(tile);
+        if !properties.is_empty() {
+            result.tile_properties.insert(id, properties);
+        }
+        if let Some(animation) = tile.get("animation").and_then(Value::as_array) {
+            let frames = animation
+                .iter()
+                .map(|frame| AnimationFrame {
+                    tile: json_u32(frame, "tileid"),
+                    duration: json_u32(frame, "duration") as f32 / 1000.0,
+                })
+                .collect();
+            result.animations.insert(id, frames);
+        }
+    }
+    result
+}
+
+fn json_layer(layer: &Value, parent_offset: &Vec2, builder: &mut MapBuilder) -> Result<(), String> {
+    let name = json_str(layer, "name");
+    let visible = layer.get("visible").and_then(Value::as_bool).unwrap_or(true);
+    let offset = Vec2::new(
+        parent_offset.x + json_f32(layer, "offsetx") as i32,
+        parent_offset.y + json_f32(layer, "offsety") as i32,
+    );
+
+    match json_str(layer, "type") {
+        "tilelayer" => {
+            let tiles = match layer.get("data") {
+                Some(Value::Array(data)) => data.iter().map(|gid| gid.as_u64().unwrap_or(0) as u32).collect(),
+                Some(Value::String(data)) => decode_tile_data(
+                    data,
+                    layer.get("encoding").and_then(Value::as_str),
+                    layer.get("compression").and_then(Value::as_str),
+                )?,
+                _ => return Err(format!("Tile layer '{}' has no data", name)),
+            };
+            let mut tile_layer = tile_layer(name, json_u32(layer, "width"), json_u32(layer, "height"), tiles)?;
+            tile_layer.visible = visible;
+            tile_layer.offset = offset;
+            tile_layer.properties = json_properties(layer);
+            builder.tilemap.layers.push(tile_layer);
+        }
+        "objectgroup" => {
+            for object in layer.get("objects").and_then(Value::as_array).into_iter().flatten() {
+                // "type" was renamed to "class" in Tiled 1.9
+                let class = match json_str(object, "class") {
+                    "" => json_str(object, "type"),
+                    class => class,
+                };
+                let data = ObjectData {
+                    id: json_u32(object, "id") as i32,
+                    name: json_str(object, "name").to_string(),
+                    class: class.to_string(),
+                    x: json_f32(object, "x"),
+                    y: json_f32(object, "y"),
+                    width: json_f32(object, "width"),
+                    height: json_f32(object, "height"),
+                    gid: object.get("gid").and_then(Value::as_u64).map(|gid| gid as u32),
+                    properties: json_properties(object),
+                };
+                builder.objects.push(object_entity(data, name, &offset));
+            }
+        }
+        "group" => {
+            for child in layer.get("layers").and_then(Value::as_array).into_iter().flatten() {
+                json_layer(child, &offset, builder)?;
+            }
+        }
+        _ => {} // Image layers aren't supported
+    }
+    Ok(())
+}
+
+fn load_external_tileset(path: &Path, first_gid: u32, pack: &AssetPack) -> Result<Tileset, String> {
+    let text = pack.read_to_string(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("tsx") => {
+            let document = roxmltree::Document::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(xml_tileset(document.root_element(), first_gid, base_dir))
+        }
+        _ => {
+            let tileset: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(json_tileset(&tileset, first_gid, base_dir))
+        }
+    }
+}
+
+// ---- XML (.tmx) ----
+
+fn xml_u32(node: roxmltree::Node, key: &str) -> u32 {
+    node.attribute(key).and_then(|v| v.parse().ok()).unwrap_or(0)
+}
+
+fn xml_f32(node: roxmltree::Node, key: &str) -> f32 {
+    node.attribute(key).and_then(|v| v.parse().ok()).unwrap_or(0.0)
+}
+
+fn xml_child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn xml_properties(node: roxmltree::Node) -> HashMap<String, TagValue> {
+    let mut properties = HashMap::new();
+    let Some(list) = xml_child(node, "properties") else {
+        return properties;
+    };
+    for property in list.children().filter(|child| child.has_tag_name("property")) {
+        // Multi-line strings are stored as text instead of the value attribute
+        let raw = property.attribute("value").or(property.text()).unwrap_or("");
+        if let Some(tag) = property_value(property.attribute("type").unwrap_or(""), raw) {
+            properties.insert(property.attribute("name").unwrap_or("").to_string(), tag);
+        }
+    }
+    properties
+}
+
+pub fn parse_tmx(text: &str, base_dir: &Path, pack: &AssetPack) -> Result<TiledMap, String> {
+    let document = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+    let map = document.root_element();
+    if map.attribute("infinite") == Some("1") {
+        return Err("Infinite maps are not supported".to_string());
+    }
+
+    let mut tilemap = Tilemap::new(
+        xml_u32(map, "width"),
+        xml_u32(map, "height"),
+        Vec2::new(xml_u32(map, "tilewidth") as i32, xml_u32(map, "tileheight") as i32),
+    );
+    tilemap.properties = xml_properties(map);
+
+    for tileset in map.children().filter(|child| child.has_tag_name("tileset")) {
+        let first_gid = xml_u32(tileset, "firstgid");
+        let tileset = match tileset.attribute("source") {
+            Some(source) => load_external_tileset(&base_dir.join(source), first_gid, pack)?,
+            None => xml_tileset(tileset, first_gid, base_dir),
+        };
+        tilemap.tilesets.push(tileset);
+    }
+
+    let mut builder = MapBuilder {
+        tilemap,
+        objects: Vec::new(),
+    };
+    for layer in map.children().filter(|child| child.is_element()) {
+        xml_layer(layer, &Vec2::new(0, 0), &mut builder)?;
+    }
+    Ok(builder.finish())
+}
+
+fn xml_tileset(tileset: roxmltree::Node, first_gid: u32, base_dir: &Path) -> Tileset {
+    let mut result = Tileset::new(
+        tileset.attribute("name").unwrap_or(""),
+        first_gid,
+        Vec2::new(xml_u32(tileset, "tilewidth") as i32, xml_u32(tileset, "tileheight") as i32),
+        xml_u32(tileset, "columns"),
+        xml_u32(tileset, "tilecount"),
+    );
+    result.margin = xml_u32(tileset, "margin");
+    result.spacing = xml_u32(tileset, "spacing");
+    if let Some(image) = xml_child(tileset, "image").and_then(|image| image.attribute("source")) {
+        result.image = Some(resolve_path(base_dir, image));
+    }
+
+    for tile in tileset.children().filter(|child| child.has_tag_name("tile")) {
+        let id = xml_u32(tile, "id");
+        let properties = xml_properties(tile);
+        if !properties.is_empty() {
+            result.tile_properties.insert(id, properties);
+        }
+        if let Some(animation) = xml_child(tile, "animation") {
+            let frames = animation
+                .children()
+                .filter(|child| child.has_tag_name("frame"))
+                .map(|frame| AnimationFrame {
+                    tile: xml_u32(frame, "tileid"),
+                    duration: xml_u32(frame, "duration") as f32 / 1000.0,
+                })
+                .collect();
+            result.animations.insert(id, frames);
+        }
+    }
+    result
+}
+
+fn xml_layer(layer: roxmltree::Node, parent_offset: &Vec2, builder: &mut MapBuilder) -> Result<(), String> {
+    let name = layer.attribute("name").unwrap_or("");
+    let visible = layer.attribute("visible") != Some("0");
+    let offset = Vec2::new(
+        parent_offset.x + xml_f32(layer, "offsetx") as i32,
+        parent_offset.y + xml_f32(layer, "offsety") as i32,
+    );
+
+    match layer.tag_name().name() {
+        "layer" => {
+            let data = xml_child(layer, "data").ok_or(format!("Tile layer '{}' has no data", name))?;
+            let tiles = match data.attribute("encoding") {
+                // No encoding means one <tile gid=".."/> element per tile
+                None => data
+                    .children()
+                    .filter(|child| child.has_tag_name("tile"))
+                    .map(|tile| xml_u32(tile, "gid"))
+                    .collect(),
+                encoding => decode_tile_data(data.text().unwrap_or(""), encoding, data.attribute("compression"))?,
+            };
+            let mut tile_layer = tile_layer(name, xml_u32(layer, "width"), xml_u32(layer, "height"), tiles)?;
+            tile_layer.visible = visible;
+            tile_layer.offset = offset;
+            tile_layer.properties = xml_properties(layer);
+            builder.tilemap.layers.push(tile_layer);
+        }
+        "objectgroup" => {
+            for object in layer.children().filter(|child| child.has_tag_name("object")) {
+                let data = ObjectData {
+                    id: xml_u32(object, "id") as i32,
+                    name: object.attribute("name").unwrap_or("").to_string(),
+                    class: object.attribute("class").or(object.attribute("type")).unwrap_or("").to_string(),
+                    x: xml_f32(object, "x"),
+                    y: xml_f32(object, "y"),
+                    width: xml_f32(object, "width"),
+                    height: xml_f32(object, "height"),
+                    gid: object.attribute("gid").and_then(|gid| gid.parse().ok()),
+                    properties: xml_properties(object),
+                };
+                builder.objects.push(object_entity(data, name, &offset));
+            }
+        }
+        "group" => {
+            for child in layer.children().filter(|child| child.is_element()) {
+                xml_layer(child, &offset, builder)?;
+            }
+        }
+        _ => {} // Image layers, properties and tilesets are handled elsewhere
+    }
+    Ok(())
+}
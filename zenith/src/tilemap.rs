@@ -0,0 +1,277 @@
+use crate::{EngineSettings2D, Handle, TagValue, Texture, TextureId, TiledMap, Vec2, VisualSprite};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Tiled keeps flip flags in the top bits of a gid, flipping isn't supported so they get masked off
+const GID_MASK: u32 = 0x0FFF_FFFF;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationFrame {
+    pub tile: u32,     // Local tile id inside the tileset
+    pub duration: f32, // Seconds
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tileset {
+    pub name: String,
+    pub first_gid: u32,
+    pub tile_size: Vec2,
+    pub columns: u32,
+    pub tile_count: u32,
+    pub margin: u32,
+    pub spacing: u32,
+    pub image: Option<String>, // Path to the tileset image, loaded through the assets
+    #[serde(skip)] // Only valid while the game runs
+    pub texture: Option<TextureId>,
+    pub tile_properties: HashMap<u32, HashMap<String, TagValue>>, // By local tile id
+    pub animations: HashMap<u32, Vec<AnimationFrame>>,          // By local tile id
+}
+
+impl Tileset {
+    pub fn new(name: &str, first_gid: u32, tile_size: Vec2, columns: u32, tile_count: u32) -> Self {
+        Tileset {
+            name: name.to_string(),
+            first_gid,
+            tile_size,
+            columns,
+            tile_count,
+            margin: 0,
+            spacing: 0,
+            image: None,
+            texture: None,
+            tile_properties: HashMap::new(),
+            animations: HashMap::new(),
+        }
+    }
+
+    pub fn with_image(self, path: &str) -> Self {
+        let mut x = self;
+        x.image = Some(path.to_string());
+        x
+    }
+
+    pub fn with_texture(self, texture: TextureId) -> Self {
+        let mut x = self;
+        x.texture = Some(texture);
+        x
+    }
+
+    pub fn contains(&self, gid: u32) -> bool {
+        // Subtracting can't overflow where adding can
+        gid >= self.first_gid && gid - self.first_gid < self.tile_count
+    }
+
+    // (position, size) of a local tile inside the tileset image
+    pub fn source_rect(&self, local_id: u32) -> (Vec2, Vec2) {
+        let columns = self.columns.max(1);
+        let column = local_id % columns;
+        let row = local_id / columns;
+        let x = self.margin + column * (self.tile_size.x as u32 + self.spacing);
+        let y = self.margin + row * (self.tile_size.y as u32 + self.spacing);
+        (Vec2::new(x as i32, y as i32), self.tile_size.clone())
+    }
+
+    // Which local tile to show right now for animated tiles
+    fn animated_tile(&self, local_id: u32, time: f32) -> u32 {
+        let Some(frames) = self.animations.get(&local_id) else {
+            return local_id;
+        };
+        let total: f32 = frames.iter().map(|frame| frame.duration).sum();
+        if total <= 0.0 {
+            return local_id;
+        }
+        let mut t = time % total;
+        for frame in frames {
+            if t < frame.duration {
+                return frame.tile;
+            }
+            t -= frame.duration;
+        }
+        local_id
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<u32>, // Global tile ids row by row, 0 = empty
+    pub visible: bool,
+    pub offset: Vec2,
+    pub render_layer: i32, // Layer used for the sprites of this tile layer
+    pub properties: HashMap<String, TagValue>,
+}
+
+impl TileLayer {
+    pub fn new(name: &str, width: u32, height: u32) -> Self {
+        TileLayer {
+            name: name.to_string(),
+            width,
+            height,
+            tiles: vec![0; width as usize * height as usize],
+            visible: true,
+            offset: Vec2::new(0, 0),
+            render_layer: -1,
+            properties: HashMap::new(),
+        }
+    }
+
+    pub fn get_tile(&self, x: u32, y: u32) -> u32 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        // Tiles is public, so it might not match the size
+        let index = y as usize * self.width as usize + x as usize;
+        self.tiles.get(index).map_or(0, |gid| gid & GID_MASK)
+    }
+
+    pub fn set_tile(&mut self, x: u32, y: u32, gid: u32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = y as usize * self.width as usize + x as usize;
+        if let Some(tile) = self.tiles.get_mut(index) {
+            *tile = gid;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tilemap {
+    pub width: u32, // In tiles
+    pub height: u32,
+    pub tile_size: Vec2,
+    pub position: Vec2, // World position of the top left corner
+    pub layers: Vec<TileLayer>,
+    pub tilesets: Vec<Tileset>,
+    pub properties: HashMap<String, TagValue>,
+    #[serde(skip)]
+    pub source: Option<Handle<TiledMap>>, // Set by Instance2D::load_tilemap
+    // The path it was loaded from, saved so load_game can hook the map back up for hot reloading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) source_path: Option<String>,
+    #[serde(skip)]
+    pub(crate) texture_handles: Vec<Handle<Texture>>,
+    time: f32, // Drives tile animations
+}
+
+impl Tilemap {
+    pub fn new(width: u32, height: u32, tile_size: Vec2) -> Self {
+        Tilemap {
+            width,
+            height,
+            tile_size,
+            position: Vec2::new(0, 0),
+            layers: Vec::new(),
+            tilesets: Vec::new(),
+            properties: HashMap::new(),
+            source: None,
+            source_path: None,
+            texture_handles: Vec::new(),
+            time: 0.0,
+        }
+    }
+
+    pub fn with_tileset(self, tileset: Tileset) -> Self {
+        let mut x = self;
+        x.tilesets.push(tileset);
+        x
+    }
+
+    pub fn with_layer(self, layer: TileLayer) -> Self {
+        let mut x = self;
+        x.layers.push(layer);
+        x
+    }
+
+    pub fn get_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    pub fn get_mut_layer(&mut self, name: &str) -> Option<&mut TileLayer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    pub fn tileset_for(&self, gid: u32) -> Option<&Tileset> {
+        let gid = gid & GID_MASK;
+        self.tilesets.iter().find(|tileset| tileset.contains(gid))
+    }
+
+    // Custom properties set on a tile in the tileset
+    pub fn tile_properties(&self, gid: u32) -> Option<&HashMap<String, TagValue>> {
+        let gid = gid & GID_MASK;
+        let tileset = self.tileset_for(gid)?;
+        tileset.tile_properties.get(&(gid - tileset.first_gid))
+    }
+
+    // Tile coordinates under a world position, None if it's outside the map
+    pub fn world_to_tile(&self, world: &Vec2) -> Option<(u32, u32)> {
+        let x = (world.x - self.position.x).div_euclid(self.tile_size.x.max(1));
+        let y = (world.y - self.position.y).div_euclid(self.tile_size.y.max(1));
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    pub fn tile_at(&self, layer: &str, world: &Vec2) -> u32 {
+        match (self.get_layer(layer), self.world_to_tile(world)) {
+            (Some(layer), Some((x, y))) => layer.get_tile(x, y),
+            _ => 0,
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+    }
+
+    // Queues only the tiles that are inside the view, camera is the world position of
+    // the top left of the screen
+    pub fn draw(&self, engine_settings: &mut EngineSettings2D, camera: &Vec2, view_size: (u32, u32)) {
+        let tile_width = self.tile_size.x.max(1);
+        let tile_height = self.tile_size.y.max(1);
+
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            let origin_x = self.position.x + layer.offset.x;
+            let origin_y = self.position.y + layer.offset.y;
+
+            let first_x = ((camera.x - origin_x).div_euclid(tile_width)).max(0);
+            let first_y = ((camera.y - origin_y).div_euclid(tile_height)).max(0);
+            // One extra so tiles taller than the grid (or partly visible ones) still show up
+            let last_x = ((camera.x + view_size.0 as i32 - origin_x).div_euclid(tile_width) + 1)
+                .min(layer.width as i32 - 1);
+            let last_y = ((camera.y + view_size.1 as i32 - origin_y).div_euclid(tile_height) + 1)
+                .min(layer.height as i32 - 1);
+
+            for y in first_y..=last_y {
+                for x in first_x..=last_x {
+                    let gid = layer.get_tile(x as u32, y as u32);
+                    if gid == 0 {
+                        continue;
+                    }
+                    let Some(tileset) = self.tileset_for(gid) else {
+                        continue;
+                    };
+                    let Some(texture) = tileset.texture else {
+                        continue;
+                    };
+
+                    let local_id = tileset.animated_tile(gid - tileset.first_gid, self.time);
+                    let (source_position, source_size) = tileset.source_rect(local_id);
+
+                    // Tiles bigger than the grid are anchored to the bottom left like Tiled does
+                    let location = Vec2::new(
+                        origin_x + x * tile_width - camera.x,
+                        origin_y + (y + 1) * tile_height - source_size.y - camera.y,
+                    );
+                    engine_settings.draw_sprite(
+                        VisualSprite::new(location, source_size.clone(), texture)
+                            .with_source(source_position, source_size)
+                            .with_layer(layer.render_layer),
+                    );
+                }
+            }
+        }
+    }
+}
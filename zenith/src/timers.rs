@@ -0,0 +1,410 @@
+// Timers and sequences, both counted in game time so they stop while the environment is
+// paused (unless told otherwise). Either can belong to an entity, they go away with it.
+//
+// A sequence is a list of steps run one after another across frames, a small stand-in
+// for coroutines:
+//     Sequence::new().wait(2.0).during(1.0, slide_in).wait_for_key("space").then(start_level)
+use crate::events::QueuedEvent;
+use crate::scene::{EntityFn, Script};
+use crate::{Entity, EntityId, Instance2D};
+
+// A repeating timer fires at most this many times in one frame, anything past it is dropped
+const MAX_TIMER_FIRES: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SequenceId(u64);
+
+#[derive(Clone)]
+pub(crate) enum Action {
+    Script(Script),
+    Entity(EntityFn), // Runs on the entity the timer or sequence belongs to
+    Event(Box<dyn QueuedEvent>),
+}
+
+#[derive(Clone)]
+pub struct Timer {
+    delay: f32,
+    repeating: bool,
+    elapsed: f32,
+    actions: Vec<Action>,
+    entity: Option<EntityId>,
+    runs_while_paused: bool,
+}
+
+impl Timer {
+    // Fires once after `seconds`
+    pub fn once(seconds: f32) -> Self {
+        Timer {
+            delay: seconds,
+            repeating: false,
+            elapsed: 0.0,
+            actions: Vec::new(),
+            entity: None,
+            runs_while_paused: false,
+        }
+    }
+
+    // Fires every `seconds` until it's cancelled
+    pub fn repeating(seconds: f32) -> Self {
+        let mut x = Self::once(seconds);
+        x.repeating = true;
+        x
+    }
+
+    pub fn with_script(self, script: fn(&mut Instance2D)) -> Self {
+        let mut x = self;
+        x.actions.push(Action::Script(script));
+        x
+    }
+
+    // Needs attached_to, runs on that entity
+    pub fn with_entity_fn(self, function: fn(&mut Entity)) -> Self {
+        let mut x = self;
+        x.actions.push(Action::Entity(function));
+        x
+    }
+
+    pub fn with_event<T: Clone + Send + Sync + 'static>(self, event: T) -> Self {
+        let mut x = self;
+        x.actions.push(Action::Event(Box::new(event)));
+        x
+    }
+
+    // The timer is cancelled when the entity is despawned
+    pub fn attached_to(self, entity: EntityId) -> Self {
+        let mut x = self;
+        x.entity = Some(entity);
+        x
+    }
+
+    pub fn with_runs_while_paused(self) -> Self {
+        let mut x = self;
+        x.runs_while_paused = true;
+        x
+    }
+}
+
+#[derive(Clone)]
+enum Step {
+    Wait(f32),
+    WaitUntil(fn(&Instance2D) -> bool),
+    WaitForKey(String),
+    Run(Action),
+    During(f32, fn(&mut Instance2D, f32)),
+    DuringEntity(f32, fn(&mut Entity, f32)),
+}
+
+#[derive(Clone)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    current: usize,
+    elapsed: f32, // In the current step
+    looping: bool,
+    entity: Option<EntityId>,
+    runs_while_paused: bool,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Sequence {
+            steps: Vec::new(),
+            current: 0,
+            elapsed: 0.0,
+            looping: false,
+            entity: None,
+            runs_while_paused: false,
+        }
+    }
+
+    pub fn wait(self, seconds: f32) -> Self {
+        self.step(Step::Wait(seconds))
+    }
+
+    // Checked once a frame
+    pub fn wait_until(self, condition: fn(&Instance2D) -> bool) -> Self {
+        self.step(Step::WaitUntil(condition))
+    }
+
+    // Key names are the ones Keys::set takes
+    pub fn wait_for_key(self, key: &str) -> Self {
+        self.step(Step::WaitForKey(key.to_string()))
+    }
+
+    pub fn then(self, script: fn(&mut Instance2D)) -> Self {
+        self.step(Step::Run(Action::Script(script)))
+    }
+
+    // Needs attached_to, runs on that entity
+    pub fn then_entity(self, function: fn(&mut Entity)) -> Self {
+        self.step(Step::Run(Action::Entity(function)))
+    }
+
+    pub fn then_send<T: Clone + Send + Sync + 'static>(self, event: T) -> Self {
+        self.step(Step::Run(Action::Event(Box::new(event))))
+    }
+
+    // Calls `function` every frame for `seconds` with how far along it is, 0 to 1. The
+    // last call always gets 1.
+    pub fn during(self, seconds: f32, function: fn(&mut Instance2D, f32)) -> Self {
+        self.step(Step::During(seconds, function))
+    }
+
+    pub fn during_entity(self, seconds: f32, function: fn(&mut Entity, f32)) -> Self {
+        self.step(Step::DuringEntity(seconds, function))
+    }
+
+    // Starts over once the last step is done
+    pub fn looping(self) -> Self {
+        let mut x = self;
+        x.looping = true;
+        x
+    }
+
+    pub fn attached_to(self, entity: EntityId) -> Self {
+        let mut x = self;
+        x.entity = Some(entity);
+        x
+    }
+
+    pub fn with_runs_while_paused(self) -> Self {
+        let mut x = self;
+        x.runs_while_paused = true;
+        x
+    }
+
+    fn step(self, step: Step) -> Self {
+        let mut x = self;
+        x.steps.push(step);
+        x
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Timers {
+    timers: Vec<(TimerId, Timer)>,
+    sequences: Vec<(SequenceId, Sequence)>,
+    running: Vec<SequenceId>, // Taken out while the sequences are being run
+    stopped: Vec<SequenceId>, // Stopped while they were
+    next_id: u64,
+}
+
+impl Timers {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add_timer(&mut self, timer: Timer) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push((id, timer));
+        id
+    }
+
+    pub(crate) fn cancel_timer(&mut self, id: TimerId) -> bool {
+        let before = self.timers.len();
+        self.timers.retain(|(existing, _)| *existing != id);
+        before != self.timers.len()
+    }
+
+    pub(crate) fn timer_remaining(&self, id: TimerId) -> Option<f32> {
+        self.timers
+            .iter()
+            .find(|(existing, _)| *existing == id)
+            .map(|(_, timer)| (timer.delay - timer.elapsed).max(0.0))
+    }
+
+    pub(crate) fn start_sequence(&mut self, sequence: Sequence) -> SequenceId {
+        let id = SequenceId(self.next_id);
+        self.next_id += 1;
+        self.sequences.push((id, sequence));
+        id
+    }
+
+    pub(crate) fn stop_sequence(&mut self, id: SequenceId) -> bool {
+        let running = self.is_sequence_running(id);
+        self.sequences.retain(|(existing, _)| *existing != id);
+        self.stopped.push(id);
+        running
+    }
+
+    pub(crate) fn is_sequence_running(&self, id: SequenceId) -> bool {
+        (self.running.contains(&id) || self.sequences.iter().any(|(existing, _)| *existing == id))
+            && !self.stopped.contains(&id)
+    }
+
+    // Drops whatever belonged to entities that are gone
+    pub(crate) fn retain_entities(&mut self, alive: impl Fn(EntityId) -> bool) {
+        self.timers.retain(|(_, timer)| timer.entity.is_none_or(&alive));
+        self.sequences.retain(|(_, sequence)| sequence.entity.is_none_or(&alive));
+    }
+
+    // Moves the timers on and returns what fired, in the order the timers were added
+    pub(crate) fn advance_timers(&mut self, delta_time: f32, paused: bool) -> Vec<(Option<EntityId>, Action)> {
+        let mut fired = Vec::new();
+        for (_, timer) in self.timers.iter_mut() {
+            if paused && !timer.runs_while_paused {
+                continue;
+            }
+            timer.elapsed += delta_time;
+            // A long frame can fire a short repeating timer more than once
+            let mut fires = 0;
+            while timer.elapsed >= timer.delay {
+                fired.extend(timer.actions.iter().map(|action| (timer.entity, action.clone())));
+                if !timer.repeating {
+                    break;
+                }
+                if timer.delay <= 0.0 {
+                    // Once a frame
+                    timer.elapsed = 0.0;
+                    break;
+                }
+                fires += 1;
+                if fires == MAX_TIMER_FIRES {
+                    // Too far behind to catch up, also stops a delay too small to subtract
+                    // from elapsed from looping forever
+                    timer.elapsed %= timer.delay;
+                    break;
+                }
+                timer.elapsed -= timer.delay;
+            }
+        }
+        self.timers.retain(|(_, timer)| timer.repeating || timer.elapsed < timer.delay);
+        fired
+    }
+}
+
+pub(crate) fn run_action(instance: &mut Instance2D, entity: Option<EntityId>, action: Action) {
+    match action {
+        Action::Script(script) => script(instance),
+        Action::Entity(function) => match entity {
+            Some(id) => instance.environment.run_entity_fn(id, function),
+            None => eprintln!("Error: Entity function has no entity to run on, the timer, sequence or tween needs one"),
+        },
+        Action::Event(event) => event.send(instance.environment.mut_events()),
+    }
+}
+
+pub(crate) fn update_timers(instance: &mut Instance2D) {
+    let environment = &mut instance.environment;
+    let entities = &environment.entities;
+    environment.timers.retain_entities(|id| entities.contains(id));
+    let delta_time = instance.engine_settings.delta_time;
+    let paused = instance.environment.is_paused();
+    let fired = instance.environment.timers.advance_timers(delta_time, paused);
+    for (entity, action) in fired {
+        run_action(instance, entity, action);
+    }
+}
+
+pub(crate) fn update_sequences(instance: &mut Instance2D) {
+    let delta_time = instance.engine_settings.delta_time;
+    let paused = instance.environment.is_paused();
+    // Taken out so the steps can have the whole instance, sequences started by a step
+    // get added to the empty list and run from next frame
+    let timers = &mut instance.environment.timers;
+    let mut sequences = std::mem::take(&mut timers.sequences);
+    timers.running = sequences.iter().map(|(id, _)| *id).collect();
+    timers.stopped.clear();
+
+    sequences.retain_mut(|(id, sequence)| {
+        if paused && !sequence.runs_while_paused {
+            return true;
+        }
+        run_sequence(instance, *id, sequence, delta_time)
+    });
+
+    let timers = &mut instance.environment.timers;
+    let stopped = std::mem::take(&mut timers.stopped);
+    timers.running.clear();
+    sequences.retain(|(id, _)| !stopped.contains(id));
+    sequences.append(&mut timers.sequences);
+    timers.sequences = sequences;
+}
+
+// Runs as many steps as this frame's time covers, returns false once the sequence is done
+fn run_sequence(instance: &mut Instance2D, id: SequenceId, sequence: &mut Sequence, delta_time: f32) -> bool {
+    let mut time = delta_time;
+    // A pass that started at the first step this frame already ran everything once
+    let mut restarted = sequence.current == 0 && sequence.elapsed == 0.0;
+    loop {
+        if instance.environment.timers.stopped.contains(&id) {
+            return false;
+        }
+        if let Some(entity) = sequence.entity {
+            if !instance.environment.contains_entity(entity) {
+                return false;
+            }
+        }
+        let Some(step) = sequence.steps.get(sequence.current).cloned() else {
+            // Only go round once a frame so a sequence with no waits can't hang the game,
+            // it starts over next frame
+            if !sequence.looping || restarted || sequence.steps.is_empty() {
+                return sequence.looping && !sequence.steps.is_empty();
+            }
+            sequence.current = 0;
+            sequence.elapsed = 0.0;
+            restarted = true;
+            continue;
+        };
+
+        let done = match step {
+            Step::Wait(seconds) => {
+                let needed = seconds - sequence.elapsed;
+                if time >= needed {
+                    time -= needed;
+                    true
+                } else {
+                    sequence.elapsed += time;
+                    false
+                }
+            }
+            Step::WaitUntil(condition) => condition(instance),
+            Step::WaitForKey(key) => instance.engine_settings.keys.all_pressed_str().contains(&key.as_str()),
+            Step::Run(action) => {
+                run_action(instance, sequence.entity, action);
+                true
+            }
+            Step::During(seconds, function) => {
+                let (progress, finished) = progress(sequence, seconds, &mut time);
+                function(instance, progress);
+                finished
+            }
+            Step::DuringEntity(seconds, function) => {
+                let (progress, finished) = progress(sequence, seconds, &mut time);
+                match sequence.entity {
+                    Some(entity) => instance
+                        .environment
+                        .run_entity_fn(entity, |entity| function(entity, progress)),
+                    None => eprintln!("Error: Sequence runs an entity function but isn't attached to an entity"),
+                }
+                finished
+            }
+        };
+        if !done {
+            return true;
+        }
+        sequence.current += 1;
+        sequence.elapsed = 0.0;
+    }
+}
+
+fn progress(sequence: &mut Sequence, seconds: f32, time: &mut f32) -> (f32, bool) {
+    let needed = seconds - sequence.elapsed;
+    if *time >= needed {
+        *time -= needed;
+        (1.0, true)
+    } else {
+        sequence.elapsed += *time;
+        *time = 0.0;
+        (sequence.elapsed / seconds, false)
+    }
+}
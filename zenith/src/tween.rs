@@ -0,0 +1,416 @@
+// Tweens move a tag or resource from one value to another over some seconds, shaped by an
+// easing curve. Ints, floats, doubles, colors and Vec2s can be tweened, anything else can
+// go through Tween::entity_fn, which gets the eased progress. Like timers they go by game
+// time and stop when the entity they belong to is despawned.
+//     Tween::tag(id, "location", TagValue::Vec2(Vec2::new(200, 0)), 0.5)
+//         .with_easing(Easing::BackOut)
+//         .then(Tween::tag(id, "scale", TagValue::Float(0.0), 0.2))
+use crate::timers::{run_action, Action};
+use crate::{Entity, EntityId, Instance2D, TagValue};
+
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    BackIn, // Pulls back a little before going
+    BackOut, // Goes a little past the end then settles
+    BackInOut,
+}
+
+impl Easing {
+    // Takes 0 to 1 and gives 0 at 0 and 1 at 1. Elastic and back go outside of 0 to 1 in
+    // between.
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        const BACK: f32 = 1.70158;
+        const BACK_IN_OUT: f32 = BACK * 1.525;
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t).powi(2),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t.powi(3),
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t.powi(3)
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::ElasticIn => {
+                if t == 0.0 || t == 1.0 {
+                    return t;
+                }
+                -(2f32.powf(10.0 * t - 10.0)) * ((10.0 * t - 10.75) * (2.0 * PI / 3.0)).sin()
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    return t;
+                }
+                2f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+            }
+            Easing::ElasticInOut => {
+                if t == 0.0 || t == 1.0 {
+                    return t;
+                }
+                let wave = ((20.0 * t - 11.125) * (2.0 * PI / 4.5)).sin();
+                if t < 0.5 {
+                    -(2f32.powf(20.0 * t - 10.0) * wave) / 2.0
+                } else {
+                    2f32.powf(-20.0 * t + 10.0) * wave / 2.0 + 1.0
+                }
+            }
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+            Easing::BackIn => (BACK + 1.0) * t.powi(3) - BACK * t * t,
+            Easing::BackOut => 1.0 + (BACK + 1.0) * (t - 1.0).powi(3) + BACK * (t - 1.0).powi(2),
+            Easing::BackInOut => {
+                if t < 0.5 {
+                    (2.0 * t).powi(2) * ((BACK_IN_OUT + 1.0) * 2.0 * t - BACK_IN_OUT) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((BACK_IN_OUT + 1.0) * (2.0 * t - 2.0) + BACK_IN_OUT) + 2.0) / 2.0
+                }
+            }
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TweenId(u64);
+
+#[derive(Clone)]
+enum Target {
+    Tag(EntityId, String),
+    Resource(String),
+    EntityFn(EntityId, fn(&mut Entity, f32)),
+}
+
+#[derive(Clone)]
+pub struct Tween {
+    target: Target,
+    from: Option<TagValue>, // Whatever the value is when the tween starts if not given
+    to: Option<TagValue>,
+    duration: f32,
+    easing: Easing,
+    elapsed: f32,
+    repeats: Option<u32>, // Plays left after this one, None is forever
+    yoyo: bool,
+    backwards: bool,
+    on_complete: Vec<Action>,
+    next: Option<Box<Tween>>,
+    runs_while_paused: bool,
+}
+
+impl Tween {
+    fn new(target: Target, to: Option<TagValue>, seconds: f32) -> Self {
+        Tween {
+            target,
+            from: None,
+            to,
+            duration: seconds,
+            easing: Easing::Linear,
+            elapsed: 0.0,
+            repeats: Some(0),
+            yoyo: false,
+            backwards: false,
+            on_complete: Vec::new(),
+            next: None,
+            runs_while_paused: false,
+        }
+    }
+
+    // A tag of the entity, from its value when the tween starts unless with_from is used
+    pub fn tag(entity: EntityId, tag: &str, to: TagValue, seconds: f32) -> Self {
+        Self::new(Target::Tag(entity, tag.to_string()), Some(to), seconds)
+    }
+
+    pub fn resource(name: &str, to: TagValue, seconds: f32) -> Self {
+        Self::new(Target::Resource(name.to_string()), Some(to), seconds)
+    }
+
+    // For anything that isn't a single tag, `function` gets the eased progress every frame
+    pub fn entity_fn(entity: EntityId, seconds: f32, function: fn(&mut Entity, f32)) -> Self {
+        Self::new(Target::EntityFn(entity, function), None, seconds)
+    }
+
+    pub fn with_from(self, from: TagValue) -> Self {
+        let mut x = self;
+        x.from = Some(from);
+        x
+    }
+
+    pub fn with_easing(self, easing: Easing) -> Self {
+        let mut x = self;
+        x.easing = easing;
+        x
+    }
+
+    // Goes back to the start after reaching the end, both ways count as one play
+    pub fn with_yoyo(self) -> Self {
+        let mut x = self;
+        x.yoyo = true;
+        x
+    }
+
+    // Plays `times` more times after the first
+    pub fn with_repeat(self, times: u32) -> Self {
+        let mut x = self;
+        x.repeats = Some(times);
+        x
+    }
+
+    // Never completes, so on_complete and then never run
+    pub fn with_repeat_forever(self) -> Self {
+        let mut x = self;
+        x.repeats = None;
+        x
+    }
+
+    pub fn with_on_complete(self, script: fn(&mut Instance2D)) -> Self {
+        let mut x = self;
+        x.on_complete.push(Action::Script(script));
+        x
+    }
+
+    // Runs on the tweened entity
+    pub fn with_on_complete_entity(self, function: fn(&mut Entity)) -> Self {
+        let mut x = self;
+        x.on_complete.push(Action::Entity(function));
+        x
+    }
+
+    pub fn with_runs_while_paused(self) -> Self {
+        let mut x = self;
+        x.runs_while_paused = true;
+        x
+    }
+
+    // Starts `next` once this one completes, under the same TweenId. Chains can be as long
+    // as you like.
+    pub fn then(self, next: Tween) -> Self {
+        let mut x = self;
+        match x.next.take() {
+            Some(existing) => x.next = Some(Box::new(existing.then(next))),
+            None => x.next = Some(Box::new(next)),
+        }
+        x
+    }
+
+    fn entity(&self) -> Option<EntityId> {
+        match &self.target {
+            Target::Tag(entity, _) | Target::EntityFn(entity, _) => Some(*entity),
+            Target::Resource(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Tweens {
+    tweens: Vec<(TweenId, Tween)>,
+    next_id: u64,
+}
+
+impl Tweens {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add(&mut self, tween: Tween) -> TweenId {
+        let id = TweenId(self.next_id);
+        self.next_id += 1;
+        self.tweens.push((id, tween));
+        id
+    }
+
+    pub(crate) fn cancel(&mut self, id: TweenId) -> bool {
+        let before = self.tweens.len();
+        self.tweens.retain(|(existing, _)| *existing != id);
+        before != self.tweens.len()
+    }
+
+    pub(crate) fn is_running(&self, id: TweenId) -> bool {
+        self.tweens.iter().any(|(existing, _)| *existing == id)
+    }
+}
+
+enum Progress {
+    Running,
+    Completed(Vec<Action>, Option<EntityId>),
+    Failed,
+}
+
+pub(crate) fn update_tweens(instance: &mut Instance2D) {
+    let delta_time = instance.engine_settings.delta_time;
+    let environment = &mut instance.environment;
+    let paused = environment.is_paused();
+    let mut tweens = std::mem::take(&mut environment.tweens.tweens);
+
+    let mut completed = Vec::new();
+    tweens.retain_mut(|(_, tween)| {
+        if paused && !tween.runs_while_paused {
+            return true;
+        }
+        let mut time = delta_time;
+        loop {
+            match advance(environment, tween, time) {
+                Progress::Running => return true,
+                Progress::Completed(actions, entity) => {
+                    completed.extend(actions.into_iter().map(|action| (entity, action)));
+                    let Some(next) = tween.next.take() else {
+                        return false;
+                    };
+                    // The next one gets the time this one didn't need
+                    time = tween.elapsed;
+                    *tween = *next;
+                }
+                Progress::Failed => return false,
+            }
+        }
+    });
+
+    // Callbacks run once the tweens are back so they can add or cancel them
+    let tweens_now = &mut instance.environment.tweens.tweens;
+    tweens.append(tweens_now);
+    *tweens_now = tweens;
+    for (entity, action) in completed {
+        run_action(instance, entity, action);
+    }
+}
+
+fn advance(environment: &mut crate::Environment, tween: &mut Tween, delta_time: f32) -> Progress {
+    if let Some(entity) = tween.entity() {
+        if !environment.contains_entity(entity) {
+            return Progress::Failed;
+        }
+    }
+    if tween.from.is_none() && tween.to.is_some() {
+        let current = match &tween.target {
+            Target::Tag(entity, tag) => environment.get_entity_by_id(*entity).and_then(|entity| entity.get_tag(tag)),
+            Target::Resource(name) => environment.get_resource(name).cloned(),
+            Target::EntityFn(..) => None,
+        };
+        match current {
+            Some(current) => tween.from = Some(current),
+            None => {
+                eprintln!("Error: Can't tween {} - it has no value to start from", describe(&tween.target));
+                return Progress::Failed;
+            }
+        }
+    }
+
+    tween.elapsed += delta_time;
+    loop {
+        if tween.duration > 0.0 && tween.elapsed < tween.duration {
+            let t = tween.elapsed / tween.duration;
+            let t = if tween.backwards { 1.0 - t } else { t };
+            if !write(environment, tween, tween.easing.ease(t)) {
+                return Progress::Failed;
+            }
+            return Progress::Running;
+        }
+
+        // Reached the end of this leg
+        if !write(environment, tween, if tween.backwards { 0.0 } else { 1.0 }) {
+            return Progress::Failed;
+        }
+        tween.elapsed = (tween.elapsed - tween.duration).max(0.0);
+        if tween.yoyo && !tween.backwards {
+            tween.backwards = true;
+            continue;
+        }
+        tween.backwards = false;
+        match tween.repeats {
+            Some(0) => return Progress::Completed(std::mem::take(&mut tween.on_complete), tween.entity()),
+            Some(left) => tween.repeats = Some(left - 1),
+            None => {}
+        }
+        // Zero seconds long, plays once a frame instead of forever
+        if tween.duration <= 0.0 {
+            tween.elapsed = 0.0;
+            return Progress::Running;
+        }
+    }
+}
+
+// False if the value couldn't be worked out or set
+fn write(environment: &mut crate::Environment, tween: &Tween, progress: f32) -> bool {
+    if let Target::EntityFn(entity, function) = &tween.target {
+        environment.run_entity_fn(*entity, |entity| function(entity, progress));
+        return true;
+    }
+    let (Some(from), Some(to)) = (&tween.from, &tween.to) else {
+        return false;
+    };
+    let Some(value) = from.lerp(to, progress) else {
+        eprintln!(
+            "Error: Can't tween {} from {:?} to {:?}",
+            describe(&tween.target),
+            from,
+            to
+        );
+        return false;
+    };
+    match &tween.target {
+        Target::Tag(entity, tag) => {
+            if let Some(entity) = environment.get_mut_entity_by_id(*entity) {
+                entity.set_tag(tag, value);
+            }
+        }
+        Target::Resource(name) => environment.set_resource(name, value),
+        Target::EntityFn(..) => {}
+    }
+    true
+}
+
+fn describe(target: &Target) -> String {
+    match target {
+        Target::Tag(_, tag) => format!("tag {}", tag),
+        Target::Resource(name) => format!("resource {}", name),
+        Target::EntityFn(..) => "entity function".to_string(),
+    }
+}
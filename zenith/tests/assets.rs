@@ -0,0 +1,126 @@
+// The asset store on a temporary folder: sharing loads, freeing unused assets and reloading.
+
+use std::cell::Cell;
+use std::path::PathBuf;
+use zenith::*;
+
+fn folder(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("zenith_assets_{}_{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn write_png(path: PathBuf, width: u32, height: u32, color: [u8; 4]) {
+    let pixels = color.repeat((width * height) as usize);
+    FrameBuffer::from_pixels(width, height, pixels).unwrap().save_png(path).unwrap();
+}
+
+thread_local! {
+    static DRAWN: Cell<Option<TextureId>> = const { Cell::new(None) };
+}
+
+fn draw(instance: &mut Instance2D) {
+    if let Some(texture) = DRAWN.with(|drawn| drawn.get()) {
+        instance
+            .engine_settings
+            .draw_sprite(VisualSprite::new(Vec2::new(0, 0), Vec2::new(10, 10), texture));
+    }
+}
+
+#[test]
+fn loading_twice_shares_one_asset() {
+    let root = folder("shared");
+    std::fs::create_dir_all(root.join("data")).unwrap();
+    std::fs::write(root.join("data/notes.txt"), "hello").unwrap();
+    let mut instance = Instance2D::new_headless();
+    instance.assets.set_pack(AssetPack::disk(&root));
+
+    let first = instance.load_asset::<TextFile>("data/notes.txt").unwrap();
+    let second = instance.load_asset::<TextFile>("./data/../data/notes.txt").unwrap();
+    assert_eq!(instance.assets.len(), 1);
+    assert_eq!(first.path(), second.path());
+    assert_eq!(instance.assets.ref_count(&first), 2);
+
+    // Another type would be an asset of its own, a failed load adds nothing
+    assert!(instance.load_asset::<TiledMap>("data/notes.txt").is_err());
+    assert_eq!(instance.assets.len(), 1);
+
+    let copy = second.clone();
+    assert_eq!(instance.assets.ref_count(&first), 3);
+    drop(second);
+    drop(copy);
+    assert_eq!(instance.assets.ref_count(&first), 1);
+    assert_eq!(instance.assets.unload_unused(&mut instance.engine_settings), 0);
+    assert_eq!(instance.get_asset(&first).unwrap().text, "hello");
+}
+
+#[test]
+fn unused_textures_are_freed() {
+    let root = folder("unused");
+    write_png(root.join("player.png"), 2, 2, [255, 0, 0, 255]);
+    let mut runner = HeadlessRunner::new();
+    runner.instance.environment.add_update_script("draw", draw);
+    runner.instance.screen.set_logical_size(Some((600, 400)));
+    runner.instance.assets.set_pack(AssetPack::disk(&root));
+    let handle = runner.instance.load_asset::<Texture>("player.png").unwrap();
+    let texture = runner.instance.get_asset(&handle).unwrap().id;
+    DRAWN.with(|drawn| drawn.set(Some(texture)));
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(frame.get_pixel(5, 5).unwrap()[..3], [255, 0, 0]);
+
+    drop(handle);
+    let instance = &mut runner.instance;
+    assert_eq!(instance.unload_unused_assets(), 1);
+    assert!(!instance.assets.is_loaded::<Texture>("player.png"));
+    // The texture itself is gone, not just the asset
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(frame.get_pixel(5, 5).unwrap()[..3], [0, 0, 0]);
+}
+
+#[test]
+fn reloaded_textures_keep_their_id() {
+    let root = folder("texture");
+    write_png(root.join("player.png"), 2, 2, [255, 0, 0, 255]);
+    let mut runner = HeadlessRunner::new();
+    runner.instance.environment.add_update_script("draw", draw);
+    runner.instance.screen.set_logical_size(Some((600, 400)));
+    runner.instance.assets.set_pack(AssetPack::disk(&root));
+    let handle = runner.instance.load_asset::<Texture>("player.png").unwrap();
+    let before = *runner.instance.get_asset(&handle).unwrap();
+    DRAWN.with(|drawn| drawn.set(Some(before.id)));
+
+    write_png(root.join("player.png"), 2, 2, [0, 255, 0, 255]);
+    let instance = &mut runner.instance;
+    assert_eq!(instance.assets.reload("player.png", &mut instance.engine_settings), Ok(1));
+    assert_eq!(*instance.get_asset(&handle).unwrap(), before);
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(frame.get_pixel(5, 5).unwrap()[..3], [0, 255, 0]);
+
+    // A different size still goes under the same id
+    write_png(root.join("player.png"), 4, 2, [0, 0, 255, 255]);
+    let instance = &mut runner.instance;
+    instance.assets.reload("player.png", &mut instance.engine_settings).unwrap();
+    let after = *instance.get_asset(&handle).unwrap();
+    assert_eq!((after.id, after.width, after.height), (before.id, 4, 2));
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(frame.get_pixel(5, 5).unwrap()[..3], [0, 0, 255]);
+}
+
+#[test]
+fn failed_reloads_keep_every_type_as_it_was() {
+    let root = folder("failed");
+    let map = r#"{ "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16, "tilesets": [], "layers": [] }"#;
+    std::fs::write(root.join("level.tmj"), map).unwrap();
+    let mut instance = Instance2D::new_headless();
+    instance.assets.set_pack(AssetPack::disk(&root));
+    let text = instance.load_asset::<TextFile>("level.tmj").unwrap();
+    let tiled = instance.load_asset::<TiledMap>("level.tmj").unwrap();
+
+    // Still text, but not a map anymore
+    std::fs::write(root.join("level.tmj"), "{").unwrap();
+    assert!(instance.assets.reload("level.tmj", &mut instance.engine_settings).is_err());
+    assert_eq!(instance.get_asset(&text).unwrap().text, map);
+    assert_eq!(instance.get_asset(&tiled).unwrap().tilemap.width, 1);
+    assert!(instance.assets.reloaded().is_empty());
+}
@@ -0,0 +1,157 @@
+// The mixer on the null device, and positional sounds following their entity.
+
+use zenith::*;
+
+fn tone(seconds: f32) -> Sound {
+    let samples = vec![0.5; (44_100.0 * seconds) as usize];
+    Sound::from_samples(1, 44_100, &samples)
+}
+
+// Left and right of the first frame of the next block
+fn first_frame(audio: &mut Audio) -> (f32, f32) {
+    let mut out = [0.0; 64];
+    audio.mix(&mut out);
+    (out[0], out[1])
+}
+
+fn mixed(audio: &mut Audio, frames: usize) -> Vec<f32> {
+    let mut out = vec![0.0; frames * 2];
+    audio.mix(&mut out);
+    out
+}
+
+#[test]
+fn sounds_are_added_together_then_clamped() {
+    let mut audio = Audio::new_null();
+    assert!(audio.is_null());
+    let sound = tone(1.0);
+    let left = PlaySettings {
+        pan: -1.0,
+        ..PlaySettings::default()
+    };
+    audio.play_with(&sound, left);
+    assert_eq!(first_frame(&mut audio), (0.5, 0.0));
+
+    let quiet = PlaySettings {
+        volume: 0.5,
+        ..PlaySettings::default()
+    };
+    audio.play_with(&sound, quiet);
+    assert_eq!(first_frame(&mut audio), (0.75, 0.25));
+
+    audio.play(&sound);
+    assert_eq!(first_frame(&mut audio), (1.0, 0.75));
+    audio.set_master_volume(0.5);
+    assert_eq!(first_frame(&mut audio), (0.625, 0.375));
+    audio.set_muted(true);
+    assert_eq!(first_frame(&mut audio), (0.0, 0.0));
+}
+
+#[test]
+fn channels_stop_at_the_end_unless_they_loop() {
+    let mut audio = Audio::new_null();
+    let short = Sound::from_samples(1, 44_100, &[0.5, 0.5, 0.5]);
+    let once = audio.play(&short).unwrap();
+    let looping = PlaySettings {
+        looping: true,
+        volume: 0.25,
+        ..PlaySettings::default()
+    };
+    let again = audio.play_with(&short, looping).unwrap();
+    let out = mixed(&mut audio, 10);
+    assert_eq!(out[..2], [0.625, 0.625]);
+    assert_eq!(out[18..], [0.125, 0.125]);
+    assert!(!audio.is_playing(once));
+    assert!(audio.is_playing(again));
+
+    // Paused channels keep their place
+    audio.set_paused(again, true);
+    assert_eq!(mixed(&mut audio, 1), [0.0, 0.0]);
+    audio.set_paused(again, false);
+    assert_eq!(mixed(&mut audio, 1), [0.125, 0.125]);
+
+    // Stopped ids stay stopped even when the channel gets used again
+    audio.stop(again);
+    let next = audio.play(&tone(1.0)).unwrap();
+    assert!(!audio.is_playing(again));
+    audio.set_volume(again, 0.0);
+    assert_eq!(first_frame(&mut audio), (0.5, 0.5));
+    assert!(audio.is_playing(next));
+}
+
+#[test]
+fn there_are_only_so_many_channels() {
+    let mut audio = Audio::new_null();
+    let sound = tone(1.0);
+    let playing: Vec<ChannelId> = std::iter::from_fn(|| audio.play(&sound)).take(100).collect();
+    assert_eq!(playing.len(), 32);
+    audio.stop(playing[3]);
+    assert!(audio.play(&sound).is_some());
+    assert!(audio.play(&sound).is_none());
+    audio.stop_all();
+    assert!(playing.iter().all(|channel| !audio.is_playing(*channel)));
+    assert_eq!(first_frame(&mut audio), (0.0, 0.0));
+}
+
+#[test]
+fn fading_out_ends_the_channel() {
+    let mut audio = Audio::new_null();
+    let channel = audio.play(&tone(1.0)).unwrap();
+    audio.fade_out(channel, 0.01);
+    let out = mixed(&mut audio, 1000);
+    // Quieter every frame until it's gone
+    assert!(out.chunks(2).zip(out.chunks(2).skip(1)).all(|(a, b)| b[0] <= a[0]));
+    assert_eq!(out[out.len() - 1], 0.0);
+    assert!(!audio.is_playing(channel));
+}
+
+fn speaker(x: i32) -> Entity {
+    Entity::new()
+        .with_name_tag("speaker")
+        .with_tag("location", TagValue::Vec2(Vec2::new(x, 200)))
+}
+
+#[test]
+fn positional_sounds_follow_their_own_entity() {
+    let mut runner = HeadlessRunner::new();
+    let environment = &mut runner.instance.environment;
+    // Same name, one in the middle of the screen and one far off to the right
+    let near = environment.add_entity(speaker(300));
+    let far = environment.add_entity(speaker(5000));
+    let sound = tone(1.0);
+    let audio = &mut runner.instance.audio;
+    let quiet = audio.play_at(&sound, far, PlaySettings::default(), Attenuation::new(50.0, 600.0)).unwrap();
+    runner.run_frames(1).unwrap();
+    assert_eq!(first_frame(&mut runner.instance.audio), (0.0, 0.0));
+
+    let loud = runner
+        .instance
+        .audio
+        .play_at(&sound, near, PlaySettings::default(), Attenuation::new(50.0, 600.0))
+        .unwrap();
+    runner.run_frames(1).unwrap();
+    assert_eq!(first_frame(&mut runner.instance.audio), (0.5, 0.5));
+
+    // Gone with the entity
+    runner.instance.environment.despawn(far);
+    runner.run_frames(1).unwrap();
+    assert!(!runner.instance.audio.is_playing(quiet));
+    assert!(runner.instance.audio.is_playing(loud));
+}
+
+#[test]
+fn a_listener_entity_that_is_gone_hears_from_the_camera() {
+    let mut runner = HeadlessRunner::new();
+    let environment = &mut runner.instance.environment;
+    let source = environment.add_entity(speaker(300));
+    let listener = environment.add_entity(speaker(5000));
+    let audio = &mut runner.instance.audio;
+    audio.set_listener(Listener::Entity(listener));
+    audio.play_at(&tone(1.0), source, PlaySettings::default(), Attenuation::new(50.0, 600.0));
+    runner.run_frames(1).unwrap();
+    assert_eq!(first_frame(&mut runner.instance.audio), (0.0, 0.0));
+
+    runner.instance.environment.despawn(listener);
+    runner.run_frames(1).unwrap();
+    assert_eq!(first_frame(&mut runner.instance.audio), (0.5, 0.5));
+}
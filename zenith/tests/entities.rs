@@ -0,0 +1,73 @@
+// Finding entities by name after they are renamed, removed or share a name.
+
+use zenith::*;
+
+fn named(name: &str, hp: i32) -> Entity {
+    Entity::new().with_name_tag(name).with_tag("hp", TagValue::Int(hp))
+}
+
+fn hp(environment: &Environment, name: &str) -> Option<i32> {
+    environment.get_entity(name).and_then(|entity| entity.get_tag("hp")).and_then(|tag| tag.extract_int())
+}
+
+fn rename(entity: &mut Entity, name: &str) {
+    entity.set_tag("name", TagValue::String(name.to_string()));
+}
+
+#[test]
+fn renamed_entities_are_found_by_their_new_name() {
+    let mut environment = Environment::new();
+    let id = environment.add_entity(named("goblin", 1));
+    rename(environment.get_mut_entity_by_id(id).unwrap(), "orc");
+    // Looked up before anything else changes the environment
+    assert_eq!(hp(&environment, "orc"), Some(1));
+    assert_eq!(hp(&environment, "goblin"), None);
+
+    environment.add_entity(named("goblin", 2));
+    assert_eq!(hp(&environment, "goblin"), Some(2));
+    assert_eq!(environment.get_entity_id("orc"), Some(id));
+
+    rename(environment.get_mut_entity("orc").unwrap(), "troll");
+    assert_eq!(hp(&environment, "troll"), Some(1));
+    rename(&mut environment.mut_entities()[0], "ogre");
+    assert_eq!(hp(&environment, "ogre"), Some(1));
+    assert_eq!(hp(&environment, "troll"), None);
+}
+
+#[test]
+fn shared_names_find_the_first_one_added() {
+    let mut environment = Environment::new();
+    let first = environment.add_entity(named("slime", 1));
+    let second = environment.add_entity(named("bat", 2));
+    let third = environment.add_entity(named("bat", 3));
+    assert_eq!(hp(&environment, "slime"), Some(1));
+    assert_eq!(hp(&environment, "bat"), Some(2));
+
+    // Renamed into a name that's taken, it comes first since it was added first
+    rename(environment.get_mut_entity_by_id(first).unwrap(), "bat");
+    assert_eq!(hp(&environment, "bat"), Some(1));
+    environment.add_entity(named("rat", 4));
+    assert_eq!(hp(&environment, "bat"), Some(1));
+
+    environment.remove_entity("bat");
+    assert_eq!(environment.get_entity_id("bat"), Some(second));
+    environment.remove_entity("bat");
+    assert_eq!(environment.get_entity_id("bat"), Some(third));
+    environment.remove_entity("bat");
+    assert_eq!(hp(&environment, "bat"), None);
+}
+
+#[test]
+fn removed_names_can_be_used_again() {
+    let mut environment = Environment::new();
+    let id = environment.add_unique_entity(named("boss", 1)).unwrap();
+    assert!(environment.add_unique_entity(named("boss", 2)).is_err());
+    environment.despawn(id);
+    assert_eq!(hp(&environment, "boss"), None);
+    environment.add_unique_entity(named("boss", 3)).unwrap();
+    assert_eq!(hp(&environment, "boss"), Some(3));
+
+    rename(environment.get_mut_entity("boss").unwrap(), "minion");
+    environment.add_unique_entity(named("boss", 4)).unwrap();
+    assert_eq!(hp(&environment, "boss"), Some(4));
+}
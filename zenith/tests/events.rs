@@ -0,0 +1,148 @@
+// How long events are kept, read_new cursors, and the engine's key and collision events.
+
+use zenith::*;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Hit(u32);
+
+#[test]
+fn events_are_kept_for_the_frame_they_were_sent_and_the_next() {
+    let mut events = Events::new();
+    assert!(events.is_empty::<Hit>());
+    events.send(Hit(1));
+    assert_eq!(events.peek::<Hit>(), [&Hit(1)]);
+
+    events.update();
+    events.send(Hit(2));
+    assert_eq!(events.peek::<Hit>(), [&Hit(1), &Hit(2)]);
+
+    events.update();
+    assert_eq!(events.peek::<Hit>(), [&Hit(2)]);
+    events.update();
+    assert!(events.peek::<Hit>().is_empty());
+    assert!(events.is_empty::<Hit>());
+
+    // Clearing drops both frames straight away and leaves other types alone
+    events.send(Hit(3));
+    events.send(KeyPressed { key: "a".to_string() });
+    events.update();
+    events.send(Hit(4));
+    events.clear::<Hit>();
+    assert!(events.is_empty::<Hit>());
+    assert_eq!(events.peek::<KeyPressed>().len(), 1);
+}
+
+#[test]
+fn read_new_gives_each_reader_every_event_once() {
+    let mut events = Events::new();
+    events.send(Hit(1));
+    events.send(Hit(2));
+    assert_eq!(events.read_new::<Hit>("ui"), [&Hit(1), &Hit(2)]);
+    assert!(events.read_new::<Hit>("ui").is_empty());
+
+    events.update();
+    events.send(Hit(3));
+    // Last frame's events are still there for a reader that hasn't had them
+    assert_eq!(events.read_new::<Hit>("sound"), [&Hit(1), &Hit(2), &Hit(3)]);
+    assert_eq!(events.read_new::<Hit>("ui"), [&Hit(3)]);
+
+    events.update();
+    events.update();
+    events.send(Hit(4));
+    assert_eq!(events.read_new::<Hit>("ui"), [&Hit(4)]);
+    // A reader that skipped two frames only gets what's still kept
+    events.update();
+    events.send(Hit(5));
+    assert_eq!(events.read_new::<Hit>("sound"), [&Hit(4), &Hit(5)]);
+    assert!(events.read_new::<KeyPressed>("sound").is_empty());
+}
+
+fn read_hits(events: &mut Events) -> Vec<Hit> {
+    events.read::<Hit>().into_iter().cloned().collect()
+}
+
+#[test]
+fn read_gives_each_call_every_event_once() {
+    let mut events = Events::new();
+    events.send(Hit(1));
+    assert_eq!(read_hits(&mut events), [Hit(1)]);
+    assert!(read_hits(&mut events).is_empty());
+    // A different call has its own place in the channel
+    assert_eq!(events.read::<Hit>(), [&Hit(1)]);
+
+    events.update();
+    events.send(Hit(2));
+    assert_eq!(read_hits(&mut events), [Hit(2)]);
+    events.update();
+    events.send(Hit(3));
+    assert_eq!(read_hits(&mut events), [Hit(3)]);
+    // Reading doesn't take them away from anyone else
+    assert_eq!(events.peek::<Hit>(), [&Hit(2), &Hit(3)]);
+    assert_eq!(events.read_new::<Hit>("ui"), [&Hit(2), &Hit(3)]);
+}
+
+fn count(instance: &mut Instance2D, name: &str, by: usize) {
+    let before = instance.environment.get_resource(name).and_then(|tag| tag.extract_int()).unwrap_or(0);
+    instance.environment.set_resource(name, TagValue::Int(before + by as i32));
+}
+
+fn count_keys(instance: &mut Instance2D) {
+    let events = instance.environment.mut_events();
+    let pressed = events.read_new::<KeyPressed>("count_keys").len();
+    let released = events.read_new::<KeyReleased>("count_keys").len();
+    count(instance, "pressed", pressed);
+    count(instance, "released", released);
+}
+
+fn count_collisions(instance: &mut Instance2D) {
+    let collisions = instance.environment.mut_events().read::<Collision>().len();
+    count(instance, "update collisions", collisions);
+}
+
+fn count_late_collisions(instance: &mut Instance2D) {
+    let collisions = instance.environment.mut_events().read_new::<Collision>("late").len();
+    count(instance, "late collisions", collisions);
+}
+
+fn counted(runner: &HeadlessRunner, name: &str) -> i32 {
+    runner
+        .instance
+        .environment
+        .get_resource(name)
+        .and_then(|tag| tag.extract_int())
+        .unwrap_or(0)
+}
+
+#[test]
+fn keys_send_one_event_when_pressed_and_one_when_released() {
+    let mut runner = HeadlessRunner::new().press(1, "space").release(4, "space").press(6, "space");
+    runner.instance.environment.add_update_script("count_keys", count_keys);
+
+    runner.run_frames(4).unwrap();
+    // Held for three frames but only pressed once
+    assert_eq!((counted(&runner, "pressed"), counted(&runner, "released")), (1, 0));
+    runner.run_frames(2).unwrap();
+    assert_eq!((counted(&runner, "pressed"), counted(&runner, "released")), (1, 1));
+    runner.run_frames(3).unwrap();
+    assert_eq!((counted(&runner, "pressed"), counted(&runner, "released")), (2, 1));
+}
+
+#[test]
+fn collisions_are_handled_once_whichever_stage_reads_them() {
+    let mut runner = HeadlessRunner::new();
+    let environment = &mut runner.instance.environment;
+    for x in [0, 5] {
+        environment.add_entity(
+            Entity::new()
+                .with_tag("location", TagValue::Vec2(Vec2::new(x, 0)))
+                .with_tag("collider", TagValue::Vec2(Vec2::new(10, 10))),
+        );
+    }
+    environment.add_update_script("count_collisions", count_collisions);
+    environment.add_late_update_script("count_late_collisions", count_late_collisions);
+
+    runner.run_frames(5).unwrap();
+    // Collisions are sent after the Update scripts, so they see each one a frame later
+    assert_eq!(counted(&runner, "late collisions"), 5);
+    assert_eq!(counted(&runner, "update collisions"), 4);
+}
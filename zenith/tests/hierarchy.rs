@@ -0,0 +1,177 @@
+// Parents are held by id, so entities that share a name (two of the same prefab) keep
+// their own children.
+
+use zenith::*;
+
+fn at(name: &str, x: i32, y: i32) -> Entity {
+    Entity::new()
+        .with_name_tag(name)
+        .with_tag("location", TagValue::Vec2(Vec2::new(x, y)))
+}
+
+fn location(environment: &Environment, id: EntityId) -> Vec2 {
+    environment.get_entity_by_id(id).unwrap().get_tag("location").unwrap().extract_vec2().unwrap()
+}
+
+fn move_to(environment: &mut Environment, id: EntityId, x: i32, y: i32) {
+    environment
+        .get_mut_entity_by_id(id)
+        .unwrap()
+        .set_tag("location", TagValue::Vec2(Vec2::new(x, y)));
+}
+
+#[test]
+fn children_follow_their_own_parent_when_names_repeat() {
+    let mut runner = HeadlessRunner::new();
+    let environment = &mut runner.instance.environment;
+    let first_tank = environment.add_entity(at("tank", 100, 100));
+    let first_turret = environment.add_entity(at("turret", 110, 100));
+    let second_tank = environment.add_entity(at("tank", 200, 200));
+    let second_turret = environment.add_entity(at("turret", 210, 200));
+    environment.set_parent_by_id(first_turret, Some(first_tank)).unwrap();
+    environment.set_parent_by_id(second_turret, Some(second_tank)).unwrap();
+    runner.run_frames(1).unwrap();
+
+    let environment = &mut runner.instance.environment;
+    move_to(environment, first_tank, 0, 0);
+    move_to(environment, second_tank, 300, 300);
+    runner.run_frames(1).unwrap();
+
+    let environment = &runner.instance.environment;
+    assert_eq!(location(environment, first_turret), Vec2::new(10, 0));
+    assert_eq!(location(environment, second_turret), Vec2::new(310, 300));
+    assert_eq!(environment.get_children_by_id(second_tank), [second_turret]);
+    assert_eq!(environment.get_parent_by_id(first_turret), Some(first_tank));
+}
+
+#[test]
+fn grandchildren_move_with_the_root() {
+    let mut runner = HeadlessRunner::new();
+    let environment = &mut runner.instance.environment;
+    // Added child first so the order in the store doesn't match the hierarchy
+    let grandchild = environment.add_entity(at("grandchild", 20, 0));
+    let child = environment.add_entity(at("child", 10, 0));
+    let root = environment.add_entity(at("root", 0, 0));
+    environment.set_parent_by_id(grandchild, Some(child)).unwrap();
+    environment.set_parent_by_id(child, Some(root)).unwrap();
+    runner.run_frames(1).unwrap();
+
+    move_to(&mut runner.instance.environment, root, 100, 50);
+    runner.run_frames(1).unwrap();
+
+    let environment = &runner.instance.environment;
+    assert_eq!(location(environment, child), Vec2::new(110, 50));
+    assert_eq!(location(environment, grandchild), Vec2::new(120, 50));
+    assert_eq!(environment.get_descendants_by_id(root), [child, grandchild]);
+    assert_eq!(environment.get_ancestors_by_id(grandchild), [child, root]);
+}
+
+#[test]
+fn parents_can_not_loop() {
+    let mut environment = Environment::new();
+    let a = environment.add_entity(at("a", 0, 0));
+    let b = environment.add_entity(at("b", 0, 0));
+    environment.set_parent_by_id(b, Some(a)).unwrap();
+    assert!(environment.set_parent_by_id(a, Some(b)).is_err());
+    assert!(environment.set_parent_by_id(a, Some(a)).is_err());
+
+    environment.set_parent_by_id(b, None).unwrap();
+    assert_eq!(environment.get_parent_by_id(b), None);
+    environment.set_parent_by_id(a, Some(b)).unwrap();
+}
+
+#[test]
+fn scenes_keep_parents_by_position() {
+    let mut environment = Environment::new();
+    let first_tank = environment.add_entity(at("tank", 0, 0));
+    let second_tank = environment.add_entity(at("tank", 100, 0));
+    let turret = environment.add_entity(at("turret", 100, 0));
+    environment.set_parent_by_id(turret, Some(second_tank)).unwrap();
+
+    let scene = environment.to_scene().unwrap();
+    assert_eq!(scene.entities[2].parent, Some(1));
+
+    let mut loaded = Environment::new();
+    loaded.load_scene(&scene).unwrap();
+    let ids = loaded.list_entity_ids();
+    assert_eq!(loaded.get_parent_by_id(ids[2]), Some(ids[1]));
+    assert!(loaded.get_children_by_id(ids[0]).is_empty());
+    assert!(environment.get_children_by_id(first_tank).is_empty());
+}
+
+#[test]
+fn old_scenes_with_parent_tags_still_load() {
+    let mut scene = Scene::default();
+    for (name, parent) in [("tank", None), ("turret", Some("tank"))] {
+        let mut entity = SceneEntity::default();
+        entity.tags.insert("name".to_string(), TagValue::String(name.to_string()));
+        if let Some(parent) = parent {
+            entity.tags.insert("parent".to_string(), TagValue::String(parent.to_string()));
+        }
+        scene.entities.push(entity);
+    }
+
+    let mut environment = Environment::new();
+    environment.load_scene(&scene).unwrap();
+    assert_eq!(environment.get_parent("turret"), Some("tank".to_string()));
+    assert!(environment.get_entity("turret").unwrap().get_tag("parent").is_none());
+}
+
+fn register_tank(environment: &mut Environment) {
+    let prefabs = environment.mut_prefabs();
+    prefabs.register(
+        "turret",
+        Prefab::new()
+            .with_tag("name", TagValue::String("turret".to_string()))
+            .with_tag("location", TagValue::Vec2(Vec2::new(10, 0))),
+    );
+    prefabs.register(
+        "tank",
+        Prefab::new()
+            .with_tag("name", TagValue::String("tank".to_string()))
+            .with_child("turret", &[]),
+    );
+}
+
+#[test]
+fn prefab_children_belong_to_the_entity_they_were_spawned_with() {
+    let mut runner = HeadlessRunner::new();
+    let environment = &mut runner.instance.environment;
+    register_tank(environment);
+    let first_tank = environment
+        .spawn_prefab("tank", &[("location", TagValue::Vec2(Vec2::new(0, 0)))])
+        .unwrap();
+    let second_tank = environment
+        .spawn_prefab("tank", &[("location", TagValue::Vec2(Vec2::new(100, 100)))])
+        .unwrap();
+    let [first_turret] = environment.get_children_by_id(first_tank)[..] else {
+        panic!("first tank should have one turret");
+    };
+    let [second_turret] = environment.get_children_by_id(second_tank)[..] else {
+        panic!("second tank should have one turret");
+    };
+    assert_ne!(first_turret, second_turret);
+    assert_eq!(location(environment, second_turret), Vec2::new(110, 100));
+
+    move_to(environment, first_tank, 50, 50);
+    runner.run_frames(1).unwrap();
+    let environment = &runner.instance.environment;
+    assert_eq!(location(environment, first_turret), Vec2::new(60, 50));
+    assert_eq!(location(environment, second_turret), Vec2::new(110, 100));
+}
+
+#[test]
+fn despawning_a_parent_leaves_others_with_the_same_name_alone() {
+    let mut environment = Environment::new();
+    let first_tank = environment.add_entity(at("tank", 0, 0));
+    let first_turret = environment.add_entity(at("turret", 0, 0));
+    let second_tank = environment.add_entity(at("tank", 0, 0));
+    let second_turret = environment.add_entity(at("turret", 0, 0));
+    environment.set_parent_by_id(first_turret, Some(first_tank)).unwrap();
+    environment.set_parent_by_id(second_turret, Some(second_tank)).unwrap();
+
+    assert_eq!(environment.despawn(first_tank), 2);
+    assert!(!environment.contains_entity(first_turret));
+    assert!(environment.contains_entity(second_tank));
+    assert!(environment.contains_entity(second_turret));
+}
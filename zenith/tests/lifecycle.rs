@@ -0,0 +1,231 @@
+// Order the entity hooks run in. These drive the environment directly, the event loop
+// calls start_entities once at startup and update_entities + apply_commands every frame.
+
+use std::cell::RefCell;
+use zenith::*;
+
+thread_local! {
+    static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn log(entity: &Entity, hook: &str) {
+    LOG.with(|log| log.borrow_mut().push(format!("{} {}", entity.get_name(), hook)));
+}
+
+fn take_log() -> Vec<String> {
+    LOG.with(|log| log.borrow_mut().drain(..).collect())
+}
+
+fn on_spawn(entity: &mut Entity) {
+    log(entity, "spawn");
+}
+
+fn on_start(entity: &mut Entity) {
+    log(entity, "start");
+}
+
+fn on_update(entity: &mut Entity) {
+    log(entity, "update");
+}
+
+fn on_late_update(entity: &mut Entity) {
+    log(entity, "late update");
+}
+
+fn on_destroy(entity: &mut Entity) {
+    log(entity, "destroy");
+}
+
+fn logged(name: &str) -> Entity {
+    Entity::new()
+        .with_name_tag(name)
+        .with_named_spawn_fn("spawn", on_spawn)
+        .with_named_start_fn("start", on_start)
+        .with_named_update_fn("update", on_update)
+        .with_named_late_update_fn("late update", on_late_update)
+        .with_named_destroy_fn("destroy", on_destroy)
+}
+
+#[test]
+fn entities_without_hooks_are_kept() {
+    let mut environment = Environment::new();
+    let wall = environment.add_entity(Entity::new().with_name_tag("wall"));
+    let only_start = environment.add_entity(Entity::new().with_name_tag("sign").with_start_fn(|_| {}));
+    environment.start_entities();
+    for _ in 0..3 {
+        environment.update_entities();
+        environment.apply_commands();
+    }
+    assert!(environment.contains_entity(wall));
+    assert!(environment.contains_entity(only_start));
+    assert_eq!(environment.list_entities().len(), 2);
+}
+
+#[test]
+fn hooks_run_in_order() {
+    take_log();
+    let mut environment = Environment::new();
+    environment.add_entity(logged("a"));
+    environment.add_entity(logged("b"));
+    assert_eq!(take_log(), ["a spawn", "b spawn"]);
+
+    environment.start_entities();
+    environment.update_entities();
+    environment.update_entities();
+    assert_eq!(
+        take_log(),
+        [
+            "a start",
+            "b start",
+            "a update",
+            "b update",
+            "a late update",
+            "b late update",
+            "a update",
+            "b update",
+            "a late update",
+            "b late update",
+        ]
+    );
+}
+
+#[test]
+fn entities_spawned_mid_game_start_before_their_first_update() {
+    take_log();
+    let mut environment = Environment::new();
+    environment.add_entity(logged("a"));
+    environment.start_entities();
+    take_log();
+
+    environment.commands().spawn(logged("b"));
+    environment.update_entities();
+    environment.apply_commands();
+    assert_eq!(take_log(), ["a update", "a late update", "b spawn"]);
+
+    environment.update_entities();
+    assert_eq!(take_log(), ["b start", "a update", "b update", "a late update", "b late update"]);
+}
+
+#[test]
+fn destroy_runs_for_children_and_can_queue_commands() {
+    take_log();
+    let mut environment = Environment::new();
+    let parent = environment.add_entity(logged("parent"));
+    let child = environment.add_entity(logged("child"));
+    environment.set_parent_by_id(child, Some(parent)).unwrap();
+    environment.add_entity(
+        Entity::new()
+            .with_name_tag("crate")
+            .with_destroy_fn(|entity| entity.commands().spawn(Entity::new().with_name_tag("debris"))),
+    );
+    take_log();
+
+    assert_eq!(environment.despawn(parent), 2);
+    assert_eq!(take_log(), ["parent destroy", "child destroy"]);
+    assert!(environment.get_entity_by_id(parent).is_none());
+
+    environment.remove_entity("crate");
+    assert!(environment.get_entity("debris").is_none());
+    environment.apply_commands();
+    assert!(environment.get_entity("debris").is_some());
+}
+
+#[test]
+fn despawning_itself_from_update() {
+    let mut environment = Environment::new();
+    let bullet = environment.add_entity(
+        Entity::new()
+            .with_name_tag("bullet")
+            .with_tag("life", TagValue::Int(2))
+            .with_update_fn(|entity| {
+                let life = entity.get_tag("life").and_then(|tag| tag.extract_int()).unwrap_or(0) - 1;
+                entity.set_tag("life", TagValue::Int(life));
+                if life <= 0 {
+                    entity.commands().despawn_self();
+                }
+            }),
+    );
+    environment.update_entities();
+    environment.apply_commands();
+    assert!(environment.contains_entity(bullet));
+    environment.update_entities();
+    environment.apply_commands();
+    assert!(!environment.contains_entity(bullet));
+}
+
+#[test]
+fn scenes_keep_every_hook() {
+    take_log();
+    let mut environment = Environment::new();
+    environment.register_entity_fn("spawn", on_spawn);
+    environment.register_entity_fn("start", on_start);
+    environment.register_entity_fn("update", on_update);
+    environment.register_entity_fn("late update", on_late_update);
+    environment.register_entity_fn("destroy", on_destroy);
+    environment.add_entity(logged("a"));
+    let scene = environment.to_scene().unwrap();
+
+    let mut loaded = Environment::new();
+    loaded.register_entity_fn("spawn", on_spawn);
+    loaded.register_entity_fn("start", on_start);
+    loaded.register_entity_fn("update", on_update);
+    loaded.register_entity_fn("late update", on_late_update);
+    loaded.register_entity_fn("destroy", on_destroy);
+    take_log();
+    loaded.load_scene(&scene).unwrap();
+    loaded.update_entities();
+    assert_eq!(take_log(), ["a spawn", "a start", "a update", "a late update"]);
+
+    // Loaded entities keep their names so they can be saved again
+    assert_eq!(loaded.to_scene().unwrap().entities, scene.entities);
+
+    loaded.load_scene(&Scene::default()).unwrap();
+    assert_eq!(take_log(), ["a destroy"]);
+}
+
+#[test]
+fn functions_are_saved_by_the_name_they_were_given() {
+    let mut environment = Environment::new();
+    // The same function under two names
+    environment.register_entity_fn("walk", on_update);
+    environment.register_entity_fn("run", on_update);
+    environment.add_entity(Entity::new().with_named_update_fn("walk", on_update));
+    environment.add_entity(Entity::new().with_named_update_fn("run", on_update));
+    let scene = environment.to_scene().unwrap();
+    let names: Vec<Option<String>> = scene.entities.iter().map(|entity| entity.update.clone()).collect();
+    assert_eq!(names, [Some("walk".to_string()), Some("run".to_string())]);
+
+    // Without a name, or with one that was never registered, there's nothing to save it as
+    let mut environment = Environment::new();
+    environment.register_entity_fn("update", on_update);
+    environment.add_entity(Entity::new().with_name_tag("anonymous").with_update_fn(on_update));
+    let error = environment.to_scene().err().unwrap();
+    assert!(error.contains("anonymous"), "{}", error);
+
+    let mut environment = Environment::new();
+    environment.add_entity(Entity::new().with_named_update_fn("update", on_update));
+    assert!(environment.to_scene().is_err());
+
+    // Replacing a named function drops the name
+    let mut environment = Environment::new();
+    environment.register_entity_fn("update", on_update);
+    environment.add_entity(Entity::new().with_named_update_fn("update", on_update).with_update_fn(on_start));
+    assert!(environment.to_scene().is_err());
+}
+
+#[test]
+fn copies_of_an_entity_do_not_copy_its_commands() {
+    let mut template = Entity::new().with_name_tag("spawner").with_update_fn(|_| {});
+    template.commands().spawn(Entity::new().with_name_tag("spawned"));
+    let mut copy = template.clone();
+    assert!(copy.commands().is_empty());
+    assert_eq!(template.commands().len(), 1);
+
+    let mut environment = Environment::new();
+    environment.add_entity(Entity::new().with_name_tag("spawner"));
+    environment.overwrite("spawner", &mut template);
+    environment.overwrite("spawner", &mut template);
+    environment.update_entities();
+    environment.apply_commands();
+    assert!(environment.get_entity_id("spawned").is_none());
+}
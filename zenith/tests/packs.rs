@@ -0,0 +1,60 @@
+// Asset archives: writing a folder out and reading it back, and archives that are broken.
+
+use std::path::{Path, PathBuf};
+use zenith::*;
+
+fn folder(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("zenith_packs_{}_{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("assets/maps")).unwrap();
+    std::fs::write(root.join("assets/notes.txt"), "hello").unwrap();
+    std::fs::write(root.join("assets/maps/level.bin"), [0, 1, 2, 255]).unwrap();
+    std::fs::write(root.join("assets/empty"), []).unwrap();
+    root
+}
+
+fn archive(root: &Path) -> Vec<u8> {
+    let output = root.join("assets.zpak");
+    assert_eq!(AssetPack::write_archive(root.join("assets"), &output), Ok(3));
+    std::fs::read(output).unwrap()
+}
+
+#[test]
+fn archives_read_back_what_was_written() {
+    let root = folder("round_trip");
+    let pack = AssetPack::from_archive_bytes(&archive(&root)).unwrap();
+    assert!(!pack.is_disk());
+    assert_eq!(pack.files(), ["empty", "maps/level.bin", "notes.txt"]);
+    assert_eq!(pack.read_to_string("notes.txt"), Ok("hello".to_string()));
+    assert_eq!(pack.read("./maps/../maps/level.bin"), Ok(vec![0, 1, 2, 255]));
+    assert_eq!(pack.read("empty"), Ok(Vec::new()));
+    assert!(!pack.exists("missing.txt"));
+    assert!(pack.read("missing.txt").is_err());
+
+    // Opening the file gives the same pack
+    let opened = AssetPack::open_archive(root.join("assets.zpak")).unwrap();
+    assert_eq!(opened.files(), pack.files());
+}
+
+#[test]
+fn broken_archives_are_errors() {
+    let root = folder("broken");
+    let bytes = archive(&root);
+    // Cut off anywhere, even inside the last file
+    for length in 0..bytes.len() {
+        assert!(AssetPack::from_archive_bytes(&bytes[..length]).is_err(), "{} bytes", length);
+    }
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[..4].copy_from_slice(b"ZIP!");
+    assert_eq!(AssetPack::from_archive_bytes(&wrong_magic).unwrap_err(), "Not an asset archive");
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[4..8].copy_from_slice(&7u32.to_le_bytes());
+    assert_eq!(AssetPack::from_archive_bytes(&wrong_version).unwrap_err(), "Unsupported archive version 7");
+
+    // A file claiming to be longer than the archive
+    let mut too_long = bytes;
+    too_long[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(AssetPack::from_archive_bytes(&too_long).is_err());
+}
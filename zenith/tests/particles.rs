@@ -0,0 +1,82 @@
+// Particle emitters: spawning, the particle limit, pausing and following an entity.
+
+use zenith::*;
+
+fn runner(delta_time: f32) -> HeadlessRunner {
+    let mut runner = HeadlessRunner::new();
+    runner.instance.engine_settings.fixed_delta_time = Some(delta_time);
+    runner
+}
+
+fn particles(runner: &HeadlessRunner) -> usize {
+    runner.instance.environment.list_emitters()[0].particle_count()
+}
+
+#[test]
+fn paused_emitters_stay_as_they_are() {
+    let mut runner = runner(0.25);
+    runner
+        .instance
+        .environment
+        .add_emitter(ParticleEmitter::new().with_spawn_rate(4.0).with_lifetime(10.0, 10.0));
+    runner.run_frames(2).unwrap();
+    assert_eq!(particles(&runner), 2);
+
+    runner.instance.environment.set_paused(true);
+    runner.run_frames(4).unwrap();
+    assert_eq!(particles(&runner), 2);
+    runner.instance.environment.set_paused(false);
+    runner.run_frames(1).unwrap();
+    assert_eq!(particles(&runner), 3);
+}
+
+#[test]
+fn spawning_stops_at_the_particle_limit() {
+    let mut runner = runner(0.25);
+    let environment = &mut runner.instance.environment;
+    environment.add_emitter(
+        ParticleEmitter::new()
+            .with_spawn_rate(1e12)
+            .with_lifetime(10.0, 10.0)
+            .with_max_particles(50),
+    );
+    runner.run_frames(2).unwrap();
+    assert_eq!(particles(&runner), 50);
+
+    let emitter = &mut runner.instance.environment.mut_emitters()[0];
+    emitter.emitting = false;
+    emitter.clear();
+    emitter.burst(u32::MAX);
+    runner.run_frames(1).unwrap();
+    assert_eq!(particles(&runner), 50);
+}
+
+fn location(x: i32) -> TagValue {
+    TagValue::Vec2(Vec2::new(x, 0))
+}
+
+#[test]
+fn emitters_follow_their_own_entity_and_stop_when_it_goes() {
+    let mut runner = runner(0.25);
+    let environment = &mut runner.instance.environment;
+    // Two copies with the same name
+    let first = environment.add_entity(Entity::new().with_name_tag("torch").with_tag("location", location(10)));
+    let second = environment.add_entity(Entity::new().with_name_tag("torch").with_tag("location", location(50)));
+    let emitter = ParticleEmitter::new().with_spawn_rate(4.0).with_lifetime(10.0, 10.0);
+    environment.add_emitter(emitter.clone().attached_to(first, Vec2::new(0, 0)));
+    environment.add_emitter(emitter.attached_to(second, Vec2::new(1, 2)));
+    runner.run_frames(1).unwrap();
+    let positions: Vec<Vec2> = runner.instance.environment.list_emitters().iter().map(|e| e.position.clone()).collect();
+    assert_eq!(positions, [Vec2::new(10, 0), Vec2::new(51, 2)]);
+
+    runner.instance.environment.despawn(second);
+    runner.run_frames(2).unwrap();
+    let emitters = runner.instance.environment.list_emitters();
+    assert!(emitters[0].emitting);
+    assert_eq!(emitters[0].particle_count(), 3);
+    // Stopped where it was, its particles are still around
+    assert!(!emitters[1].emitting);
+    assert_eq!(emitters[1].attached_to, None);
+    assert_eq!(emitters[1].position, Vec2::new(51, 2));
+    assert_eq!(emitters[1].particle_count(), 1);
+}
@@ -0,0 +1,90 @@
+// The scene target starts every frame empty and post effects don't build up on it.
+
+use zenith::*;
+
+fn draw(instance: &mut Instance2D) {
+    let frame = instance.environment.get_resource("frame").and_then(|tag| tag.extract_int()).unwrap_or(0);
+    instance.environment.set_resource("frame", TagValue::Int(frame + 1));
+
+    // Drawn every frame
+    instance
+        .engine_settings
+        .draw_rect(VisualRect::new(Vec2::new(0, 0), Vec2::new(10, 10), Color::white()));
+    // Only on the first frame
+    if frame == 0 {
+        instance
+            .engine_settings
+            .draw_rect(VisualRect::new(Vec2::new(50, 0), Vec2::new(10, 10), Color::white()));
+    }
+}
+
+fn runner(effects: Vec<PostEffect>) -> HeadlessRunner {
+    let mut runner = HeadlessRunner::new();
+    runner.instance.environment.add_update_script("draw", draw);
+    runner.instance.engine_settings.post_effects = effects;
+    runner
+}
+
+fn red(frame: &FrameBuffer, x: u32, y: u32) -> u8 {
+    frame.get_pixel(x, y).unwrap()[0]
+}
+
+#[test]
+fn effects_do_not_compound_across_frames() {
+    let mut runner = runner(vec![PostEffect::Fade { amount: 0.5 }]);
+    let first = runner.run_frames(1).unwrap();
+    let later = runner.run_frames(5).unwrap();
+    assert!((120..=135).contains(&red(&first, 5, 5)), "faded once: {}", red(&first, 5, 5));
+    assert_eq!(red(&later, 5, 5), red(&first, 5, 5));
+    // Drawn once, so it should be gone rather than faded again
+    assert!(red(&first, 55, 5) > 0);
+    assert_eq!(red(&later, 55, 5), 0);
+}
+
+#[test]
+fn old_frames_do_not_show_through() {
+    for effects in [vec![], vec![PostEffect::Scanlines { spacing: 2, darkness: 0.5 }]] {
+        let mut runner = runner(effects);
+        runner.instance.screen.set_logical_size(Some((300, 200)));
+        let first = runner.run_frames(1).unwrap();
+        let later = runner.run_frames(2).unwrap();
+        assert!(red(&first, 110, 10) > 0);
+        assert_eq!(red(&later, 110, 10), 0);
+    }
+}
+
+#[test]
+fn the_effects_target_follows_the_window() {
+    let mut runner = runner(vec![PostEffect::Fade { amount: 0.5 }]);
+    runner.run_frames(2).unwrap();
+    assert_eq!(runner.instance.engine_settings.view_size(), (600, 400));
+
+    let mut screen = runner.instance.screen.clone();
+    screen.set_window_size((300, 200));
+    runner.instance.engine_settings.apply_screen(&screen);
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!((frame.width, frame.height), (300, 200));
+    assert_eq!(runner.instance.engine_settings.view_size(), (300, 200));
+    // Still drawn at full size rather than squashed into the smaller window
+    assert!(red(&frame, 8, 8) > 0);
+    assert_eq!(red(&frame, 12, 12), 0);
+
+    runner.instance.engine_settings.post_effects.clear();
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(red(&frame, 5, 5), 255);
+}
+
+#[test]
+fn clearing_the_logical_size_goes_back_to_the_window() {
+    let mut runner = runner(vec![]);
+    runner.instance.screen.set_logical_size(Some((300, 200)));
+    runner.run_frames(1).unwrap();
+    assert_eq!(runner.instance.engine_settings.view_size(), (300, 200));
+
+    let mut screen = runner.instance.screen.clone();
+    screen.set_logical_size(None);
+    runner.instance.engine_settings.apply_screen(&screen);
+    assert_eq!(runner.instance.engine_settings.view_size(), (600, 400));
+    runner.run_frames(1).unwrap();
+    assert_eq!(runner.instance.engine_settings.view_size(), (600, 400));
+}
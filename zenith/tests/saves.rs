@@ -0,0 +1,141 @@
+// Save games: round trips, the migration chain and writing the file in one go.
+
+use serde_json::Value;
+use std::path::PathBuf;
+use zenith::*;
+
+fn folder(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("zenith_saves_{}_{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn environment(hp: i32) -> Environment {
+    let mut environment = Environment::new();
+    environment.add_entity(Entity::new().with_name_tag("player").with_tag("level", TagValue::Int(3)));
+    environment.set_resource("hp", TagValue::Int(hp));
+    environment
+}
+
+fn resource(environment: &Environment, name: &str) -> Option<i32> {
+    environment.get_resource(name).and_then(|tag| tag.extract_int())
+}
+
+#[test]
+fn saves_load_back_the_same() {
+    let root = folder("round_trip");
+    let path = root.join("save.json");
+    let path = path.to_str().unwrap();
+    let mut saved = environment(10);
+    saved.add_tilemap(Tilemap::new(2, 2, Vec2::new(16, 16)));
+    Saves::new(4).save(&saved, path).unwrap();
+
+    let mut loaded = Environment::new();
+    loaded.set_resource("gold", TagValue::Int(1));
+    assert_eq!(Saves::new(4).load(&mut loaded, path), Ok(4));
+    assert_eq!(loaded.to_scene().unwrap(), saved.to_scene().unwrap());
+    assert_eq!(resource(&loaded, "hp"), Some(10));
+    // Everything from before the load is gone
+    assert_eq!(resource(&loaded, "gold"), None);
+    assert_eq!(loaded.list_tilemaps().len(), 1);
+}
+
+#[test]
+fn saving_replaces_the_old_file_in_one_go() {
+    let root = folder("atomic");
+    let path = root.join("save.json");
+    let path = path.to_str().unwrap();
+    let saves = Saves::default();
+    saves.save(&environment(1), path).unwrap();
+    saves.save(&environment(2), path).unwrap();
+
+    let mut loaded = Environment::new();
+    saves.load(&mut loaded, path).unwrap();
+    assert_eq!(resource(&loaded, "hp"), Some(2));
+    let files: Vec<_> = std::fs::read_dir(&root).unwrap().map(|file| file.unwrap().file_name()).collect();
+    assert_eq!(files, ["save.json"]);
+
+    // A save that can't be written leaves nothing behind
+    let missing = root.join("missing").join("save.json");
+    assert!(saves.save(&environment(3), missing.to_str().unwrap()).is_err());
+    assert!(!root.join("missing").exists());
+}
+
+// Version 1 called it "hp", version 2 "health", version 3 counts it in halves
+fn rename_hp(data: &mut Value) -> Result<(), String> {
+    let resources = data["resources"].as_object_mut().ok_or("No resources")?;
+    let hp = resources.remove("hp").ok_or("No hp")?;
+    resources.insert("health".to_string(), hp);
+    Ok(())
+}
+
+fn double_health(data: &mut Value) -> Result<(), String> {
+    let health = &mut data["resources"]["health"]["Int"];
+    let value = health.as_i64().ok_or("No health")?;
+    *health = Value::from(value * 2);
+    Ok(())
+}
+
+#[test]
+fn old_saves_go_through_every_migration_in_order() {
+    let json = Saves::new(1).to_json(&environment(10)).unwrap();
+    let saves = Saves::new(3).with_migration(2, double_health).with_migration(1, rename_hp);
+    let mut loaded = Environment::new();
+    assert_eq!(saves.load_json(&mut loaded, &json), Ok(1));
+    assert_eq!(resource(&loaded, "health"), Some(20));
+    assert_eq!(resource(&loaded, "hp"), None);
+
+    // Saves already at a version only get the migrations after it
+    let json = Saves::new(2).to_json(&loaded).unwrap();
+    let mut loaded = Environment::new();
+    assert_eq!(saves.load_json(&mut loaded, &json), Ok(2));
+    assert_eq!(resource(&loaded, "health"), Some(40));
+}
+
+#[test]
+fn saves_that_cannot_be_brought_up_to_date_change_nothing() {
+    let mut loaded = environment(5);
+    let before = loaded.to_scene().unwrap();
+
+    let json = Saves::new(1).to_json(&environment(10)).unwrap();
+    let missing = Saves::new(3).with_migration(1, rename_hp);
+    assert_eq!(
+        missing.load_json(&mut loaded, &json),
+        Err("No migration from save version 2 to 3".to_string())
+    );
+
+    let failing = Saves::new(2).with_migration(1, double_health);
+    let error = failing.load_json(&mut loaded, &json).unwrap_err();
+    assert!(error.starts_with("Migration from version 1 failed"), "{}", error);
+
+    let too_new = Saves::new(5).to_json(&environment(10)).unwrap();
+    assert_eq!(
+        Saves::new(4).load_json(&mut loaded, &too_new),
+        Err("Save is version 5 but this game only knows up to version 4".to_string())
+    );
+
+    assert_eq!(loaded.to_scene().unwrap(), before);
+    assert_eq!(resource(&loaded, "hp"), Some(5));
+}
+
+#[test]
+fn loaded_tilemaps_still_know_their_map_file() {
+    let root = folder("tilemap");
+    let map = r#"{ "width": 2, "height": 1, "tilewidth": 16, "tileheight": 16, "tilesets": [],
+  "layers": [{ "type": "tilelayer", "name": "ground", "width": 2, "height": 1, "data": [0, 0] }] }"#;
+    std::fs::write(root.join("level.tmj"), map).unwrap();
+    let path = root.join("save.json");
+    let path = path.to_str().unwrap();
+
+    let mut instance = Instance2D::new_headless();
+    instance.assets.set_pack(AssetPack::disk(&root));
+    instance.load_tilemap("level.tmj").unwrap();
+    instance.save_game(path).unwrap();
+
+    let mut loaded = Instance2D::new_headless();
+    loaded.assets.set_pack(AssetPack::disk(&root));
+    loaded.load_game(path).unwrap();
+    let source = loaded.environment.list_tilemaps()[0].source.clone().unwrap();
+    assert_eq!(Some(source), loaded.assets.find::<TiledMap>("level.tmj"));
+}
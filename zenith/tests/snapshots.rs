@@ -0,0 +1,87 @@
+// Golden image tests, references are in tests/snapshots.
+// A missing reference fails the test, run with ZENITH_BLESS=1 to record or update them.
+
+use zenith::*;
+
+fn background(instance: &mut Instance2D) {
+    instance.engine_settings.draw_rect(
+        VisualRect::new(Vec2::new(0, 0), Vec2::new(600, 400), Color { r: 20, g: 20, b: 40 }).with_layer(-1),
+    );
+}
+
+fn move_player(instance: &mut Instance2D) {
+    let right = instance.get_pressed().D;
+    let down = instance.get_pressed().S;
+    if let Some(player) = instance.environment.get_mut_entity("player") {
+        let mut location = player.get_tag("location").and_then(|t| t.extract_vec2()).unwrap();
+        if right {
+            location.x += 10;
+        }
+        if down {
+            location.y += 10;
+        }
+        player.set_tag("location", TagValue::Vec2(location));
+    }
+}
+
+fn draw_player(instance: &mut Instance2D) {
+    if let Some(player) = instance.environment.get_entity("player") {
+        let location = player.get_tag("location").and_then(|t| t.extract_vec2()).unwrap();
+        instance
+            .engine_settings
+            .draw_rect(VisualRect::new(location, Vec2::new(40, 40), Color { r: 220, g: 60, b: 60 }));
+    }
+}
+
+fn runner() -> HeadlessRunner {
+    let mut runner = HeadlessRunner::new();
+    let instance = &mut runner.instance;
+    instance.environment.add_update_script("background", background);
+    instance.environment.add_update_script("move player", move_player);
+    instance.environment.add_update_script("draw player", draw_player);
+    instance.environment.add_entity(
+        Entity::new()
+            .with_name_tag("player")
+            .with_tag("location", TagValue::Vec2(Vec2::new(50, 50)))
+            .with_update_fn(|_| {}),
+    );
+    runner
+}
+
+#[test]
+fn player_idle() {
+    let frame = runner().run_frames(5).unwrap();
+    assert_snapshot("player_idle", &frame, 0);
+}
+
+#[test]
+fn player_moves_with_scripted_input() {
+    let mut runner = runner().press(2, "d").press(4, "s").release(8, "d").release(10, "s");
+    let frame = runner.run_frames(15).unwrap();
+    assert_eq!(runner.frame(), 15);
+    assert_snapshot("player_moved", &frame, 0);
+}
+
+#[test]
+fn frames_compare_with_tolerance() {
+    let expected = FrameBuffer::from_pixels(2, 1, vec![10, 10, 10, 255, 200, 200, 200, 255]).unwrap();
+    let mut actual = expected.clone();
+    actual.set_pixel(1, 0, [203, 200, 200, 255]);
+
+    assert!(compare_frames(&actual, &expected, 3).is_ok());
+    let diff = compare_frames(&actual, &expected, 2).unwrap_err();
+    assert_eq!(diff.mismatched_pixels, 1);
+    assert_eq!(diff.max_difference, 3);
+    assert_eq!(diff.diff.get_pixel(1, 0), Some([255, 0, 0, 255]));
+}
+
+#[test]
+fn missing_reference_is_an_error() {
+    if std::env::var("ZENITH_BLESS").is_ok_and(|v| v == "1") {
+        return;
+    }
+    let frame = FrameBuffer::from_pixels(1, 1, vec![0, 0, 0, 255]).unwrap();
+    let error = check_snapshot("no_such_snapshot", &frame, 0).unwrap_err();
+    assert!(error.contains("ZENITH_BLESS"));
+    assert!(!snapshot_dir().join("no_such_snapshot.png").exists());
+}
@@ -0,0 +1,154 @@
+// How systems get batched and that running the batches in parallel changes nothing.
+
+use zenith::*;
+
+fn nothing(_: &mut SystemContext) {}
+
+#[test]
+fn systems_that_do_not_conflict_share_a_batch() {
+    let mut environment = Environment::new();
+    environment.add_system(System::new("move", nothing).with_read("velocity").with_write("location"));
+    // Reading what move reads is fine
+    environment.add_system(System::new("drag", nothing).with_read("velocity").with_write("drag"));
+    environment.add_system(System::new("score", nothing).with_resource_write("score"));
+    assert_eq!(environment.list_system_batches(), [["move", "drag", "score"]]);
+
+    // Reads what move writes, so it has to wait for it
+    environment.add_system(System::new("camera", nothing).with_read("location"));
+    // Writes what drag writes
+    environment.add_system(System::new("friction", nothing).with_write("drag"));
+    // Reads a resource score writes
+    environment.add_system(System::new("hud", nothing).with_resource_read("score"));
+    // Conflicts with camera only, which is in the second batch
+    environment.add_system(System::new("teleport", nothing).with_write("location"));
+    assert_eq!(
+        environment.list_system_batches(),
+        vec![vec!["move", "drag", "score"], vec!["camera", "friction", "hud"], vec!["teleport"]]
+    );
+}
+
+#[test]
+fn batches_are_planned_again_when_systems_change() {
+    let mut environment = Environment::new();
+    environment.add_system(System::new("a", nothing).with_write("x"));
+    environment.add_system(System::new("b", nothing).with_read("x"));
+    assert_eq!(environment.list_system_batches(), [["a"], ["b"]]);
+
+    // Replacing a with one that doesn't write x lets b go with it
+    environment.add_system(System::new("a", nothing).with_write("y"));
+    assert_eq!(environment.list_systems(), ["b", "a"]);
+    assert_eq!(environment.list_system_batches(), [["b", "a"]]);
+
+    environment.add_system(System::new("c", nothing).with_write("x"));
+    assert_eq!(environment.list_system_batches(), vec![vec!["b", "a"], vec!["c"]]);
+    assert!(environment.remove_system("b"));
+    assert!(!environment.remove_system("b"));
+    assert_eq!(environment.list_system_batches(), [["a", "c"]]);
+}
+
+fn int(context: &SystemContext, id: EntityId, tag: &str) -> i32 {
+    context.get(id, tag).and_then(|value| value.extract_int()).unwrap_or(0)
+}
+
+fn movement(context: &mut SystemContext) {
+    for id in context.query(&["x", "speed"]) {
+        let x = int(context, id, "x") + int(context, id, "speed");
+        context.set(id, "x", TagValue::Int(x));
+    }
+}
+
+fn accelerate(context: &mut SystemContext) {
+    for id in context.query(&["speed"]) {
+        let speed = int(context, id, "speed");
+        context.set(id, "speed", TagValue::Int(speed + 1));
+    }
+}
+
+fn heal(context: &mut SystemContext) {
+    for id in context.query(&["hp"]) {
+        let hp = int(context, id, "hp");
+        context.set(id, "hp", TagValue::Int(hp + 2));
+    }
+}
+
+fn total(context: &mut SystemContext) {
+    let sum: i32 = context.query(&["x"]).into_iter().map(|id| int(context, id, "x")).sum();
+    let before = context.get_resource("total").and_then(|value| value.extract_int()).unwrap_or(0);
+    context.set_resource("total", TagValue::Int(before + sum));
+}
+
+fn world(parallel: bool) -> (Environment, Vec<EntityId>) {
+    let mut environment = Environment::new();
+    environment.set_parallel_systems(parallel);
+    environment.add_system(System::new("movement", movement).with_read("speed").with_write("x"));
+    environment.add_system(System::new("accelerate", accelerate).with_write("speed"));
+    environment.add_system(System::new("heal", heal).with_write("hp"));
+    environment.add_system(System::new("total", total).with_read("x").with_resource_write("total"));
+    let ids = (0..50)
+        .map(|i| {
+            environment.add_entity(
+                Entity::new()
+                    .with_tag("x", TagValue::Int(i))
+                    .with_tag("speed", TagValue::Int(i % 7))
+                    .with_tag("hp", TagValue::Int(i * 3)),
+            )
+        })
+        .collect();
+    (environment, ids)
+}
+
+fn snapshot(environment: &Environment, ids: &[EntityId]) -> Vec<(Option<TagValue>, Option<TagValue>, Option<TagValue>)> {
+    ids.iter()
+        .map(|id| {
+            let entity = environment.get_entity_by_id(*id).unwrap();
+            (entity.get_tag("x"), entity.get_tag("speed"), entity.get_tag("hp"))
+        })
+        .collect()
+}
+
+#[test]
+fn parallel_runs_match_single_threaded_runs() {
+    let (mut parallel, parallel_ids) = world(true);
+    let (mut single, single_ids) = world(false);
+    assert_eq!(
+        parallel.list_system_batches(),
+        vec![vec!["movement", "heal"], vec!["accelerate", "total"]]
+    );
+    for _ in 0..10 {
+        parallel.run_systems(0.016);
+        single.run_systems(0.016);
+        assert_eq!(snapshot(&parallel, &parallel_ids), snapshot(&single, &single_ids));
+        assert_eq!(parallel.get_resource("total"), single.get_resource("total"));
+    }
+
+    // Same as running them one after another in the order they were added: movement
+    // used the speed from before accelerate, total the x from after movement
+    let entity = single.get_entity_by_id(single_ids[3]).unwrap();
+    assert_eq!(entity.get_tag("speed"), Some(TagValue::Int(3 + 10)));
+    assert_eq!(entity.get_tag("x"), Some(TagValue::Int(3 + (3..13).sum::<i32>())));
+    assert_eq!(entity.get_tag("hp"), Some(TagValue::Int(9 + 20)));
+}
+
+fn sneaky(context: &mut SystemContext) {
+    let id = context.entity_ids()[0];
+    let saw_secret = context.get(id, "secret").is_some();
+    let found = context.query(&["secret"]).len() as i32;
+    let saw_gold = context.get_resource("gold").is_some();
+    context.set(id, "seen", TagValue::Int(saw_secret as i32 + found + saw_gold as i32));
+    context.set(id, "secret", TagValue::Int(0));
+    context.set_resource("gold", TagValue::Int(0));
+}
+
+#[test]
+fn undeclared_reads_and_writes_are_ignored() {
+    let mut environment = Environment::new();
+    let id = environment.add_entity(Entity::new().with_tag("secret", TagValue::Int(42)));
+    environment.set_resource("gold", TagValue::Int(10));
+    environment.add_system(System::new("sneaky", sneaky).with_write("seen"));
+    environment.run_systems(0.016);
+
+    let entity = environment.get_entity_by_id(id).unwrap();
+    assert_eq!(entity.get_tag("seen"), Some(TagValue::Int(0)));
+    assert_eq!(entity.get_tag("secret"), Some(TagValue::Int(42)));
+    assert_eq!(environment.get_resource("gold"), Some(&TagValue::Int(10)));
+}
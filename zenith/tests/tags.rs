@@ -0,0 +1,208 @@
+// Tag arithmetic and the typed accessors, one test per kind of value.
+
+use std::collections::BTreeMap;
+use zenith::*;
+
+fn int(x: i32) -> TagValue {
+    TagValue::Int(x)
+}
+
+fn float(x: f32) -> TagValue {
+    TagValue::Float(x)
+}
+
+fn double(x: f64) -> TagValue {
+    TagValue::Double(x)
+}
+
+fn string(x: &str) -> TagValue {
+    TagValue::String(x.to_string())
+}
+
+fn vec2(x: i32, y: i32) -> TagValue {
+    TagValue::Vec2(Vec2::new(x, y))
+}
+
+fn color(r: u8, g: u8, b: u8) -> TagValue {
+    TagValue::Color(Color { r, g, b })
+}
+
+fn incompatible(operation: &'static str, left: &'static str, right: &'static str) -> Result<TagValue, TagError> {
+    Err(TagError::Incompatible { operation, left, right })
+}
+
+#[test]
+fn ints_stay_ints_and_report_overflow() {
+    assert_eq!(int(7).try_add(&int(5)), Ok(int(12)));
+    assert_eq!(int(7).try_sub(&int(10)), Ok(int(-3)));
+    assert_eq!(int(7).try_mul(&int(-3)), Ok(int(-21)));
+    assert_eq!(int(7).try_div(&int(2)), Ok(int(3)));
+    assert_eq!(int(-7).try_div(&int(2)), Ok(int(-3)));
+
+    assert_eq!(int(7).try_div(&int(0)), Err(TagError::DivideByZero));
+    assert_eq!(int(i32::MAX).try_add(&int(1)), Err(TagError::Overflow));
+    assert_eq!(int(i32::MIN).try_sub(&int(1)), Err(TagError::Overflow));
+    assert_eq!(int(i32::MAX).try_mul(&int(2)), Err(TagError::Overflow));
+    assert_eq!(int(i32::MIN).try_div(&int(-1)), Err(TagError::Overflow));
+}
+
+#[test]
+fn mixed_numbers_widen() {
+    assert_eq!(int(1).try_add(&float(0.5)), Ok(float(1.5)));
+    assert_eq!(float(0.5).try_add(&int(1)), Ok(float(1.5)));
+    assert_eq!(float(3.0).try_div(&float(2.0)), Ok(float(1.5)));
+    assert_eq!(int(3).try_mul(&double(0.5)), Ok(double(1.5)));
+    assert_eq!(double(0.5).try_sub(&float(0.25)), Ok(double(0.25)));
+    assert_eq!(double(1.0).try_add(&double(2.0)), Ok(double(3.0)));
+
+    // Floats divide by zero the float way
+    assert_eq!(float(1.0).try_div(&int(0)), Ok(float(f32::INFINITY)));
+    assert_eq!(double(-1.0).try_div(&double(0.0)), Ok(double(f64::NEG_INFINITY)));
+    // No overflow for floats either
+    assert_eq!(float(f32::MAX).try_mul(&int(2)), Ok(float(f32::INFINITY)));
+
+    assert_eq!(double(1.0).try_add(&string("a")), incompatible("add", "Double", "String"));
+    assert_eq!(vec2(1, 1).try_add(&double(1.0)), incompatible("add", "Vec2", "Double"));
+    assert_eq!(float(1.0).try_add(&TagValue::Bool(true)), incompatible("add", "Float", "Bool"));
+}
+
+#[test]
+fn vec2s_work_with_each_other_and_scale_by_numbers() {
+    assert_eq!(vec2(1, 2).try_add(&vec2(10, 20)), Ok(vec2(11, 22)));
+    assert_eq!(vec2(1, 2).try_sub(&vec2(10, 20)), Ok(vec2(-9, -18)));
+    assert_eq!(vec2(3, 4).try_mul(&vec2(2, -1)), Ok(vec2(6, -4)));
+    assert_eq!(vec2(9, 8).try_div(&vec2(3, 2)), Ok(vec2(3, 4)));
+    assert_eq!(vec2(9, 8).try_div(&vec2(3, 0)), Err(TagError::DivideByZero));
+    assert_eq!(vec2(i32::MAX, 0).try_add(&vec2(1, 0)), Err(TagError::Overflow));
+
+    // Scaling rounds to the nearest whole number
+    assert_eq!(vec2(3, 5).try_mul(&int(2)), Ok(vec2(6, 10)));
+    assert_eq!(vec2(3, 5).try_mul(&float(0.5)), Ok(vec2(2, 3)));
+    assert_eq!(vec2(10, -10).try_div(&double(4.0)), Ok(vec2(3, -3)));
+    assert_eq!(vec2(3, 5).try_div(&int(0)), Err(TagError::DivideByZero));
+    assert_eq!(vec2(3, 5).try_div(&float(0.0)), Err(TagError::DivideByZero));
+    // Too big for an Int instead of stopping at i32::MAX
+    assert_eq!(vec2(i32::MAX, 0).try_mul(&int(2)), Err(TagError::Overflow));
+    assert_eq!(vec2(0, i32::MIN).try_mul(&double(1.5)), Err(TagError::Overflow));
+    assert_eq!(vec2(1, 1).try_div(&double(1e-300)), Err(TagError::Overflow));
+    assert_eq!(vec2(1, 1).try_mul(&float(f32::NAN)), Err(TagError::Overflow));
+    assert_eq!(vec2(i32::MIN, i32::MAX).try_mul(&int(1)), Ok(vec2(i32::MIN, i32::MAX)));
+
+    assert_eq!(vec2(3, 5).try_add(&int(1)), incompatible("add", "Vec2", "Int"));
+    assert_eq!(vec2(3, 5).try_sub(&float(1.0)), incompatible("subtract", "Vec2", "Float"));
+    assert_eq!(int(2).try_mul(&vec2(3, 5)), incompatible("multiply", "Int", "Vec2"));
+    assert_eq!(vec2(3, 5).try_add(&color(1, 1, 1)), incompatible("add", "Vec2", "Color"));
+}
+
+#[test]
+fn colors_stay_between_0_and_255() {
+    assert_eq!(color(200, 10, 0).try_add(&color(100, 10, 0)), Ok(color(255, 20, 0)));
+    assert_eq!(color(200, 10, 0).try_sub(&color(100, 20, 0)), Ok(color(100, 0, 0)));
+    assert_eq!(color(20, 2, 255).try_mul(&color(20, 3, 1)), Ok(color(255, 6, 255)));
+    assert_eq!(color(20, 3, 255).try_div(&color(2, 2, 255)), Ok(color(10, 2, 1)));
+    assert_eq!(color(20, 3, 255).try_div(&color(2, 0, 255)), Err(TagError::DivideByZero));
+
+    assert_eq!(color(100, 200, 3).try_mul(&int(2)), Ok(color(200, 255, 6)));
+    assert_eq!(color(100, 200, 3).try_mul(&float(0.5)), Ok(color(50, 100, 2)));
+    assert_eq!(color(100, 200, 3).try_mul(&double(-1.0)), Ok(color(0, 0, 0)));
+    assert_eq!(color(100, 200, 3).try_div(&int(4)), Ok(color(25, 50, 1)));
+    assert_eq!(color(100, 0, 3).try_mul(&double(1e300)), Ok(color(255, 0, 255)));
+    assert_eq!(color(100, 200, 3).try_div(&double(0.0)), Err(TagError::DivideByZero));
+
+    assert_eq!(color(1, 1, 1).try_add(&int(1)), incompatible("add", "Color", "Int"));
+    assert_eq!(color(1, 1, 1).try_mul(&vec2(1, 1)), incompatible("multiply", "Color", "Vec2"));
+}
+
+#[test]
+fn strings_and_lists_only_add() {
+    assert_eq!(string("fire").try_add(&string("ball")), Ok(string("fireball")));
+    assert_eq!(string("a").try_sub(&string("a")), incompatible("subtract", "String", "String"));
+    assert_eq!(string("1").try_add(&int(1)), incompatible("add", "String", "Int"));
+
+    let list = TagValue::List(vec![int(1), string("two")]);
+    assert_eq!(
+        list.try_add(&TagValue::List(vec![int(3)])),
+        Ok(TagValue::List(vec![int(1), string("two"), int(3)]))
+    );
+    assert_eq!(list.try_mul(&list), incompatible("multiply", "List", "List"));
+
+    let map = TagValue::Map(BTreeMap::new());
+    assert_eq!(map.try_add(&map), incompatible("add", "Map", "Map"));
+    let yes = TagValue::Bool(true);
+    assert_eq!(yes.try_add(&yes), incompatible("add", "Bool", "Bool"));
+}
+
+#[test]
+fn errors_say_what_went_wrong() {
+    assert_eq!(
+        string("a").try_sub(&int(1)).unwrap_err().to_string(),
+        "Can't subtract String and Int"
+    );
+    assert_eq!(TagError::DivideByZero.to_string(), "Divided by zero");
+    assert_eq!(TagError::Missing("hp".to_string()).to_string(), "No tag or resource named hp");
+    assert_eq!(int(1).as_str().unwrap_err().to_string(), "Expected String but the value is Int");
+}
+
+#[test]
+fn accessors_return_the_value_or_what_it_was() {
+    let wrong = |expected: &'static str, found: &'static str| TagError::WrongType { expected, found };
+    assert_eq!(string("a").as_str(), Ok("a"));
+    assert_eq!(int(2).as_int(), Ok(2));
+    assert_eq!(float(2.5).as_float(), Ok(2.5));
+    assert_eq!(double(2.5).as_double(), Ok(2.5));
+    assert_eq!(TagValue::Bool(true).as_bool(), Ok(true));
+    assert_eq!(color(1, 2, 3).as_color(), Ok(&Color { r: 1, g: 2, b: 3 }));
+    assert_eq!(vec2(1, 2).as_vec2(), Ok(&Vec2::new(1, 2)));
+    assert_eq!(TagValue::List(vec![int(1)]).as_list(), Ok(&vec![int(1)]));
+    assert_eq!(TagValue::Map(BTreeMap::new()).as_map(), Ok(&BTreeMap::new()));
+
+    // Ints aren't quietly turned into floats or bools
+    assert_eq!(int(1).as_float(), Err(wrong("Float", "Int")));
+    assert_eq!(int(1).as_bool(), Err(wrong("Bool", "Int")));
+    assert_eq!(float(1.0).as_int(), Err(wrong("Int", "Float")));
+    assert_eq!(float(1.0).as_double(), Err(wrong("Double", "Float")));
+    assert_eq!(string("a").as_color().unwrap_err(), wrong("Color", "String"));
+    assert_eq!(color(0, 0, 0).as_vec2().unwrap_err(), wrong("Vec2", "Color"));
+    assert_eq!(vec2(0, 0).as_list().unwrap_err(), wrong("List", "Vec2"));
+    assert_eq!(TagValue::List(Vec::new()).as_map().unwrap_err(), wrong("Map", "List"));
+    assert_eq!(TagValue::Map(BTreeMap::new()).as_str().unwrap_err(), wrong("String", "Map"));
+}
+
+#[test]
+fn lists_and_maps_can_be_changed_in_place() {
+    let mut inventory = TagValue::List(vec![string("sword")]);
+    inventory.as_mut_list().unwrap().push(string("shield"));
+    assert_eq!(inventory.extract_list(), Some(vec![string("sword"), string("shield")]));
+    assert!(int(1).as_mut_list().is_err());
+
+    let mut stats = TagValue::Map(BTreeMap::new());
+    let map = stats.as_mut_map().unwrap();
+    map.insert("speed".to_string(), int(3));
+    map.insert("armor".to_string(), int(1));
+    // Always in key order
+    let keys: Vec<String> = stats.extract_map().unwrap().into_keys().collect();
+    assert_eq!(keys, ["armor", "speed"]);
+    assert!(int(1).as_mut_map().is_err());
+}
+
+#[test]
+fn tags_and_resources_are_changed_where_they_are() {
+    let mut entity = Entity::new().with_tag("hp", int(10));
+    assert_eq!(entity.try_get_tag("hp").and_then(TagValue::as_int), Ok(10));
+    assert_eq!(entity.try_get_tag("mana"), Err(TagError::Missing("mana".to_string())));
+
+    entity
+        .modify_tag("hp", |hp| hp.apply(|hp| hp.try_mul(&int(i32::MAX)).unwrap_or(int(0))))
+        .unwrap();
+    assert_eq!(entity.get_tag("hp"), Some(int(0)));
+    assert_eq!(
+        entity.modify_tag("mana", |_| panic!("there is no mana")),
+        Err(TagError::Missing("mana".to_string()))
+    );
+
+    let mut environment = Environment::new();
+    environment.set_resource("score", int(5));
+    environment.modify_resource("score", |score| *score = int(6)).unwrap();
+    assert_eq!(environment.get_resource("score"), Some(&int(6)));
+    assert!(environment.modify_resource("lives", |_| {}).is_err());
+}
@@ -0,0 +1,60 @@
+// Texture ids after their texture is destroyed, and texture data of the wrong size.
+
+use std::cell::Cell;
+use zenith::*;
+
+thread_local! {
+    static DRAWN: Cell<Option<TextureId>> = const { Cell::new(None) };
+}
+
+fn draw(instance: &mut Instance2D) {
+    if let Some(texture) = DRAWN.with(|drawn| drawn.get()) {
+        instance
+            .engine_settings
+            .draw_sprite(VisualSprite::new(Vec2::new(0, 0), Vec2::new(10, 10), texture));
+    }
+}
+
+fn pixel(color: [u8; 4]) -> Vec<u8> {
+    color.repeat(4)
+}
+
+#[test]
+fn destroyed_texture_ids_do_not_draw_the_next_texture() {
+    let mut runner = HeadlessRunner::new();
+    runner.instance.environment.add_update_script("draw", draw);
+    // A scene target so every frame starts out black
+    runner.instance.screen.set_logical_size(Some((600, 400)));
+    let settings = &mut runner.instance.engine_settings;
+    let red = settings.create_texture(2, 2, &pixel([255, 0, 0, 255])).unwrap();
+    settings.destroy_texture(red);
+    let green = settings.create_texture(2, 2, &pixel([0, 255, 0, 255])).unwrap();
+    assert_ne!(red, green);
+
+    DRAWN.with(|drawn| drawn.set(Some(green)));
+    // What the script draws is shown the frame after
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(frame.get_pixel(5, 5).unwrap()[..3], [0, 255, 0]);
+
+    DRAWN.with(|drawn| drawn.set(Some(red)));
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(frame.get_pixel(5, 5).unwrap()[..3], [0, 0, 0]);
+
+    // Destroying through a stale id leaves the new texture alone
+    runner.instance.engine_settings.destroy_texture(red);
+    DRAWN.with(|drawn| drawn.set(Some(green)));
+    let frame = runner.run_frames(2).unwrap();
+    assert_eq!(frame.get_pixel(5, 5).unwrap()[..3], [0, 255, 0]);
+}
+
+#[test]
+fn texture_data_has_to_match_the_size() {
+    let mut runner = HeadlessRunner::new();
+    let settings = &mut runner.instance.engine_settings;
+    assert!(settings.create_texture(2, 2, &[255; 12]).is_err());
+    assert!(settings.create_texture(2, 2, &[255; 20]).is_err());
+    // Would overflow if worked out in u32
+    let error = settings.create_texture(65536, 65536, &[]).unwrap_err();
+    assert!(error.contains("17179869184"), "{}", error);
+    assert!(settings.create_texture(u32::MAX, u32::MAX, &[]).is_err());
+}
@@ -0,0 +1,292 @@
+// Loading Tiled maps in both formats, from files written to a temporary folder.
+
+use base64::Engine;
+use std::io::Write;
+use std::path::PathBuf;
+use zenith::*;
+
+fn folder(test: &str, files: &[(&str, &str)]) -> AssetPack {
+    let root: PathBuf = std::env::temp_dir().join(format!("zenith_tiled_{}_{}", std::process::id(), test));
+    for (path, text) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+    AssetPack::disk(root)
+}
+
+fn tiles(map: &TiledMap, layer: &str) -> Vec<u32> {
+    let layer = map.tilemap.get_layer(layer).unwrap();
+    (0..layer.height)
+        .flat_map(|y| (0..layer.width).map(move |x| (x, y)))
+        .map(|(x, y)| layer.get_tile(x, y))
+        .collect()
+}
+
+fn encode(gids: &[u32], compression: &str) -> String {
+    let bytes: Vec<u8> = gids.iter().flat_map(|gid| gid.to_le_bytes()).collect();
+    let bytes = match compression {
+        "zlib" => {
+            let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&bytes).unwrap();
+            encoder.finish().unwrap()
+        }
+        "gzip" => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&bytes).unwrap();
+            encoder.finish().unwrap()
+        }
+        _ => bytes,
+    };
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn tmx(layers: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="8" columns="4">
+  <image source="terrain.png" width="64" height="32"/>
+ </tileset>
+ {}
+</map>"#,
+        layers
+    )
+}
+
+fn tmj(layers: &str) -> String {
+    format!(
+        r#"{{
+  "width": 3, "height": 2, "tilewidth": 16, "tileheight": 16, "infinite": false,
+  "tilesets": [{{ "firstgid": 1, "name": "terrain", "tilewidth": 16, "tileheight": 16,
+                  "tilecount": 8, "columns": 4, "image": "terrain.png" }}],
+  "layers": [{}]
+}}"#,
+        layers
+    )
+}
+
+#[test]
+fn csv_layers_load() {
+    let map = tmx(r#"<layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,3,
+4,5,2147483654
+</data>
+ </layer>
+ <layer id="2" name="decor" width="3" height="2">
+  <data encoding="csv">0,0,7,0,0,0</data>
+ </layer>"#);
+    let pack = folder("csv", &[("maps/level.tmx", &map)]);
+    let map = load_tiled_map_from(&pack, "maps/level.tmx").unwrap();
+
+    // The flip flag on the last tile is masked off
+    assert_eq!(tiles(&map, "ground"), [1, 2, 3, 4, 5, 6]);
+    assert_eq!(tiles(&map, "decor"), [0, 0, 7, 0, 0, 0]);
+    let layers: Vec<i32> = map.tilemap.layers.iter().map(|layer| layer.render_layer).collect();
+    assert_eq!(layers, [-2, -1]);
+    assert_eq!(map.tilemap.tilesets[0].image.as_deref(), Some("maps/terrain.png"));
+}
+
+#[test]
+fn base64_layers_load_compressed_or_not() {
+    let gids = [1, 2, 3, 4, 5, 6];
+    let json_layer = |compression: &str| {
+        format!(
+            r#"{{ "type": "tilelayer", "name": "ground", "width": 3, "height": 2, "encoding": "base64",
+                  "compression": "{}", "data": "{}" }}"#,
+            compression,
+            encode(&gids, compression)
+        )
+    };
+    let xml_layer = |compression: &str| {
+        format!(
+            r#"<layer id="1" name="ground" width="3" height="2"><data encoding="base64" compression="{}">
+   {}
+  </data></layer>"#,
+            compression,
+            encode(&gids, compression)
+        )
+    };
+
+    for compression in ["", "zlib", "gzip"] {
+        let test = format!("base64_{}", compression);
+        let pack = folder(
+            &test,
+            &[
+                ("level.tmj", &tmj(&json_layer(compression))),
+                ("level.tmx", &tmx(&xml_layer(compression))),
+            ],
+        );
+        for path in ["level.tmj", "level.tmx"] {
+            let map = load_tiled_map_from(&pack, path).unwrap();
+            assert_eq!(tiles(&map, "ground"), gids, "{} with '{}'", path, compression);
+        }
+    }
+
+    let pack = folder("base64_zstd", &[("level.tmx", &tmx(&xml_layer("zstd")))]);
+    let error = load_tiled_map_from(&pack, "level.tmx").err().unwrap();
+    assert!(error.contains("zstd"), "{}", error);
+}
+
+#[test]
+fn layers_with_the_wrong_number_of_tiles_are_errors() {
+    let short_csv = tmx(r#"<layer id="1" name="ground" width="3" height="2"><data encoding="csv">1,2,3,4,5</data></layer>"#);
+    let long_array = tmj(r#"{ "type": "tilelayer", "name": "ground", "width": 3, "height": 2, "data": [1,2,3,4,5,6,7] }"#);
+    let short_base64 = tmx(&format!(
+        r#"<layer id="1" name="ground" width="3" height="2"><data encoding="base64" compression="zlib">{}</data></layer>"#,
+        encode(&[1, 2, 3], "zlib")
+    ));
+    let too_few_elements = tmx(r#"<layer id="1" name="ground" width="3" height="2"><data><tile gid="1"/></data></layer>"#);
+    let pack = folder(
+        "wrong_count",
+        &[
+            ("short_csv.tmx", &short_csv),
+            ("long_array.tmj", &long_array),
+            ("short_base64.tmx", &short_base64),
+            ("too_few_elements.tmx", &too_few_elements),
+        ],
+    );
+    for path in ["short_csv.tmx", "long_array.tmj", "short_base64.tmx", "too_few_elements.tmx"] {
+        let error = load_tiled_map_from(&pack, path).err().unwrap_or_else(|| panic!("{} loaded", path));
+        assert!(error.contains("'ground'"), "{}", error);
+    }
+}
+
+#[test]
+fn external_tilesets_load_relative_to_themselves() {
+    let tsx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" name="water" tilewidth="16" tileheight="16" spacing="1" margin="2" tilecount="4" columns="2">
+ <image source="water.png" width="35" height="35"/>
+ <tile id="1">
+  <properties>
+   <property name="depth" type="int" value="3"/>
+  </properties>
+  <animation>
+   <frame tileid="1" duration="100"/>
+   <frame tileid="2" duration="250"/>
+  </animation>
+ </tile>
+</tileset>"#;
+    let map = r#"{
+  "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16,
+  "tilesets": [{ "firstgid": 5, "source": "../tilesets/water.tsx" }],
+  "layers": [{ "type": "tilelayer", "name": "ground", "width": 1, "height": 1, "data": [6] }]
+}"#;
+    let pack = folder("external", &[("maps/level.tmj", map), ("tilesets/water.tsx", tsx)]);
+    let map = load_tiled_map_from(&pack, "maps/level.tmj").unwrap();
+
+    let tileset = &map.tilemap.tilesets[0];
+    assert_eq!(tileset.name, "water");
+    assert_eq!(tileset.first_gid, 5);
+    assert_eq!((tileset.margin, tileset.spacing), (2, 1));
+    assert_eq!(tileset.image.as_deref(), Some("maps/../tilesets/water.png"));
+    assert_eq!(tileset.animations[&1].len(), 2);
+    assert_eq!(tileset.animations[&1][1].duration, 0.25);
+    assert_eq!(map.tilemap.tile_properties(6).unwrap()["depth"], TagValue::Int(3));
+
+    let missing = r#"{ "width": 1, "height": 1, "tilesets": [{ "firstgid": 1, "source": "gone.tsx" }], "layers": [] }"#;
+    let pack = folder("external_missing", &[("level.tmj", missing)]);
+    assert!(load_tiled_map_from(&pack, "level.tmj").is_err());
+}
+
+fn object_named<'a>(map: &'a TiledMap, name: &str) -> &'a Entity {
+    map.objects
+        .iter()
+        .find(|object| object.get_tag("name") == Some(TagValue::String(name.to_string())))
+        .unwrap()
+}
+
+#[test]
+fn object_layers_become_entities() {
+    let json = tmj(r#"{ "type": "group", "name": "things", "offsetx": 100, "offsety": 10, "layers": [
+  { "type": "objectgroup", "name": "spawns", "offsetx": 5, "objects": [
+    { "id": 1, "name": "door", "class": "exit", "x": 32, "y": 16, "width": 16, "height": 32,
+      "properties": [{ "name": "to", "type": "string", "value": "cave" },
+                     { "name": "keys", "type": "int", "value": 2 },
+                     { "name": "locked", "type": "bool", "value": true }] },
+    { "id": 2, "name": "coin", "type": "pickup", "gid": 3, "x": 0, "y": 48, "width": 16, "height": 16 }
+  ]}
+]}"#);
+    let xml = tmx(r#"<objectgroup id="2" name="spawns" offsetx="5">
+  <object id="1" name="door" class="exit" x="32" y="16" width="16" height="32">
+   <properties>
+    <property name="to" value="cave"/>
+    <property name="keys" type="int" value="2"/>
+    <property name="locked" type="bool" value="true"/>
+   </properties>
+  </object>
+  <object id="2" name="coin" type="pickup" gid="3" x="0" y="48" width="16" height="16"/>
+ </objectgroup>"#);
+    let pack = folder("objects", &[("level.tmj", &json), ("level.tmx", &xml)]);
+
+    // The JSON objects are in a group that moves them another (100, 10)
+    for (path, offset) in [("level.tmj", Vec2::new(105, 10)), ("level.tmx", Vec2::new(5, 0))] {
+        let map = load_tiled_map_from(&pack, path).unwrap();
+        assert_eq!(map.objects.len(), 2, "{}", path);
+
+        let door = object_named(&map, "door");
+        assert_eq!(door.get_tag("tiled_id"), Some(TagValue::Int(1)));
+        assert_eq!(door.get_tag("type"), Some(TagValue::String("exit".to_string())));
+        assert_eq!(door.get_tag("layer"), Some(TagValue::String("spawns".to_string())));
+        assert_eq!(door.get_tag("location"), Some(TagValue::Vec2(Vec2::new(32 + offset.x, 16 + offset.y))));
+        assert_eq!(door.get_tag("size"), Some(TagValue::Vec2(Vec2::new(16, 32))));
+        assert_eq!(door.get_tag("to"), Some(TagValue::String("cave".to_string())));
+        assert_eq!(door.get_tag("keys"), Some(TagValue::Int(2)));
+        assert_eq!(door.get_tag("locked"), Some(TagValue::Int(1)));
+
+        // Tile objects are placed by their bottom left corner
+        let coin = object_named(&map, "coin");
+        assert_eq!(coin.get_tag("type"), Some(TagValue::String("pickup".to_string())));
+        assert_eq!(coin.get_tag("gid"), Some(TagValue::Int(3)));
+        assert_eq!(coin.get_tag("location"), Some(TagValue::Vec2(Vec2::new(offset.x, 32 + offset.y))));
+    }
+}
+
+#[test]
+fn tilesets_at_the_end_of_the_gid_range_do_not_overflow() {
+    let tileset = Tileset::new("last", u32::MAX - 1, Vec2::new(16, 16), 4, 8);
+    assert!(!tileset.contains(u32::MAX - 2));
+    assert!(tileset.contains(u32::MAX - 1));
+    assert!(tileset.contains(u32::MAX));
+}
+
+#[test]
+fn color_properties_that_are_not_colors_are_skipped() {
+    let json = tmj(r##"{ "type": "objectgroup", "name": "spawns", "objects": [
+    { "id": 1, "name": "lamp", "x": 0, "y": 0, "properties": [
+      { "name": "light", "type": "color", "value": "#ff102030" },
+      { "name": "shade", "type": "color", "value": "#a0b0c0" },
+      { "name": "wide", "type": "color", "value": "#aééb" },
+      { "name": "short", "type": "color", "value": "#12345" }] }
+]}"##);
+    let pack = folder("colors", &[("level.tmj", &json)]);
+    let map = load_tiled_map_from(&pack, "level.tmj").unwrap();
+    let lamp = object_named(&map, "lamp");
+    assert_eq!(lamp.get_tag("light"), Some(TagValue::Color(Color { r: 0x10, g: 0x20, b: 0x30 })));
+    assert_eq!(lamp.get_tag("shade"), Some(TagValue::Color(Color { r: 0xa0, g: 0xb0, b: 0xc0 })));
+    // Six bytes long but not six characters
+    assert_eq!(lamp.get_tag("wide"), None);
+    assert_eq!(lamp.get_tag("short"), None);
+}
+
+#[test]
+fn tilemaps_made_in_code_load_their_images_as_assets() {
+    let root = std::env::temp_dir().join(format!("zenith_tiled_{}_in_code", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    FrameBuffer::new(64, 32).save_png(root.join("terrain.png")).unwrap();
+
+    let mut instance = Instance2D::new_headless();
+    instance.assets.set_pack(AssetPack::disk(&root));
+    let tileset = Tileset::new("terrain", 1, Vec2::new(16, 16), 4, 8).with_image("terrain.png");
+    let mut first = Tilemap::new(1, 1, Vec2::new(16, 16)).with_tileset(tileset.clone());
+    let mut second = Tilemap::new(1, 1, Vec2::new(16, 16)).with_tileset(tileset);
+    instance.load_tileset_textures(&mut first).unwrap();
+    instance.load_tileset_textures(&mut second).unwrap();
+
+    let handle = instance.assets.find::<Texture>("terrain.png").unwrap();
+    let texture = instance.assets.get(&handle).map(|texture| texture.id);
+    assert!(texture.is_some());
+    assert_eq!(first.tilesets[0].texture, texture);
+    assert_eq!(second.tilesets[0].texture, texture);
+}
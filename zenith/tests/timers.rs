@@ -0,0 +1,261 @@
+// Timers and sequences, run with a fixed time step that adds up exactly in floats.
+
+use std::cell::Cell;
+use zenith::*;
+
+fn runner(delta_time: f32) -> HeadlessRunner {
+    let mut runner = HeadlessRunner::new();
+    runner.instance.engine_settings.fixed_delta_time = Some(delta_time);
+    runner
+}
+
+fn add_one(instance: &mut Instance2D, name: &str) {
+    let before = instance.environment.get_resource(name).and_then(|tag| tag.extract_int()).unwrap_or(0);
+    instance.environment.set_resource(name, TagValue::Int(before + 1));
+}
+
+fn ticks(runner: &HeadlessRunner) -> i32 {
+    counted(runner, "ticks")
+}
+
+fn counted(runner: &HeadlessRunner, name: &str) -> i32 {
+    runner
+        .instance
+        .environment
+        .get_resource(name)
+        .and_then(|tag| tag.extract_int())
+        .unwrap_or(0)
+}
+
+fn tick(instance: &mut Instance2D) {
+    add_one(instance, "ticks");
+}
+
+fn tock(instance: &mut Instance2D) {
+    add_one(instance, "tocks");
+}
+
+#[test]
+fn once_fires_once_and_repeating_keeps_going() {
+    let mut runner = runner(0.125);
+    let environment = &mut runner.instance.environment;
+    let once = environment.add_timer(Timer::once(0.3).with_script(tick));
+    environment.add_timer(Timer::repeating(0.25).with_script(tock));
+
+    runner.run_frames(1).unwrap();
+    let remaining = runner.instance.environment.get_timer_remaining(once).unwrap();
+    assert!((remaining - 0.175).abs() < 1e-6, "{}", remaining);
+    runner.run_frames(1).unwrap();
+    assert_eq!(ticks(&runner), 0);
+    runner.run_frames(1).unwrap();
+    assert_eq!(ticks(&runner), 1);
+    assert_eq!(runner.instance.environment.get_timer_remaining(once), None);
+    assert!(!runner.instance.environment.cancel_timer(once));
+
+    runner.run_frames(5).unwrap();
+    assert_eq!(ticks(&runner), 1);
+    assert_eq!(counted(&runner, "tocks"), 4);
+}
+
+#[test]
+fn a_long_frame_fires_a_short_timer_more_than_once() {
+    let mut runner = runner(1.0);
+    let timer = runner.instance.environment.add_timer(Timer::repeating(0.375).with_script(tick));
+    runner.run_frames(1).unwrap();
+    assert_eq!(ticks(&runner), 2);
+    // The time left over from the frame counts towards the next one
+    assert_eq!(runner.instance.environment.get_timer_remaining(timer), Some(0.125));
+    runner.run_frames(1).unwrap();
+    assert_eq!(ticks(&runner), 5);
+
+    assert!(runner.instance.environment.cancel_timer(timer));
+    runner.run_frames(1).unwrap();
+    assert_eq!(ticks(&runner), 5);
+}
+
+#[test]
+fn tiny_timers_fire_a_few_times_a_frame_not_forever() {
+    let mut runner = runner(1.0);
+    let environment = &mut runner.instance.environment;
+    let tiny = environment.add_timer(Timer::repeating(1e-9).with_script(tick));
+    environment.add_timer(Timer::repeating(1e-6).with_script(tock));
+    runner.run_frames(2).unwrap();
+    assert_eq!((ticks(&runner), counted(&runner, "tocks")), (16, 16));
+    let remaining = runner.instance.environment.get_timer_remaining(tiny).unwrap();
+    assert!((0.0..=1e-9).contains(&remaining), "{}", remaining);
+}
+
+#[test]
+fn pausing_stops_timers_and_sequences_unless_they_run_while_paused() {
+    let mut runner = runner(0.25);
+    let environment = &mut runner.instance.environment;
+    environment.add_timer(Timer::repeating(0.25).with_script(tick));
+    environment.add_timer(Timer::repeating(0.25).with_script(tock).with_runs_while_paused());
+    environment.start_sequence(Sequence::new().then(|instance| add_one(instance, "steps")).wait(0.25).looping());
+    environment.start_sequence(
+        Sequence::new()
+            .then(|instance| add_one(instance, "paused steps"))
+            .wait(0.25)
+            .looping()
+            .with_runs_while_paused(),
+    );
+    environment.set_paused(true);
+    runner.run_frames(4).unwrap();
+    assert_eq!((ticks(&runner), counted(&runner, "tocks")), (0, 4));
+    assert_eq!((counted(&runner, "steps"), counted(&runner, "paused steps")), (0, 4));
+
+    runner.instance.environment.set_paused(false);
+    runner.run_frames(2).unwrap();
+    assert_eq!((ticks(&runner), counted(&runner, "tocks")), (2, 6));
+    assert_eq!((counted(&runner, "steps"), counted(&runner, "paused steps")), (2, 6));
+}
+
+fn hit(entity: &mut Entity) {
+    let hits = entity.get_tag("hits").and_then(|tag| tag.extract_int()).unwrap_or(0);
+    entity.set_tag("hits", TagValue::Int(hits + 1));
+}
+
+fn hits(runner: &HeadlessRunner, id: EntityId) -> i32 {
+    let entity = runner.instance.environment.get_entity_by_id(id).unwrap();
+    entity.get_tag("hits").and_then(|tag| tag.extract_int()).unwrap_or(0)
+}
+
+#[test]
+fn timers_and_sequences_go_away_with_their_entity() {
+    let mut runner = runner(0.25);
+    let environment = &mut runner.instance.environment;
+    let target = environment.add_entity(Entity::new().with_name_tag("target"));
+    let doomed = environment.add_entity(Entity::new().with_name_tag("doomed"));
+    environment.add_timer(Timer::repeating(0.25).with_entity_fn(hit).attached_to(target));
+    let timer = environment.add_timer(Timer::repeating(0.25).with_entity_fn(hit).with_script(tick).attached_to(doomed));
+    let sequence = environment.start_sequence(
+        Sequence::new()
+            .then_entity(hit)
+            .wait(0.25)
+            .then(tock)
+            .attached_to(doomed),
+    );
+    runner.run_frames(1).unwrap();
+    assert_eq!((hits(&runner, target), hits(&runner, doomed)), (1, 2));
+    assert_eq!((ticks(&runner), counted(&runner, "tocks")), (1, 1));
+
+    let environment = &mut runner.instance.environment;
+    environment.despawn(doomed);
+    environment.start_sequence(Sequence::new().wait(0.25).then(tock).attached_to(doomed));
+    runner.run_frames(3).unwrap();
+    assert_eq!(hits(&runner, target), 4);
+    assert_eq!((ticks(&runner), counted(&runner, "tocks")), (1, 1));
+    assert_eq!(runner.instance.environment.get_timer_remaining(timer), None);
+    assert!(!runner.instance.environment.is_sequence_running(sequence));
+}
+
+thread_local! {
+    static RUNNING: Cell<Option<SequenceId>> = const { Cell::new(None) };
+}
+
+fn stop_myself(instance: &mut Instance2D) {
+    let id = RUNNING.with(|running| running.get()).unwrap();
+    assert!(instance.environment.is_sequence_running(id));
+    assert!(instance.environment.stop_sequence(id));
+    assert!(!instance.environment.is_sequence_running(id));
+    assert!(!instance.environment.stop_sequence(id));
+}
+
+#[test]
+fn a_step_can_stop_its_own_sequence() {
+    let mut runner = runner(0.25);
+    let id = runner
+        .instance
+        .environment
+        .start_sequence(Sequence::new().then(tick).then(stop_myself).then(tock).looping());
+    RUNNING.with(|running| running.set(Some(id)));
+    runner.run_frames(3).unwrap();
+    assert_eq!((ticks(&runner), counted(&runner, "tocks")), (1, 0));
+    assert!(!runner.instance.environment.is_sequence_running(id));
+}
+
+fn record(instance: &mut Instance2D, progress: f32) {
+    let mut list = instance
+        .environment
+        .get_resource("progress")
+        .and_then(|tag| tag.extract_list())
+        .unwrap_or_default();
+    list.push(TagValue::Float(progress));
+    instance.environment.set_resource("progress", TagValue::List(list));
+}
+
+fn recorded(runner: &HeadlessRunner) -> Vec<f32> {
+    let list = runner.instance.environment.get_resource("progress").and_then(|tag| tag.extract_list());
+    list.unwrap_or_default().iter().filter_map(|tag| tag.extract_float()).collect()
+}
+
+#[test]
+fn during_ends_on_exactly_one() {
+    let mut runner = runner(0.125);
+    runner
+        .instance
+        .environment
+        .start_sequence(Sequence::new().during(0.5, record).then(tick));
+    runner.run_frames(3).unwrap();
+    assert_eq!(recorded(&runner), [0.25, 0.5, 0.75]);
+    assert_eq!(ticks(&runner), 0);
+    runner.run_frames(1).unwrap();
+    // The step after it runs in the same frame
+    assert_eq!(recorded(&runner), [0.25, 0.5, 0.75, 1.0]);
+    assert_eq!(ticks(&runner), 1);
+
+    // A frame longer than the whole step still gets its 1
+    let mut runner = self::runner(1.0);
+    runner.instance.environment.start_sequence(Sequence::new().during(0.5, record));
+    runner.run_frames(2).unwrap();
+    assert_eq!(recorded(&runner), [1.0]);
+}
+
+#[test]
+fn looping_sequences_start_over() {
+    let mut runner = runner(0.125);
+    let environment = &mut runner.instance.environment;
+    let waiting = environment.start_sequence(Sequence::new().wait(0.25).then(tick).looping());
+    // Nothing to wait on, so it goes round once a frame instead of forever
+    environment.start_sequence(Sequence::new().then(tock).looping());
+    runner.run_frames(8).unwrap();
+    assert_eq!((ticks(&runner), counted(&runner, "tocks")), (4, 8));
+    assert!(runner.instance.environment.is_sequence_running(waiting));
+}
+
+fn start_another(instance: &mut Instance2D) {
+    let id = instance.environment.start_sequence(Sequence::new().then(tock));
+    // Runs from next frame but already counts as running
+    assert!(instance.environment.is_sequence_running(id));
+    RUNNING.with(|running| running.set(Some(id)));
+}
+
+#[test]
+fn sequences_are_running_until_they_finish_or_are_stopped() {
+    let mut runner = runner(0.25);
+    let environment = &mut runner.instance.environment;
+    let short = environment.start_sequence(Sequence::new().then(tick));
+    let long = environment.start_sequence(Sequence::new().wait(10.0).then(tick));
+    let starter = environment.start_sequence(Sequence::new().then(start_another));
+    assert!(environment.is_sequence_running(short));
+
+    runner.run_frames(1).unwrap();
+    let environment = &mut runner.instance.environment;
+    assert!(!environment.is_sequence_running(short));
+    assert!(!environment.stop_sequence(short));
+    assert!(!environment.is_sequence_running(starter));
+    let started = RUNNING.with(|running| running.get()).unwrap();
+    assert!(environment.is_sequence_running(started));
+    assert_eq!(counted(&runner, "tocks"), 0);
+
+    runner.run_frames(1).unwrap();
+    assert_eq!(counted(&runner, "tocks"), 1);
+    assert!(!runner.instance.environment.is_sequence_running(started));
+
+    let environment = &mut runner.instance.environment;
+    assert!(environment.is_sequence_running(long));
+    assert!(environment.stop_sequence(long));
+    assert!(!environment.is_sequence_running(long));
+    runner.run_frames(1).unwrap();
+    assert_eq!(ticks(&runner), 1);
+}
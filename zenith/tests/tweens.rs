@@ -0,0 +1,210 @@
+// Easing curves and how tweens play, repeat, yoyo and chain.
+
+use zenith::*;
+
+const EASINGS: [Easing; 16] = [
+    Easing::Linear,
+    Easing::QuadIn,
+    Easing::QuadOut,
+    Easing::QuadInOut,
+    Easing::CubicIn,
+    Easing::CubicOut,
+    Easing::CubicInOut,
+    Easing::ElasticIn,
+    Easing::ElasticOut,
+    Easing::ElasticInOut,
+    Easing::BounceIn,
+    Easing::BounceOut,
+    Easing::BounceInOut,
+    Easing::BackIn,
+    Easing::BackOut,
+    Easing::BackInOut,
+];
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-5
+}
+
+#[test]
+fn every_easing_starts_at_0_and_ends_at_1() {
+    for easing in EASINGS {
+        assert!(close(easing.ease(0.0), 0.0), "{:?} at 0 is {}", easing, easing.ease(0.0));
+        assert!(close(easing.ease(1.0), 1.0), "{:?} at 1 is {}", easing, easing.ease(1.0));
+        // Outside of 0 to 1 is clamped
+        assert_eq!(easing.ease(-1.0), easing.ease(0.0), "{:?}", easing);
+        assert_eq!(easing.ease(2.0), easing.ease(1.0), "{:?}", easing);
+    }
+}
+
+fn runner(delta_time: f32) -> HeadlessRunner {
+    let mut runner = HeadlessRunner::new();
+    runner.instance.engine_settings.fixed_delta_time = Some(delta_time);
+    runner
+}
+
+fn float(runner: &HeadlessRunner, name: &str) -> f32 {
+    runner
+        .instance
+        .environment
+        .get_resource(name)
+        .and_then(|tag| tag.extract_float())
+        .unwrap()
+}
+
+// The value of the resource after each frame
+fn play(runner: &mut HeadlessRunner, name: &str, frames: u32) -> Vec<f32> {
+    (0..frames)
+        .map(|_| {
+            runner.run_frames(1).unwrap();
+            float(runner, name)
+        })
+        .collect()
+}
+
+fn done(instance: &mut Instance2D) {
+    let before = instance.environment.get_resource("done").and_then(|tag| tag.extract_int()).unwrap_or(0);
+    instance.environment.set_resource("done", TagValue::Int(before + 1));
+}
+
+fn times_done(runner: &HeadlessRunner) -> i32 {
+    runner
+        .instance
+        .environment
+        .get_resource("done")
+        .and_then(|tag| tag.extract_int())
+        .unwrap_or(0)
+}
+
+#[test]
+fn yoyo_goes_there_and_back_as_one_play() {
+    let mut runner = runner(0.125);
+    let environment = &mut runner.instance.environment;
+    environment.set_resource("x", TagValue::Float(0.0));
+    let id = environment.add_tween(
+        Tween::resource("x", TagValue::Float(1.0), 0.5)
+            .with_yoyo()
+            .with_on_complete(done),
+    );
+    assert_eq!(play(&mut runner, "x", 7), [0.25, 0.5, 0.75, 1.0, 0.75, 0.5, 0.25]);
+    assert!(runner.instance.environment.is_tween_running(id));
+    assert_eq!(play(&mut runner, "x", 2), [0.0, 0.0]);
+    assert!(!runner.instance.environment.is_tween_running(id));
+    assert_eq!(times_done(&runner), 1);
+}
+
+#[test]
+fn repeats_play_again_from_the_start() {
+    let mut runner = runner(0.125);
+    let environment = &mut runner.instance.environment;
+    environment.set_resource("x", TagValue::Float(0.0));
+    let id = environment.add_tween(
+        Tween::resource("x", TagValue::Float(1.0), 0.25)
+            .with_repeat(2)
+            .with_on_complete(done),
+    );
+    // Each play ends on 1.0 and the next one starts back at 0.0 in the same frame
+    assert_eq!(play(&mut runner, "x", 5), [0.5, 0.0, 0.5, 0.0, 0.5]);
+    assert_eq!(times_done(&runner), 0);
+    assert_eq!(play(&mut runner, "x", 1), [1.0]);
+    assert_eq!(times_done(&runner), 1);
+    assert!(!runner.instance.environment.is_tween_running(id));
+
+    // A frame that covers several plays gets through all of them
+    let mut runner = self::runner(0.875);
+    runner.instance.environment.set_resource("x", TagValue::Float(0.0));
+    runner.instance.environment.add_tween(
+        Tween::resource("x", TagValue::Float(1.0), 0.25)
+            .with_repeat(2)
+            .with_yoyo()
+            .with_on_complete(done),
+    );
+    assert_eq!(play(&mut runner, "x", 1), [0.5]);
+    assert_eq!(play(&mut runner, "x", 1), [0.0]);
+    assert_eq!(times_done(&runner), 1);
+}
+
+#[test]
+fn repeating_forever_never_completes() {
+    let mut runner = runner(0.125);
+    let environment = &mut runner.instance.environment;
+    environment.set_resource("x", TagValue::Float(0.0));
+    let id = environment.add_tween(
+        Tween::resource("x", TagValue::Float(1.0), 0.25)
+            .with_repeat_forever()
+            .with_on_complete(done),
+    );
+    assert_eq!(play(&mut runner, "x", 4), [0.5, 0.0, 0.5, 0.0]);
+    assert!(runner.instance.environment.is_tween_running(id));
+    assert_eq!(times_done(&runner), 0);
+}
+
+#[test]
+fn zero_second_tweens_still_repeat() {
+    let mut runner = runner(0.125);
+    let environment = &mut runner.instance.environment;
+    environment.set_resource("forever", TagValue::Float(0.0));
+    environment.set_resource("twice", TagValue::Float(0.0));
+    environment.set_resource("yoyo", TagValue::Float(0.0));
+    let forever = environment.add_tween(Tween::resource("forever", TagValue::Float(1.0), 0.0).with_repeat_forever());
+    let twice = environment.add_tween(
+        Tween::resource("twice", TagValue::Float(1.0), 0.0)
+            .with_repeat(1)
+            .with_on_complete(done),
+    );
+    environment.add_tween(Tween::resource("yoyo", TagValue::Float(1.0), 0.0).with_yoyo());
+
+    runner.run_frames(1).unwrap();
+    assert_eq!(float(&runner, "forever"), 1.0);
+    assert_eq!(float(&runner, "twice"), 1.0);
+    // Went there and came straight back
+    assert_eq!(float(&runner, "yoyo"), 0.0);
+    assert_eq!(times_done(&runner), 0);
+    runner.run_frames(3).unwrap();
+    assert_eq!(times_done(&runner), 1);
+    assert!(!runner.instance.environment.is_tween_running(twice));
+    assert!(runner.instance.environment.is_tween_running(forever));
+}
+
+#[test]
+fn chained_tweens_carry_over_the_time_left() {
+    let mut runner = runner(0.25);
+    let environment = &mut runner.instance.environment;
+    environment.set_resource("a", TagValue::Float(0.0));
+    environment.set_resource("b", TagValue::Float(0.0));
+    let id = environment.add_tween(
+        Tween::resource("a", TagValue::Float(1.0), 0.375)
+            .then(Tween::resource("b", TagValue::Float(1.0), 0.5).with_on_complete(done))
+            .then(Tween::resource("a", TagValue::Float(0.0), 0.25)),
+    );
+
+    runner.run_frames(2).unwrap();
+    // a needed 0.375 of the 0.5, b gets the other 0.125
+    assert_eq!((float(&runner, "a"), float(&runner, "b")), (1.0, 0.25));
+    runner.run_frames(2).unwrap();
+    assert_eq!((float(&runner, "a"), float(&runner, "b")), (0.5, 1.0));
+    assert_eq!(times_done(&runner), 1);
+    runner.run_frames(1).unwrap();
+    assert_eq!(float(&runner, "a"), 0.0);
+    assert!(!runner.instance.environment.is_tween_running(id));
+}
+
+#[test]
+fn easing_shapes_the_value() {
+    let mut runner = runner(0.25);
+    let environment = &mut runner.instance.environment;
+    let id = environment.add_entity(Entity::new().with_tag("location", TagValue::Vec2(Vec2::new(0, 0))));
+    environment.add_tween(
+        Tween::tag(id, "location", TagValue::Vec2(Vec2::new(100, 0)), 1.0).with_easing(Easing::QuadIn),
+    );
+    let x = |runner: &HeadlessRunner| {
+        let entity = runner.instance.environment.get_entity_by_id(id).unwrap();
+        entity.get_tag("location").and_then(|tag| tag.extract_vec2()).unwrap().x
+    };
+    let xs: Vec<i32> = (0..4)
+        .map(|_| {
+            runner.run_frames(1).unwrap();
+            x(&runner)
+        })
+        .collect();
+    assert_eq!(xs, [6, 25, 56, 100]);
+}